@@ -3,6 +3,70 @@
 #include <stdint.h>
 #include <stdlib.h>
 
+/**
+ * message types accepted by opaque_envelope_encode and opaque_envelope_decode
+ */
+#define OPAQUE_MSG_REGISTRATION_REQUEST 1
+
+#define OPAQUE_MSG_REGISTRATION_RESPONSE 2
+
+#define OPAQUE_MSG_REGISTRATION_UPLOAD 3
+
+#define OPAQUE_MSG_CREDENTIAL_REQUEST 4
+
+#define OPAQUE_MSG_CREDENTIAL_RESPONSE 5
+
+#define OPAQUE_MSG_CREDENTIAL_FINALIZATION 6
+
+/**
+ * normalizations accepted by opaque_set_password_normalization
+ * and opaque_normalize_password
+ */
+#define OPAQUE_PASSWORD_NORMALIZATION_NONE 1
+
+#define OPAQUE_PASSWORD_NORMALIZATION_NFC 2
+
+#define OPAQUE_PASSWORD_NORMALIZATION_NFKC 3
+
+/**
+ * issues set in OpaquePasswordCheck by opaque_password_check
+ */
+#define OPAQUE_PASSWORD_TOO_SHORT 1
+
+#define OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES 2
+
+#define OPAQUE_PASSWORD_COMMON 4
+
+#define OPAQUE_PASSWORD_CONTAINS_USERNAME 8
+
+#define OPAQUE_PASSWORD_REPETITIVE 16
+
+/**
+ * server side of the protocol in a single object: it owns the server setup,
+ * the credential store and the login states between the two login steps,
+ * so the caller only passes messages around
+ */
+typedef struct OpaqueServer OpaqueServer;
+
+/**
+ * client side of the protocol in a single object: it keeps password
+ * and state between begin and complete of registration and login,
+ * wiping them when the step completes or the client is dropped
+ */
+typedef struct OpaqueClient OpaqueClient;
+
+/**
+ * one side of a protected channel: seal what goes to the other side,
+ * open what comes from it, refusing replayed and too old records
+ */
+typedef struct SecureChannel SecureChannel;
+
+/**
+ * identities and context shared by the four registration and login steps:
+ * client and server must use equal configurations
+ */
+typedef struct ProtocolConfig ProtocolConfig;
+
 /**
  * struct needed to return the client or server
  * state allowing remove of redis usage
@@ -116,6 +180,169 @@ typedef struct ServerLogState {
   uintptr_t size_state;
 } ServerLogState;
 
+/**
+ * struct needed to pass the key-encryption key
+ * used to seal password files and server setups:
+ * id is stored inside the sealed record to allow KEK rotation
+ */
+typedef struct KeyEncryptionKey {
+  uint32_t id;
+  const uint8_t *key;
+  uintptr_t size_key;
+} KeyEncryptionKey;
+
+/**
+ * struct needed to return the result of
+ * opaque_client_complete_registration
+ */
+typedef struct OpaqueRegistrationResult {
+  const uint8_t *upload;
+  uintptr_t size_upload;
+  const uint8_t *export_key;
+  uintptr_t size_export_key;
+  const uint8_t *server_public_key;
+  uintptr_t size_server_public_key;
+} OpaqueRegistrationResult;
+
+/**
+ * struct needed to return the result of
+ * opaque_client_complete_login
+ */
+typedef struct OpaqueLoginResult {
+  const uint8_t *credential_finalization;
+  uintptr_t size_credential_finalization;
+  const uint8_t *session_key;
+  uintptr_t size_session_key;
+  const uint8_t *export_key;
+  uintptr_t size_export_key;
+  const uint8_t *server_public_key;
+  uintptr_t size_server_public_key;
+} OpaqueLoginResult;
+
+/**
+ * struct needed to return the result of
+ * opaque_recovery_codes_generate
+ */
+typedef struct OpaqueRecoveryCodes {
+  const uint8_t *codes;
+  uintptr_t size_codes;
+  const uint8_t *records;
+  uintptr_t size_records;
+} OpaqueRecoveryCodes;
+
+/**
+ * struct needed to return the result of
+ * opaque_client_begin_password_change
+ */
+typedef struct OpaquePasswordChangeRequest {
+  const uint8_t *credential_request;
+  uintptr_t size_credential_request;
+  const uint8_t *registration_request;
+  uintptr_t size_registration_request;
+} OpaquePasswordChangeRequest;
+
+/**
+ * struct needed to return the result of
+ * opaque_server_handle_password_change_request
+ */
+typedef struct OpaquePasswordChangeResponse {
+  const uint8_t *credential_response;
+  uintptr_t size_credential_response;
+  const uint8_t *registration_response;
+  uintptr_t size_registration_response;
+  const uint8_t *wrapped_secret;
+  uintptr_t size_wrapped_secret;
+} OpaquePasswordChangeResponse;
+
+/**
+ * struct needed to return the result of
+ * opaque_client_complete_password_change
+ */
+typedef struct OpaquePasswordChangeResult {
+  const uint8_t *credential_finalization;
+  uintptr_t size_credential_finalization;
+  const uint8_t *upload;
+  uintptr_t size_upload;
+  const uint8_t *wrapped_secret;
+  uintptr_t size_wrapped_secret;
+  const uint8_t *tag;
+  uintptr_t size_tag;
+  const uint8_t *session_key;
+  uintptr_t size_session_key;
+  const uint8_t *export_key;
+  uintptr_t size_export_key;
+  const uint8_t *recovery_secret;
+  uintptr_t size_recovery_secret;
+} OpaquePasswordChangeResult;
+
+/**
+ * struct needed to return the result of
+ * opaque_client_begin_recovery
+ */
+typedef struct OpaqueRecoveryRequest {
+  const uint8_t *verifier;
+  uintptr_t size_verifier;
+  const uint8_t *registration_request;
+  uintptr_t size_registration_request;
+} OpaqueRecoveryRequest;
+
+/**
+ * struct needed to return the result of
+ * opaque_server_handle_recovery_request
+ */
+typedef struct OpaqueRecoveryResponse {
+  const uint8_t *wrapped_secret;
+  uintptr_t size_wrapped_secret;
+  const uint8_t *registration_response;
+  uintptr_t size_registration_response;
+} OpaqueRecoveryResponse;
+
+/**
+ * struct needed to return the result of
+ * opaque_client_complete_recovery
+ */
+typedef struct OpaqueRecoveryResult {
+  const uint8_t *verifier;
+  uintptr_t size_verifier;
+  const uint8_t *upload;
+  uintptr_t size_upload;
+  const uint8_t *wrapped_secret;
+  uintptr_t size_wrapped_secret;
+  const uint8_t *export_key;
+  uintptr_t size_export_key;
+  const uint8_t *secret;
+  uintptr_t size_secret;
+} OpaqueRecoveryResult;
+
+/**
+ * struct needed to return the result of
+ * opaque_channel_open
+ */
+typedef struct OpaqueChannelOpenResult {
+  /**
+   * false if the record was refused
+   */
+  bool ok;
+  const uint8_t *plaintext;
+  uintptr_t size_plaintext;
+} OpaqueChannelOpenResult;
+
+/**
+ * struct needed to return the result of
+ * opaque_password_check
+ */
+typedef struct OpaquePasswordCheck {
+  /**
+   * OPAQUE_PASSWORD_* issues found, 0 if the password is acceptable
+   */
+  uint32_t issues;
+  /**
+   * 0 (very weak) to 4 (very strong)
+   */
+  uint8_t score;
+  uint32_t entropy_bits;
+} OpaquePasswordCheck;
+
 /**
  * function to deallocate Box pointers previously passed to C:
  * MUST be called after used the pointed value in C
@@ -125,13 +352,15 @@ void free_memlib(const uint8_t *ptr);
 /**
  * first step of opaque registration: client registration start
  * password: password typed by client
+ * empty if the policy of opaque_set_default_password_policy refuses password
  */
 struct OpaqueWithState opaque_client_registration_start(const char *password);
 
 /**
  * second step of opaque registration: server registration start
  * registration_request: result of client registration start
- * private_key: hexadecimal value of a 32 bytes private key
+ * private_key: hexadecimal value of a 32 bytes private key, or empty
+ * (data can be NULL) for a new random one
  */
 struct OpaqueWithSetup opaque_server_registration_start(const char *username,
                                                         struct ClientRegStartResult registration_request,
@@ -141,6 +370,8 @@ struct OpaqueWithSetup opaque_server_registration_start(const char *username,
  * third step of opaque registration: client registration finish
  * server_registration_start: result of server registration start
  * client_reg_start_state: result of client registration start
+ * empty if the policy of opaque_set_default_password_policy refuses
+ * password for username
  */
 struct Opaque opaque_client_registration_finish(const char *password,
                                                 struct ServerRegStartResult server_registration_start,
@@ -150,7 +381,8 @@ struct Opaque opaque_client_registration_finish(const char *password,
 
 /**
  * fourth step of opaque registration: server registration finish
- * message: result of client registration finish
+ * message: result of client registration finish, the identities it
+ * was made with are checked only by opaque_server_registration_finish_with_config
  */
 struct Opaque opaque_server_registration_finish(struct Opaque message);
 
@@ -185,9 +417,582 @@ struct Opaque opaque_client_login_finish(const char *password,
                                          const char *servername,
                                          const char *context);
 
+/**
+ * same as opaque_server_login_start, with a binary context:
+ * context: e.g. result of opaque_channel_binding_context,
+ * the client MUST use the same one to finish the login
+ * every field is empty on error
+ */
+struct OpaqueWithState opaque_server_login_start_with_context(const char *username,
+                                                              struct Opaque password_file,
+                                                              struct ClientLogStartResult credential_request,
+                                                              struct ServerSetup serv_setup,
+                                                              const char *servername,
+                                                              struct Opaque context);
+
+/**
+ * same as opaque_client_login_finish, with a binary context:
+ * login fails if it isn't the one used by the server
+ * context: e.g. result of opaque_channel_binding_context
+ */
+struct Opaque opaque_client_login_finish_with_context(const char *password,
+                                                      struct ServerLogStartResult login_response,
+                                                      struct ClientLogState client_login_state,
+                                                      const char *username,
+                                                      const char *servername,
+                                                      struct Opaque context);
+
 /**
  * fourth step of opaque login: server login finish
- * server_login_state: result of server login start
+ * server_login_state: result of server login start,
+ * refused if older than LOGIN_STATE_MAX_AGE seconds (default 300)
  */
 bool opaque_server_login_finish(struct Opaque credential_finalization,
                                 struct ServerLogState server_login_state);
+
+/**
+ * fourth step of opaque login: server login finish with custom checks
+ * max_age_seconds: server login state older than this is refused
+ * single_use: refuse a server login state already used in this process
+ */
+bool opaque_server_login_finish_with_policy(struct Opaque credential_finalization,
+                                            struct ServerLogState server_login_state,
+                                            uint64_t max_age_seconds,
+                                            bool single_use);
+
+/**
+ * seal a password file before storing it
+ * kek: 32 bytes key-encryption key
+ * username: owner of the password file, bound to the sealed record
+ * password_file: result of server registration finish
+ */
+struct Opaque opaque_seal_password_file(struct KeyEncryptionKey kek,
+                                        const char *username,
+                                        struct Opaque password_file);
+
+/**
+ * unseal a password file previously sealed with opaque_seal_password_file
+ * sealed: result of opaque_seal_password_file
+ */
+struct Opaque opaque_unseal_password_file(struct KeyEncryptionKey kek,
+                                          const char *username,
+                                          struct Opaque sealed);
+
+/**
+ * seal a server setup before storing it
+ * kek: 32 bytes key-encryption key
+ * servername: server identity, bound to the sealed record
+ * serv_setup: result of server registration start
+ */
+struct Opaque opaque_seal_server_setup(struct KeyEncryptionKey kek,
+                                       const char *servername,
+                                       struct ServerSetup serv_setup);
+
+/**
+ * unseal a server setup previously sealed with opaque_seal_server_setup
+ * sealed: result of opaque_seal_server_setup
+ */
+struct Opaque opaque_unseal_server_setup(struct KeyEncryptionKey kek,
+                                         const char *servername,
+                                         struct Opaque sealed);
+
+/**
+ * return the id of the KEK used to seal a record,
+ * or -1 if the record is malformed
+ */
+int64_t opaque_sealed_kek_id(struct Opaque sealed);
+
+/**
+ * second step of opaque login without server side session storage:
+ * same as opaque_server_login_start, but the returned state is a token
+ * sealed under kek that the client sends back with its login finish result,
+ * response and token are both empty if the login start failed
+ * ttl_seconds: validity of the token
+ */
+struct OpaqueWithState opaque_server_login_start_stateless(struct KeyEncryptionKey kek,
+                                                           uint64_t ttl_seconds,
+                                                           const char *username,
+                                                           struct Opaque password_file,
+                                                           struct ClientLogStartResult credential_request,
+                                                           struct ServerSetup serv_setup,
+                                                           const char *servername,
+                                                           const char *context);
+
+/**
+ * fourth step of opaque login without server side session storage
+ * credential_finalization: result of client login finish
+ * token: state returned by opaque_server_login_start_stateless, accepted
+ * until the ttl given at login start and only once in this process
+ */
+bool opaque_server_login_finish_stateless(struct KeyEncryptionKey kek,
+                                          const char *username,
+                                          struct Opaque credential_finalization,
+                                          struct Opaque token);
+
+/**
+ * create an OpaqueServer, which owns server setup, stored password files
+ * and login states: MUST be released with opaque_server_free
+ * serv_setup: result of server registration start, or empty to use
+ * the setup saved in the store (generating it the first time)
+ * store_path: file where password files are saved, or NULL to keep them in memory
+ * returns NULL on error
+ */
+struct OpaqueServer *opaque_server_new(struct ServerSetup serv_setup,
+                                       const char *servername,
+                                       const char *context,
+                                       const char *store_path);
+
+/**
+ * release an OpaqueServer created with opaque_server_new
+ */
+void opaque_server_free(struct OpaqueServer *server);
+
+/**
+ * first server step of registration with OpaqueServer:
+ * fails if username already has a password file
+ * registration_request: result of client registration start
+ */
+struct Opaque opaque_server_handle_registration_request(const struct OpaqueServer *server,
+                                                        const char *username,
+                                                        struct ClientRegStartResult registration_request);
+
+/**
+ * last server step of registration with OpaqueServer:
+ * the password file is saved in the server store, failing if username
+ * already has one: only a password change can replace it
+ * upload: result of client registration finish
+ */
+bool opaque_server_handle_registration_upload(const struct OpaqueServer *server,
+                                              const char *username,
+                                              struct Opaque upload);
+
+/**
+ * first server step of login with OpaqueServer:
+ * the login state is kept by the server under session_id
+ * credential_request: result of client login start
+ */
+struct Opaque opaque_server_handle_credential_request(const struct OpaqueServer *server,
+                                                      const char *username,
+                                                      const char *session_id,
+                                                      struct ClientLogStartResult credential_request);
+
+/**
+ * last server step of login with OpaqueServer
+ * credential_finalization: result of client login finish
+ * returns the session key, empty if login failed
+ */
+struct Opaque opaque_server_handle_credential_finalization(const struct OpaqueServer *server,
+                                                           const char *username,
+                                                           const char *session_id,
+                                                           struct Opaque credential_finalization);
+
+/**
+ * create an OpaqueClient, which keeps password and state between
+ * begin and complete steps: MUST be released with opaque_client_free
+ */
+struct OpaqueClient *opaque_client_new(const char *username,
+                                       const char *servername,
+                                       const char *context);
+
+/**
+ * release an OpaqueClient created with opaque_client_new,
+ * wiping password and in-flight state
+ */
+void opaque_client_free(struct OpaqueClient *client);
+
+/**
+ * first client step of registration with OpaqueClient
+ * returns the registration request for the server
+ */
+struct Opaque opaque_client_begin_registration(struct OpaqueClient *client, const char *password);
+
+/**
+ * last client step of registration with OpaqueClient
+ * registration_response: server answer to the registration request
+ * every field is empty on error
+ */
+struct OpaqueRegistrationResult opaque_client_complete_registration(struct OpaqueClient *client,
+                                                                    struct ServerRegStartResult registration_response);
+
+/**
+ * first client step of login with OpaqueClient
+ * returns the credential request for the server
+ */
+struct Opaque opaque_client_begin_login(struct OpaqueClient *client, const char *password);
+
+/**
+ * last client step of login with OpaqueClient
+ * credential_response: server answer to the credential request
+ * every field is empty if login failed
+ */
+struct OpaqueLoginResult opaque_client_complete_login(struct OpaqueClient *client,
+                                                      struct ServerLogStartResult credential_response);
+
+/**
+ * put a protocol message inside a self-describing envelope
+ * (magic, version, message type, ciphersuite id, length)
+ * message_type: one of the OPAQUE_MSG_* values
+ * message: result of any registration or login step
+ */
+struct Opaque opaque_envelope_encode(uint8_t message_type, struct Opaque message);
+
+/**
+ * give back the protocol message inside an envelope,
+ * empty if it isn't the expected message type or ciphersuite
+ * expected_type: one of the OPAQUE_MSG_* values
+ */
+struct Opaque opaque_envelope_decode(uint8_t expected_type, struct Opaque envelope);
+
+/**
+ * message type (one of the OPAQUE_MSG_* values) held by an envelope,
+ * or -1 if data isn't a valid envelope
+ */
+int32_t opaque_envelope_message_type(struct Opaque envelope);
+
+/**
+ * first client step of a password change with OpaqueClient: login with
+ * current_password and registration of new_password at the same time
+ * every field is empty on error
+ */
+struct OpaquePasswordChangeRequest opaque_client_begin_password_change(struct OpaqueClient *client,
+                                                                       const char *current_password,
+                                                                       const char *new_password);
+
+/**
+ * server step of a password change with OpaqueServer, keeping
+ * the login state under session_id until the finalization
+ * credential_request, registration_request: result of client password change start
+ * every field is empty on error, wrapped_secret also while the recovery
+ * secret of the user is still the one derived from the password
+ */
+struct OpaquePasswordChangeResponse opaque_server_handle_password_change_request(const struct OpaqueServer *server,
+                                                                                 const char *username,
+                                                                                 const char *session_id,
+                                                                                 struct Opaque credential_request,
+                                                                                 struct Opaque registration_request);
+
+/**
+ * last client step of a password change with OpaqueClient
+ * credential_response, registration_response, wrapped_secret:
+ * server answer to the request
+ * recovery_secret: the secret of the user, the same one as before the change
+ * every field is empty if the current password is wrong or wrapped_secret
+ * doesn't open under it
+ */
+struct OpaquePasswordChangeResult opaque_client_complete_password_change(struct OpaqueClient *client,
+                                                                         struct Opaque credential_response,
+                                                                         struct Opaque registration_response,
+                                                                         struct Opaque wrapped_secret);
+
+/**
+ * last server step of a password change with OpaqueServer: the password
+ * file of username is replaced only if the login succeeds and upload
+ * is bound to its session key
+ * credential_finalization, upload, wrapped_secret, tag:
+ * result of client password change finish
+ * returns the session key, empty if the password wasn't changed
+ */
+struct Opaque opaque_server_handle_password_change_finalization(const struct OpaqueServer *server,
+                                                                const char *username,
+                                                                const char *session_id,
+                                                                struct Opaque credential_finalization,
+                                                                struct Opaque upload,
+                                                                struct Opaque wrapped_secret,
+                                                                struct Opaque tag);
+
+/**
+ * same as opaque_server_handle_registration_upload, keeping next to
+ * the password file the records of the recovery codes of the user
+ * records: result of opaque_recovery_codes_generate
+ */
+bool opaque_server_handle_registration_upload_with_recovery(const struct OpaqueServer *server,
+                                                            const char *username,
+                                                            struct Opaque upload,
+                                                            struct Opaque records);
+
+/**
+ * recovery secret of username wrapped under the export key of the current
+ * password, for opaque_recovery_secret_from_export_key: send it to the
+ * client only after a successful login
+ * returns an empty value if the secret is still derived from the export key
+ */
+struct Opaque opaque_server_recovery_wrapped_secret(const struct OpaqueServer *server,
+                                                    const char *username);
+
+/**
+ * first client step of a recovery with OpaqueClient: code is one of the
+ * recovery codes of the user, new_password the one to register in place
+ * of the forgotten one
+ * every field is empty on error
+ */
+struct OpaqueRecoveryRequest opaque_client_begin_recovery(struct OpaqueClient *client,
+                                                          const char *code,
+                                                          const char *new_password);
+
+/**
+ * server step of a recovery with OpaqueServer: the code is not used yet
+ * verifier, registration_request: result of client recovery start
+ * every field is empty if username has no such recovery code
+ */
+struct OpaqueRecoveryResponse opaque_server_handle_recovery_request(const struct OpaqueServer *server,
+                                                                    const char *username,
+                                                                    struct Opaque verifier,
+                                                                    struct Opaque registration_request);
+
+/**
+ * last client step of a recovery with OpaqueClient
+ * wrapped_secret, registration_response: server answer to the request
+ * secret: the secret of the user, the same one as before the recovery
+ * every field is empty if the code doesn't open the secret
+ */
+struct OpaqueRecoveryResult opaque_client_complete_recovery(struct OpaqueClient *client,
+                                                            struct Opaque wrapped_secret,
+                                                            struct Opaque registration_response);
+
+/**
+ * last server step of a recovery with OpaqueServer: the recovery code is
+ * removed and the password file of username replaced in a single write
+ * verifier, upload, wrapped_secret: result of client recovery finish
+ * returns false if the code is invalid or was already used
+ */
+bool opaque_server_handle_recovery_finalization(const struct OpaqueServer *server,
+                                                const char *username,
+                                                struct Opaque verifier,
+                                                struct Opaque upload,
+                                                struct Opaque wrapped_secret);
+
+/**
+ * secret to encrypt client data with, instead of the export key:
+ * it survives password changes and recoveries
+ * export_key: export key of the first registration
+ */
+struct Opaque opaque_recovery_secret(struct Opaque export_key);
+
+/**
+ * new recovery codes for username, each one wrapping secret
+ * secret: result of opaque_recovery_secret or opaque_recovery_unwrap_secret
+ * codes: one code per line, to show to the user once
+ * records: to be kept by the server next to the password file
+ */
+struct OpaqueRecoveryCodes opaque_recovery_codes_generate(struct Opaque secret,
+                                                          const char *username,
+                                                          uint32_t count);
+
+/**
+ * value sent to the server to prove knowledge of a recovery code
+ * code: as typed by the user, case and dashes don't matter
+ */
+struct Opaque opaque_recovery_verifier(const char *code, const char *username);
+
+/**
+ * server step of a recovery, before registering the new password, for
+ * servers keeping the records themselves (see opaque_server_handle_recovery_request)
+ * records: result of opaque_recovery_codes_generate
+ * verifier: result of opaque_recovery_verifier
+ * returns the wrapped secret for the client, empty if the code is invalid
+ */
+struct Opaque opaque_recovery_find(struct Opaque records, struct Opaque verifier);
+
+/**
+ * server step of a recovery, when the new password is registered, for servers
+ * keeping the records themselves (see opaque_server_handle_recovery_finalization):
+ * the code of verifier is removed, since every code is single use
+ * returns the records to store in place of the old ones,
+ * empty if the code is invalid (the new password MUST then be refused)
+ */
+struct Opaque opaque_recovery_take(struct Opaque records, struct Opaque verifier);
+
+/**
+ * client step of a recovery: the secret inside the wrapped
+ * secret returned by opaque_recovery_find, empty on error
+ */
+struct Opaque opaque_recovery_unwrap_secret(const char *code,
+                                            const char *username,
+                                            struct Opaque wrapped);
+
+/**
+ * secret wrapped under the export key of a new password,
+ * to be kept by the server next to the password file
+ */
+struct Opaque opaque_recovery_wrap_secret(struct Opaque export_key, struct Opaque secret);
+
+/**
+ * secret after a login: unwrapped with the export key if the password
+ * was changed or recovered, derived from it if wrapped is empty
+ * wrapped: result of opaque_server_recovery_wrapped_secret
+ * returns an empty value on error
+ */
+struct Opaque opaque_recovery_secret_from_export_key(struct Opaque export_key,
+                                                     struct Opaque wrapped);
+
+/**
+ * create a SecureChannel protecting messages with keys derived from the
+ * session key of a login: MUST be released with opaque_channel_free
+ * session_key: result of the last login step on this side
+ * is_server: which side of the login this channel belongs to
+ */
+struct SecureChannel *opaque_channel_new(struct Opaque session_key, bool is_server);
+
+/**
+ * release a SecureChannel created by opaque_channel_new
+ */
+void opaque_channel_free(struct SecureChannel *channel);
+
+/**
+ * protect plaintext for the other side of the channel
+ * aad: authenticated but not sent, the other side must pass the same value
+ * aad and plaintext can be empty, with data NULL
+ * returns the record to send, empty on error
+ */
+struct Opaque opaque_channel_seal(struct SecureChannel *channel,
+                                  struct Opaque aad,
+                                  struct Opaque plaintext);
+
+/**
+ * plaintext of a record sealed by the other side of the channel
+ * aad: the value passed to opaque_channel_seal, can be empty with data NULL
+ * ok is false, with an empty plaintext, if the record is forged,
+ * replayed or too old
+ */
+struct OpaqueChannelOpenResult opaque_channel_open(struct SecureChannel *channel,
+                                                   struct Opaque aad,
+                                                   struct Opaque record);
+
+/**
+ * login context binding the login to the channel binding bytes
+ * of an outer channel, to pass as context on both sides
+ * label: type of binding, e.g. "tls-exporter" (RFC 9266)
+ * binding: the binding bytes, e.g. the TLS exporter value
+ */
+struct Opaque opaque_channel_binding_context(const char *label, struct Opaque binding);
+
+/**
+ * create a ProtocolConfig, holding the identities and the context to pass
+ * to every *_with_config step: client and server must use equal ones,
+ * MUST be released with opaque_protocol_config_free
+ * context: login context, e.g. result of opaque_channel_binding_context
+ */
+struct ProtocolConfig *opaque_protocol_config_new(const char *servername, struct Opaque context);
+
+/**
+ * release a ProtocolConfig created with opaque_protocol_config_new
+ */
+void opaque_protocol_config_free(struct ProtocolConfig *config);
+
+/**
+ * same as opaque_client_registration_finish, with the identities of config
+ * empty on error
+ */
+struct Opaque opaque_client_registration_finish_with_config(const struct ProtocolConfig *config,
+                                                            const char *password,
+                                                            struct ServerRegStartResult server_registration_start,
+                                                            struct ClientRegState client_reg_start_state,
+                                                            const char *username);
+
+/**
+ * same as opaque_server_registration_finish, but the password file records
+ * the identities of config: logins started with other identities
+ * are refused by every server login start
+ * empty on error, or if the client registered with other identities
+ */
+struct Opaque opaque_server_registration_finish_with_config(const struct ProtocolConfig *config,
+                                                            const char *username,
+                                                            struct Opaque message);
+
+/**
+ * same as opaque_server_login_start, with the identities and context of config:
+ * every field is empty on error, or if password_file was registered
+ * with other identities
+ */
+struct OpaqueWithState opaque_server_login_start_with_config(const struct ProtocolConfig *config,
+                                                             const char *username,
+                                                             struct Opaque password_file,
+                                                             struct ClientLogStartResult credential_request,
+                                                             struct ServerSetup serv_setup);
+
+/**
+ * same as opaque_client_login_finish, with the identities and context of config
+ */
+struct Opaque opaque_client_login_finish_with_config(const struct ProtocolConfig *config,
+                                                     const char *password,
+                                                     struct ServerLogStartResult login_response,
+                                                     struct ClientLogState client_login_state,
+                                                     const char *username);
+
+/**
+ * same as opaque_server_login_start_with_config, for a credential registered
+ * under credential_id (passed as username to the registration steps) and
+ * logging in as identity, e.g. an email address: the client must use the
+ * same identity, which can change without registering again
+ */
+struct OpaqueWithState opaque_server_login_start_with_identity(const struct ProtocolConfig *config,
+                                                               const char *credential_id,
+                                                               const char *identity,
+                                                               struct Opaque password_file,
+                                                               struct ClientLogStartResult credential_request,
+                                                               struct ServerSetup serv_setup);
+
+/**
+ * same as opaque_client_login_finish_with_config, for a credential
+ * registered under credential_id and logging in as identity
+ */
+struct Opaque opaque_client_login_finish_with_identity(const struct ProtocolConfig *config,
+                                                       const char *password,
+                                                       struct ServerLogStartResult login_response,
+                                                       struct ClientLogState client_login_state,
+                                                       const char *credential_id,
+                                                       const char *identity);
+
+/**
+ * same as opaque_server_handle_credential_request, for a credential
+ * registered under credential_id and logging in as identity
+ */
+struct Opaque opaque_server_handle_credential_request_with_identity(const struct OpaqueServer *server,
+                                                                    const char *credential_id,
+                                                                    const char *identity,
+                                                                    const char *session_id,
+                                                                    struct ClientLogStartResult credential_request);
+
+/**
+ * log the OpaqueClient in as identity from the next login on: its username
+ * is then the credential id, and the server must use the same identity
+ */
+void opaque_client_set_identity(struct OpaqueClient *client, const char *identity);
+
+/**
+ * set the normalization applied to passwords by every client function:
+ * one of the OPAQUE_PASSWORD_NORMALIZATION_* values, none by default;
+ * it MUST be the same at registration and login
+ * returns false, changing nothing, if normalization is unknown
+ */
+bool opaque_set_password_normalization(uint8_t normalization);
+
+/**
+ * password as normalized by the client functions with normalization
+ * (see opaque_set_password_normalization), UTF-8 without terminator:
+ * empty if normalization is unknown
+ */
+struct Opaque opaque_normalize_password(const char *password, uint8_t normalization);
+
+/**
+ * check of password for username (may be empty) with the default password
+ * policy: at least 8 characters of 2 kinds, not common, not containing
+ * the username; run it before opaque_client_registration_start, the server
+ * can't tell a weak password
+ */
+struct OpaquePasswordCheck opaque_password_check(const char *password, const char *username);
+
+/**
+ * make the OpaqueClient refuse registrations and password changes to
+ * passwords with issues under the default password policy
+ * (see opaque_password_check): the begin step then returns empty
+ */
+void opaque_client_set_default_password_policy(struct OpaqueClient *client);
+
+/**
+ * enabled: make opaque_client_registration_start, every registration finish
+ * and the OpaqueClients without a policy of their own refuse passwords with
+ * issues under the default password policy (see opaque_password_check),
+ * returning empty; false, the default, accepts every password
+ */
+void opaque_set_default_password_policy(bool enabled);
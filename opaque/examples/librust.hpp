@@ -0,0 +1,375 @@
+// C++20 wrapper of librust.h, header only. It covers the stateless
+// registration and login steps, password file sealing, envelopes and the
+// basic OpaqueServer and OpaqueClient steps; the rest of librust.h (configs,
+// password change, recovery, channels, password policy...) is not wrapped
+// yet and is called through the C API, which this header includes. Keep the
+// wrapped functions in sync when their signature in librust.h changes.
+//
+// Every buffer returned by the library is owned by an opaque::Buffer, which
+// calls free_memlib when destroyed, and every failure (empty result or false
+// in the C API) is thrown as an opaque::Error.
+
+#pragma once
+
+#include <cstdint>
+#include <span>
+#include <stdexcept>
+#include <string>
+#include <utility>
+#include <vector>
+
+extern "C" {
+#include "librust.h"
+}
+
+namespace opaque {
+
+using Bytes = std::span<const uint8_t>;
+
+class Error : public std::runtime_error {
+public:
+    using std::runtime_error::runtime_error;
+};
+
+// move-only owner of a buffer allocated by the library
+class Buffer {
+public:
+    Buffer() = default;
+    Buffer(const uint8_t *data, size_t size) : data_(data), size_(size) {}
+    explicit Buffer(::Opaque value) : Buffer(value.data, value.size) {}
+
+    Buffer(const Buffer &) = delete;
+    Buffer &operator=(const Buffer &) = delete;
+
+    Buffer(Buffer &&other) noexcept
+        : data_(std::exchange(other.data_, nullptr)), size_(std::exchange(other.size_, 0)) {}
+
+    Buffer &operator=(Buffer &&other) noexcept {
+        if (this != &other) {
+            reset();
+            data_ = std::exchange(other.data_, nullptr);
+            size_ = std::exchange(other.size_, 0);
+        }
+        return *this;
+    }
+
+    ~Buffer() { reset(); }
+
+    const uint8_t *data() const { return data_; }
+    size_t size() const { return size_; }
+    bool empty() const { return size_ == 0; }
+
+    Bytes span() const { return Bytes(data_, size_); }
+    operator Bytes() const { return span(); }
+
+    std::vector<uint8_t> to_vector() const { return std::vector<uint8_t>(data_, data_ + size_); }
+
+private:
+    // an empty result holds no allocation
+    void reset() {
+        if (data_ != nullptr && size_ > 0) {
+            free_memlib(data_);
+        }
+        data_ = nullptr;
+        size_ = 0;
+    }
+
+    const uint8_t *data_ = nullptr;
+    size_t size_ = 0;
+};
+
+// message for the other side and state to keep until the next step
+struct WithState {
+    Buffer data;
+    Buffer state;
+};
+
+// registration response and server setup to keep for the logins
+struct WithSetup {
+    Buffer data;
+    Buffer setup;
+};
+
+struct RegistrationResult {
+    Buffer upload;
+    Buffer export_key;
+    Buffer server_public_key;
+};
+
+struct LoginResult {
+    Buffer credential_finalization;
+    Buffer session_key;
+    Buffer export_key;
+    Buffer server_public_key;
+};
+
+namespace detail {
+
+inline ::Opaque opaque(Bytes value) { return ::Opaque{value.data(), value.size()}; }
+
+inline ::KeyEncryptionKey kek(uint32_t id, Bytes key) {
+    return ::KeyEncryptionKey{id, key.data(), key.size()};
+}
+
+inline Buffer check(::Opaque value, const char *what) {
+    Buffer result(value);
+    if (result.empty()) {
+        throw Error(what);
+    }
+    return result;
+}
+
+inline WithState check(::OpaqueWithState value, const char *what) {
+    WithState result{Buffer(value.data, value.size_data), Buffer(value.state, value.size_state)};
+    if (result.data.empty() || result.state.empty()) {
+        throw Error(what);
+    }
+    return result;
+}
+
+}  // namespace detail
+
+/// first step of opaque registration: client registration start
+inline WithState client_registration_start(const std::string &password) {
+    return detail::check(opaque_client_registration_start(password.c_str()),
+                         "client registration start failed");
+}
+
+/// second step of opaque registration: server registration start,
+/// with an empty private key a new key pair is generated
+inline WithSetup server_registration_start(const std::string &username, Bytes registration_request,
+                                           Bytes private_key = {}) {
+    ::OpaqueWithSetup value = opaque_server_registration_start(
+        username.c_str(),
+        ::ClientRegStartResult{registration_request.data(), registration_request.size()},
+        ::ServerRegPrivateKey{private_key.data(), private_key.size()});
+    WithSetup result{Buffer(value.data, value.size_data), Buffer(value.setup, value.size_setup)};
+    if (result.data.empty() || result.setup.empty()) {
+        throw Error("server registration start failed");
+    }
+    return result;
+}
+
+/// third step of opaque registration: client registration finish
+inline Buffer client_registration_finish(const std::string &password, Bytes registration_response,
+                                         Bytes client_state, const std::string &username,
+                                         const std::string &servername) {
+    return detail::check(
+        opaque_client_registration_finish(
+            password.c_str(),
+            ::ServerRegStartResult{registration_response.data(), registration_response.size()},
+            ::ClientRegState{client_state.data(), client_state.size()}, username.c_str(),
+            servername.c_str()),
+        "client registration finish failed");
+}
+
+/// fourth step of opaque registration: server registration finish,
+/// returning the password file
+inline Buffer server_registration_finish(Bytes upload) {
+    return detail::check(opaque_server_registration_finish(detail::opaque(upload)),
+                         "server registration finish failed");
+}
+
+/// first step of opaque login: client login start
+inline WithState client_login_start(const std::string &password) {
+    return detail::check(opaque_client_login_start(password.c_str()), "client login start failed");
+}
+
+/// second step of opaque login: server login start
+inline WithState server_login_start(const std::string &username, Bytes password_file,
+                                    Bytes credential_request, Bytes setup,
+                                    const std::string &servername, const std::string &context) {
+    return detail::check(
+        opaque_server_login_start(
+            username.c_str(), detail::opaque(password_file),
+            ::ClientLogStartResult{credential_request.data(), credential_request.size()},
+            ::ServerSetup{setup.data(), setup.size()}, servername.c_str(), context.c_str()),
+        "server login start failed");
+}
+
+/// third step of opaque login: client login finish,
+/// throws if the password is wrong or the server isn't the expected one
+inline Buffer client_login_finish(const std::string &password, Bytes login_response,
+                                  Bytes client_state, const std::string &username,
+                                  const std::string &servername, const std::string &context) {
+    return detail::check(
+        opaque_client_login_finish(
+            password.c_str(), ::ServerLogStartResult{login_response.data(), login_response.size()},
+            ::ClientLogState{client_state.data(), client_state.size()}, username.c_str(),
+            servername.c_str(), context.c_str()),
+        "client login failed");
+}
+
+/// fourth step of opaque login: server login finish, throws if the login failed
+inline void server_login_finish(Bytes credential_finalization, Bytes server_state) {
+    if (!opaque_server_login_finish(detail::opaque(credential_finalization),
+                                    ::ServerLogState{server_state.data(), server_state.size()})) {
+        throw Error("server login failed");
+    }
+}
+
+/// same as server_login_finish, refusing states older than max_age_seconds
+/// and, with single_use, states already used
+inline void server_login_finish(Bytes credential_finalization, Bytes server_state,
+                                uint64_t max_age_seconds, bool single_use) {
+    if (!opaque_server_login_finish_with_policy(
+            detail::opaque(credential_finalization),
+            ::ServerLogState{server_state.data(), server_state.size()}, max_age_seconds,
+            single_use)) {
+        throw Error("server login failed");
+    }
+}
+
+inline Buffer seal_password_file(uint32_t kek_id, Bytes kek, const std::string &username,
+                                 Bytes password_file) {
+    return detail::check(opaque_seal_password_file(detail::kek(kek_id, kek), username.c_str(),
+                                                   detail::opaque(password_file)),
+                         "password file sealing failed");
+}
+
+inline Buffer unseal_password_file(uint32_t kek_id, Bytes kek, const std::string &username,
+                                   Bytes sealed) {
+    return detail::check(opaque_unseal_password_file(detail::kek(kek_id, kek), username.c_str(),
+                                                     detail::opaque(sealed)),
+                         "password file unsealing failed");
+}
+
+inline Buffer envelope_encode(uint8_t message_type, Bytes message) {
+    return detail::check(opaque_envelope_encode(message_type, detail::opaque(message)),
+                         "envelope encoding failed");
+}
+
+/// message inside the envelope, throws if it isn't of expected_type
+inline Buffer envelope_decode(uint8_t expected_type, Bytes envelope) {
+    return detail::check(opaque_envelope_decode(expected_type, detail::opaque(envelope)),
+                         "envelope decoding failed");
+}
+
+/// OpaqueServer of the C API, freed when destroyed
+class Server {
+public:
+    /// in memory, or saved in the file at store_path; with an empty
+    /// setup the one in the store is used (or generated)
+    Server(Bytes setup, const std::string &servername, const std::string &context,
+           const char *store_path = nullptr)
+        : server_(opaque_server_new(::ServerSetup{setup.data(), setup.size()}, servername.c_str(),
+                                    context.c_str(), store_path)) {
+        if (server_ == nullptr) {
+            throw Error("server creation failed");
+        }
+    }
+
+    Server(const Server &) = delete;
+    Server &operator=(const Server &) = delete;
+    Server(Server &&other) noexcept : server_(std::exchange(other.server_, nullptr)) {}
+    Server &operator=(Server &&other) noexcept {
+        std::swap(server_, other.server_);
+        return *this;
+    }
+
+    ~Server() {
+        if (server_ != nullptr) {
+            opaque_server_free(server_);
+        }
+    }
+
+    Buffer handle_registration_request(const std::string &username, Bytes request) const {
+        return detail::check(opaque_server_handle_registration_request(
+                                 server_, username.c_str(),
+                                 ::ClientRegStartResult{request.data(), request.size()}),
+                             "registration request refused");
+    }
+
+    void handle_registration_upload(const std::string &username, Bytes upload) const {
+        if (!opaque_server_handle_registration_upload(server_, username.c_str(),
+                                                      detail::opaque(upload))) {
+            throw Error("registration upload refused");
+        }
+    }
+
+    Buffer handle_credential_request(const std::string &username, const std::string &session_id,
+                                     Bytes request) const {
+        return detail::check(opaque_server_handle_credential_request(
+                                 server_, username.c_str(), session_id.c_str(),
+                                 ::ClientLogStartResult{request.data(), request.size()}),
+                             "credential request refused");
+    }
+
+    /// session key, throws if the login failed
+    Buffer handle_credential_finalization(const std::string &username,
+                                          const std::string &session_id,
+                                          Bytes finalization) const {
+        return detail::check(
+            opaque_server_handle_credential_finalization(
+                server_, username.c_str(), session_id.c_str(), detail::opaque(finalization)),
+            "server login failed");
+    }
+
+private:
+    ::OpaqueServer *server_;
+};
+
+/// OpaqueClient of the C API, freed when destroyed
+class Client {
+public:
+    Client(const std::string &username, const std::string &servername,
+           const std::string &context)
+        : client_(opaque_client_new(username.c_str(), servername.c_str(), context.c_str())) {}
+
+    Client(const Client &) = delete;
+    Client &operator=(const Client &) = delete;
+    Client(Client &&other) noexcept : client_(std::exchange(other.client_, nullptr)) {}
+    Client &operator=(Client &&other) noexcept {
+        std::swap(client_, other.client_);
+        return *this;
+    }
+
+    ~Client() {
+        if (client_ != nullptr) {
+            opaque_client_free(client_);
+        }
+    }
+
+    Buffer begin_registration(const std::string &password) {
+        return detail::check(opaque_client_begin_registration(client_, password.c_str()),
+                             "client registration start failed");
+    }
+
+    RegistrationResult complete_registration(Bytes response) {
+        ::OpaqueRegistrationResult value = opaque_client_complete_registration(
+            client_, ::ServerRegStartResult{response.data(), response.size()});
+        RegistrationResult result{
+            Buffer(value.upload, value.size_upload),
+            Buffer(value.export_key, value.size_export_key),
+            Buffer(value.server_public_key, value.size_server_public_key)};
+        if (result.upload.empty()) {
+            throw Error("client registration finish failed");
+        }
+        return result;
+    }
+
+    Buffer begin_login(const std::string &password) {
+        return detail::check(opaque_client_begin_login(client_, password.c_str()),
+                             "client login start failed");
+    }
+
+    /// throws if the password is wrong or the server isn't the expected one
+    LoginResult complete_login(Bytes response) {
+        ::OpaqueLoginResult value = opaque_client_complete_login(
+            client_, ::ServerLogStartResult{response.data(), response.size()});
+        LoginResult result{
+            Buffer(value.credential_finalization, value.size_credential_finalization),
+            Buffer(value.session_key, value.size_session_key),
+            Buffer(value.export_key, value.size_export_key),
+            Buffer(value.server_public_key, value.size_server_public_key)};
+        if (result.credential_finalization.empty()) {
+            throw Error("client login failed");
+        }
+        return result;
+    }
+
+private:
+    ::OpaqueClient *client_;
+};
+
+}  // namespace opaque
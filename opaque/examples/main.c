@@ -58,6 +58,30 @@ int happyPath() {
     Opaque registration_server_finish = opaque_server_registration_finish(registration_client_finish);
     printf("%s Server reg finish %s \n", c_prefix, registration_server_finish.data);
 
+    int result = 0;
+
+    // seal the password file as it would be stored in a database, then unseal it back
+    const uint8_t kekBytes[32] = {1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32};
+    KeyEncryptionKey kek = {
+        .id = 1,
+        .key = kekBytes,
+        .size_key = 32,
+    };
+    Opaque sealed_password_file = opaque_seal_password_file(kek, "pippo", registration_server_finish);
+    Opaque unsealed_password_file = opaque_unseal_password_file(kek, "pippo", sealed_password_file);
+    if (unsealed_password_file.size != registration_server_finish.size ||
+        memcmp(unsealed_password_file.data, registration_server_finish.data, registration_server_finish.size) != 0) {
+        printf("%s HAPPYPATH UNSEAL FAILED \n", c_prefix);
+        result = 1;
+    }
+    printf("%s Sealed password file with KEK %lld \n", c_prefix, (long long)opaque_sealed_kek_id(sealed_password_file));
+    if (sealed_password_file.size > 0) {
+        free_memlib(sealed_password_file.data);
+    }
+    if (unsealed_password_file.size > 0) {
+        free_memlib(unsealed_password_file.data);
+    }
+
     //////////////////////////////////////////
     ////////////// LOGIN STEPS //////////////
     ////////////////////////////////////////
@@ -108,13 +132,20 @@ int happyPath() {
         .state = login_server_start.state, 
         .size_state = login_server_start.size_state 
     };
-    bool check = opaque_server_login_finish(login_client_finish, server_log_state);
+    bool check = opaque_server_login_finish_with_policy(login_client_finish, server_log_state, 300, true);
     if (check == true) {
         printf("%s HAPPYPATH LOGIN SUCCESSFUL \n", c_prefix);
     } else {
         printf("%s HAPPYPATH LOGIN FAILED \n", c_prefix);
     }
 
+    // the same server login state can't be used twice
+    bool replay = opaque_server_login_finish_with_policy(login_client_finish, server_log_state, 300, true);
+    if (replay == true) {
+        printf("%s HAPPYPATH REPLAYED LOGIN ACCEPTED \n", c_prefix);
+        result = 1;
+    }
+
     free_memlib(registration_client_start.data);
     free_memlib(registration_client_start.state);
     free_memlib(registration_server_start.data);
@@ -127,7 +158,7 @@ int happyPath() {
     free_memlib(login_server_start.state);
     free_memlib(login_client_finish.data);
 
-    return 0;
+    return result;
 }
 
 // to test an error path, is sufficient to use below 'incorrect' variables
@@ -156,6 +187,20 @@ int errorPath() {
     OpaqueWithState registration_client_start = opaque_client_registration_start(correct_password);
     printOpaqueWithState(&registration_client_start, "C - LOG: Client reg start");
 
+    // an enveloped registration request can't be taken for a credential request
+    Opaque registration_request = {
+        .data = registration_client_start.data,
+        .size = registration_client_start.size_data
+    };
+    Opaque envelope = opaque_envelope_encode(OPAQUE_MSG_REGISTRATION_REQUEST, registration_request);
+    Opaque wrong_message = opaque_envelope_decode(OPAQUE_MSG_CREDENTIAL_REQUEST, envelope);
+    if (wrong_message.size == 0) {
+        printf("C - LOG: Envelope message type mismatch detected \n");
+    } else {
+        free_memlib(wrong_message.data);
+    }
+    free_memlib(envelope.data);
+
     // prepare input structs for second registration step
     ClientRegStartResult client_reg_start_result = {
         .data = registration_client_start.data,
@@ -334,12 +379,900 @@ int errorPath() {
     return 0;
 }
 
+// registration of username with the object API: returns 1 when
+// the server saved the password file
+int objectRegister(OpaqueServer* server, OpaqueClient* client, const char* username, const char* password) {
+    Opaque registration_request = opaque_client_begin_registration(client, password);
+    ClientRegStartResult client_reg_start_result = {
+        .data = registration_request.data,
+        .size_data = registration_request.size
+    };
+    Opaque registration_response = opaque_server_handle_registration_request(server, username, client_reg_start_result);
+    ServerRegStartResult server_reg_start_result = {
+        .data = registration_response.data,
+        .size_data = registration_response.size
+    };
+    OpaqueRegistrationResult registration = opaque_client_complete_registration(client, server_reg_start_result);
+    Opaque upload = { .data = registration.upload, .size = registration.size_upload };
+    int success = registration.size_upload > 0 && opaque_server_handle_registration_upload(server, username, upload);
+
+    if (registration_request.size > 0) {
+        free_memlib(registration_request.data);
+    }
+    if (registration_response.size > 0) {
+        free_memlib(registration_response.data);
+    }
+    if (registration.size_upload > 0) {
+        free_memlib(registration.upload);
+        free_memlib(registration.export_key);
+        free_memlib(registration.server_public_key);
+    }
+    return success;
+}
+
+// login of username with the object API: returns 1 when
+// client and server end up with the same session key
+int objectLogin(OpaqueServer* server, OpaqueClient* client, const char* username, const char* password, const char* session_id) {
+    Opaque credential_request = opaque_client_begin_login(client, password);
+    ClientLogStartResult client_log_start_result = {
+        .data = credential_request.data,
+        .size_data = credential_request.size
+    };
+    Opaque credential_response = opaque_server_handle_credential_request(server, username, session_id, client_log_start_result);
+
+    ServerLogStartResult server_log_start_result = {
+        .data = credential_response.data,
+        .size_data = credential_response.size
+    };
+    OpaqueLoginResult login = opaque_client_complete_login(client, server_log_start_result);
+    Opaque credential_finalization = {
+        .data = login.credential_finalization,
+        .size = login.size_credential_finalization
+    };
+    Opaque session_key = opaque_server_handle_credential_finalization(server, username, session_id, credential_finalization);
+    int success = session_key.size > 0 && session_key.size == login.size_session_key &&
+        memcmp(session_key.data, login.session_key, session_key.size) == 0;
+
+    free_memlib(credential_request.data);
+    free_memlib(credential_response.data);
+    if (login.size_credential_finalization > 0) {
+        free_memlib(login.credential_finalization);
+        free_memlib(login.session_key);
+        free_memlib(login.export_key);
+        free_memlib(login.server_public_key);
+    }
+    if (session_key.size > 0) {
+        free_memlib(session_key.data);
+    }
+    return success;
+}
+
+// same registration and login of happyPath, handled by OpaqueClient and OpaqueServer
+int objectPath() {
+    const char* c_prefix = "C - LOG: ";
+    const char* servername = "servername";
+    const char* context = "context";
+    int result = 0;
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s OBJECT API TEST\n", c_prefix);
+
+    ServerSetup no_setup = {
+        .setup = NULL,
+        .size_setup = 0
+    };
+    OpaqueServer* server = opaque_server_new(no_setup, servername, context, NULL);
+    if (server == NULL) {
+        printf("%s Server creation ERROR \n", c_prefix);
+        return 1;
+    }
+    OpaqueClient* client = opaque_client_new("pippo", servername, context);
+
+    // registration
+    Opaque registration_request = opaque_client_begin_registration(client, "ciao");
+    ClientRegStartResult client_reg_start_result = {
+        .data = registration_request.data,
+        .size_data = registration_request.size
+    };
+    Opaque registration_response = opaque_server_handle_registration_request(server, "pippo", client_reg_start_result);
+
+    ServerRegStartResult server_reg_start_result = {
+        .data = registration_response.data,
+        .size_data = registration_response.size
+    };
+    OpaqueRegistrationResult registration = opaque_client_complete_registration(client, server_reg_start_result);
+    Opaque upload = {
+        .data = registration.upload,
+        .size = registration.size_upload
+    };
+    if (!opaque_server_handle_registration_upload(server, "pippo", upload)) {
+        printf("%s Server registration upload ERROR \n", c_prefix);
+        result = 1;
+    }
+
+    // login
+    Opaque credential_request = opaque_client_begin_login(client, "ciao");
+    ClientLogStartResult client_log_start_result = {
+        .data = credential_request.data,
+        .size_data = credential_request.size
+    };
+    Opaque credential_response = opaque_server_handle_credential_request(server, "pippo", "session-1", client_log_start_result);
+
+    ServerLogStartResult server_log_start_result = {
+        .data = credential_response.data,
+        .size_data = credential_response.size
+    };
+    OpaqueLoginResult login = opaque_client_complete_login(client, server_log_start_result);
+    Opaque credential_finalization = {
+        .data = login.credential_finalization,
+        .size = login.size_credential_finalization
+    };
+    Opaque session_key = opaque_server_handle_credential_finalization(server, "pippo", "session-1", credential_finalization);
+    if (session_key.size > 0 && session_key.size == login.size_session_key &&
+        memcmp(session_key.data, login.session_key, session_key.size) == 0) {
+        printf("%s OBJECT API LOGIN SUCCESSFUL \n", c_prefix);
+    } else {
+        printf("%s OBJECT API LOGIN FAILED \n", c_prefix);
+        result = 1;
+    }
+
+    // messages protected with the session key: a replayed record must be refused
+    Opaque client_key = { .data = login.session_key, .size = login.size_session_key };
+    SecureChannel* client_channel = opaque_channel_new(client_key, false);
+    SecureChannel* server_channel = opaque_channel_new(session_key, true);
+    const uint8_t hello[] = "hello";
+    Opaque no_aad = { .data = NULL, .size = 0 };
+    Opaque plaintext = { .data = hello, .size = sizeof(hello) };
+    Opaque record = opaque_channel_seal(client_channel, no_aad, plaintext);
+    OpaqueChannelOpenResult opened = opaque_channel_open(server_channel, no_aad, record);
+    OpaqueChannelOpenResult replayed = opaque_channel_open(server_channel, no_aad, record);
+    if (opened.ok && opened.size_plaintext == sizeof(hello) &&
+        memcmp(opened.plaintext, hello, sizeof(hello)) == 0 && !replayed.ok) {
+        printf("%s OBJECT API CHANNEL SUCCESSFUL \n", c_prefix);
+    } else {
+        printf("%s OBJECT API CHANNEL FAILED \n", c_prefix);
+        result = 1;
+    }
+    // an empty message is opened as such, not refused
+    Opaque empty = { .data = NULL, .size = 0 };
+    Opaque empty_record = opaque_channel_seal(client_channel, no_aad, empty);
+    OpaqueChannelOpenResult empty_opened = opaque_channel_open(server_channel, no_aad, empty_record);
+    if (empty_opened.ok && empty_opened.size_plaintext == 0) {
+        printf("%s OBJECT API EMPTY CHANNEL MESSAGE SUCCESSFUL \n", c_prefix);
+    } else {
+        printf("%s OBJECT API EMPTY CHANNEL MESSAGE FAILED \n", c_prefix);
+        result = 1;
+    }
+    free_memlib(record.data);
+    free_memlib(opened.plaintext);
+    free_memlib(empty_record.data);
+    opaque_channel_free(client_channel);
+    opaque_channel_free(server_channel);
+
+    // password change: a wrong current password must leave the password file as it is
+    OpaquePasswordChangeRequest wrong_change = opaque_client_begin_password_change(client, "wrong", "nuova");
+    Opaque wrong_credential_request = { .data = wrong_change.credential_request, .size = wrong_change.size_credential_request };
+    Opaque wrong_registration_request = { .data = wrong_change.registration_request, .size = wrong_change.size_registration_request };
+    OpaquePasswordChangeResponse wrong_change_response = opaque_server_handle_password_change_request(
+        server, "pippo", "session-2", wrong_credential_request, wrong_registration_request);
+    Opaque wrong_credential_response = { .data = wrong_change_response.credential_response, .size = wrong_change_response.size_credential_response };
+    Opaque wrong_registration_response = { .data = wrong_change_response.registration_response, .size = wrong_change_response.size_registration_response };
+    Opaque wrong_wrapped_secret = { .data = wrong_change_response.wrapped_secret, .size = wrong_change_response.size_wrapped_secret };
+    OpaquePasswordChangeResult wrong_change_result = opaque_client_complete_password_change(
+        client, wrong_credential_response, wrong_registration_response, wrong_wrapped_secret);
+    if (wrong_change_result.size_upload > 0) {
+        printf("%s OBJECT API PASSWORD CHANGE WITH WRONG PASSWORD ACCEPTED \n", c_prefix);
+        result = 1;
+    }
+
+    OpaquePasswordChangeRequest change = opaque_client_begin_password_change(client, "ciao", "nuova");
+    Opaque change_credential_request = { .data = change.credential_request, .size = change.size_credential_request };
+    Opaque change_registration_request = { .data = change.registration_request, .size = change.size_registration_request };
+    OpaquePasswordChangeResponse change_response = opaque_server_handle_password_change_request(
+        server, "pippo", "session-3", change_credential_request, change_registration_request);
+    Opaque change_credential_response = { .data = change_response.credential_response, .size = change_response.size_credential_response };
+    Opaque change_registration_response = { .data = change_response.registration_response, .size = change_response.size_registration_response };
+    Opaque change_wrapped_secret = { .data = change_response.wrapped_secret, .size = change_response.size_wrapped_secret };
+    OpaquePasswordChangeResult change_result = opaque_client_complete_password_change(
+        client, change_credential_response, change_registration_response, change_wrapped_secret);
+    Opaque change_finalization = { .data = change_result.credential_finalization, .size = change_result.size_credential_finalization };
+    Opaque change_upload = { .data = change_result.upload, .size = change_result.size_upload };
+    Opaque change_new_wrapped_secret = { .data = change_result.wrapped_secret, .size = change_result.size_wrapped_secret };
+    Opaque change_tag = { .data = change_result.tag, .size = change_result.size_tag };
+    Opaque change_session_key = opaque_server_handle_password_change_finalization(
+        server, "pippo", "session-3", change_finalization, change_upload, change_new_wrapped_secret, change_tag);
+    if (change_session_key.size > 0) {
+        printf("%s OBJECT API PASSWORD CHANGED \n", c_prefix);
+    } else {
+        printf("%s OBJECT API PASSWORD CHANGE FAILED \n", c_prefix);
+        result = 1;
+    }
+
+    // the recovery secret is still the one derived at registration
+    Opaque registration_export_key = { .data = registration.export_key, .size = registration.size_export_key };
+    Opaque secret = opaque_recovery_secret(registration_export_key);
+    if (change_result.size_recovery_secret == secret.size &&
+        memcmp(change_result.recovery_secret, secret.data, secret.size) == 0) {
+        printf("%s OBJECT API RECOVERY SECRET KEPT \n", c_prefix);
+    } else {
+        printf("%s OBJECT API RECOVERY SECRET LOST \n", c_prefix);
+        result = 1;
+    }
+    free_memlib(secret.data);
+
+    // only the new password logs in from now on
+    if (objectLogin(server, client, "pippo", "nuova", "session-4")) {
+        printf("%s OBJECT API LOGIN WITH NEW PASSWORD SUCCESSFUL \n", c_prefix);
+    } else {
+        printf("%s OBJECT API LOGIN WITH NEW PASSWORD FAILED \n", c_prefix);
+        result = 1;
+    }
+    if (objectLogin(server, client, "pippo", "ciao", "session-5")) {
+        printf("%s OBJECT API LOGIN WITH OLD PASSWORD ACCEPTED \n", c_prefix);
+        result = 1;
+    } else {
+        printf("%s OBJECT API LOGIN WITH OLD PASSWORD REFUSED \n", c_prefix);
+    }
+
+    free_memlib(wrong_change.credential_request);
+    free_memlib(wrong_change.registration_request);
+    free_memlib(wrong_change_response.credential_response);
+    free_memlib(wrong_change_response.registration_response);
+    if (wrong_change_response.size_wrapped_secret > 0) {
+        free_memlib(wrong_change_response.wrapped_secret);
+    }
+    free_memlib(change.credential_request);
+    free_memlib(change.registration_request);
+    free_memlib(change_response.credential_response);
+    free_memlib(change_response.registration_response);
+    if (change_response.size_wrapped_secret > 0) {
+        free_memlib(change_response.wrapped_secret);
+    }
+    free_memlib(change_result.credential_finalization);
+    free_memlib(change_result.upload);
+    free_memlib(change_result.wrapped_secret);
+    free_memlib(change_result.tag);
+    free_memlib(change_result.session_key);
+    free_memlib(change_result.export_key);
+    free_memlib(change_result.recovery_secret);
+    free_memlib(change_session_key.data);
+    free_memlib(registration_request.data);
+    free_memlib(registration_response.data);
+    free_memlib(registration.upload);
+    free_memlib(registration.export_key);
+    free_memlib(registration.server_public_key);
+    free_memlib(credential_request.data);
+    free_memlib(credential_response.data);
+    free_memlib(login.credential_finalization);
+    free_memlib(login.session_key);
+    free_memlib(login.export_key);
+    free_memlib(login.server_public_key);
+    free_memlib(session_key.data);
+    opaque_client_free(client);
+    opaque_server_free(server);
+
+    return result;
+}
+
+// recovery of pippo with code, registering new_password: returns 1 if the
+// server accepted the new password and the client got back expected_secret
+int recover(OpaqueServer* server, OpaqueClient* client, const char* code, const char* new_password, Opaque expected_secret) {
+    OpaqueRecoveryRequest request = opaque_client_begin_recovery(client, code, new_password);
+    Opaque verifier = { .data = request.verifier, .size = request.size_verifier };
+    Opaque registration_request = { .data = request.registration_request, .size = request.size_registration_request };
+    OpaqueRecoveryResponse response = opaque_server_handle_recovery_request(server, "pippo", verifier, registration_request);
+
+    Opaque wrapped_secret = { .data = response.wrapped_secret, .size = response.size_wrapped_secret };
+    Opaque registration_response = { .data = response.registration_response, .size = response.size_registration_response };
+    OpaqueRecoveryResult recovery = opaque_client_complete_recovery(client, wrapped_secret, registration_response);
+    Opaque final_verifier = { .data = recovery.verifier, .size = recovery.size_verifier };
+    Opaque upload = { .data = recovery.upload, .size = recovery.size_upload };
+    Opaque new_wrapped_secret = { .data = recovery.wrapped_secret, .size = recovery.size_wrapped_secret };
+    int success = recovery.size_upload > 0 &&
+        opaque_server_handle_recovery_finalization(server, "pippo", final_verifier, upload, new_wrapped_secret) &&
+        recovery.size_secret == expected_secret.size &&
+        memcmp(recovery.secret, expected_secret.data, expected_secret.size) == 0;
+
+    if (request.size_verifier > 0) {
+        free_memlib(request.verifier);
+        free_memlib(request.registration_request);
+    }
+    if (response.size_wrapped_secret > 0) {
+        free_memlib(response.wrapped_secret);
+        free_memlib(response.registration_response);
+    }
+    if (recovery.size_upload > 0) {
+        free_memlib(recovery.verifier);
+        free_memlib(recovery.upload);
+        free_memlib(recovery.wrapped_secret);
+        free_memlib(recovery.export_key);
+        free_memlib(recovery.secret);
+    }
+    return success;
+}
+
+int recoveryPath() {
+    const char* c_prefix = "C - LOG: ";
+    const char* servername = "servername";
+    const char* context = "context";
+    int result = 0;
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s RECOVERY TEST\n", c_prefix);
+
+    ServerSetup no_setup = {
+        .setup = NULL,
+        .size_setup = 0
+    };
+    OpaqueServer* server = opaque_server_new(no_setup, servername, context, NULL);
+    if (server == NULL) {
+        printf("%s Server creation ERROR \n", c_prefix);
+        return 1;
+    }
+    OpaqueClient* client = opaque_client_new("pippo", servername, context);
+
+    // registration, the server keeping the records of 2 recovery codes
+    Opaque registration_request = opaque_client_begin_registration(client, "ciao");
+    ClientRegStartResult client_reg_start_result = {
+        .data = registration_request.data,
+        .size_data = registration_request.size
+    };
+    Opaque registration_response = opaque_server_handle_registration_request(server, "pippo", client_reg_start_result);
+    ServerRegStartResult server_reg_start_result = {
+        .data = registration_response.data,
+        .size_data = registration_response.size
+    };
+    OpaqueRegistrationResult registration = opaque_client_complete_registration(client, server_reg_start_result);
+    Opaque export_key = { .data = registration.export_key, .size = registration.size_export_key };
+    Opaque secret = opaque_recovery_secret(export_key);
+    OpaqueRecoveryCodes codes = opaque_recovery_codes_generate(secret, "pippo", 2);
+    Opaque upload = { .data = registration.upload, .size = registration.size_upload };
+    Opaque records = { .data = codes.records, .size = codes.size_records };
+    if (!opaque_server_handle_registration_upload_with_recovery(server, "pippo", upload, records)) {
+        printf("%s Server registration upload ERROR \n", c_prefix);
+        result = 1;
+    }
+
+    // first of the codes, one per line
+    char code[32] = {0};
+    size_t code_len = 0;
+    while (code_len < codes.size_codes && code_len < sizeof(code) - 1 && codes.codes[code_len] != '\n') {
+        code[code_len] = (char)codes.codes[code_len];
+        code_len++;
+    }
+
+    if (recover(server, client, code, "nuova", secret)) {
+        printf("%s RECOVERY SUCCESSFUL \n", c_prefix);
+    } else {
+        printf("%s RECOVERY FAILED \n", c_prefix);
+        result = 1;
+    }
+    if (objectLogin(server, client, "pippo", "nuova", "session-1") &&
+        !objectLogin(server, client, "pippo", "ciao", "session-2")) {
+        printf("%s LOGIN WITH RECOVERED PASSWORD SUCCESSFUL \n", c_prefix);
+    } else {
+        printf("%s LOGIN WITH RECOVERED PASSWORD FAILED \n", c_prefix);
+        result = 1;
+    }
+
+    // every code is single use
+    if (recover(server, client, code, "altra", secret)) {
+        printf("%s RECOVERY CODE USED TWICE \n", c_prefix);
+        result = 1;
+    } else {
+        printf("%s USED RECOVERY CODE REFUSED \n", c_prefix);
+    }
+
+    free_memlib(registration_request.data);
+    free_memlib(registration_response.data);
+    free_memlib(registration.upload);
+    free_memlib(registration.export_key);
+    free_memlib(registration.server_public_key);
+    free_memlib(secret.data);
+    free_memlib(codes.codes);
+    free_memlib(codes.records);
+    opaque_client_free(client);
+    opaque_server_free(server);
+
+    return result;
+}
+
+// registration made with the stateless API: the server setup
+// to log in with and the password file
+typedef struct Registration {
+    OpaqueWithSetup server_start;
+    Opaque password_file;
+} Registration;
+
+// register username with password through the stateless API, with the
+// identities of config, or the default ones of "servername" if NULL
+Registration registerUser(const ProtocolConfig* config, const char* username, const char* password, ServerRegPrivateKey private_key) {
+    OpaqueWithState client_start = opaque_client_registration_start(password);
+    ClientRegStartResult client_reg_start_result = {
+        .data = client_start.data,
+        .size_data = client_start.size_data
+    };
+    OpaqueWithSetup server_start = opaque_server_registration_start(
+        username,
+        client_reg_start_result,
+        private_key
+    );
+    ServerRegStartResult server_reg_start_result = {
+        .data = server_start.data,
+        .size_data = server_start.size_data
+    };
+    ClientRegState client_reg_state = {
+        .state = client_start.state,
+        .size_state = client_start.size_state
+    };
+    Opaque upload;
+    Opaque password_file;
+    if (config == NULL) {
+        upload = opaque_client_registration_finish(
+            password,
+            server_reg_start_result,
+            client_reg_state,
+            username,
+            "servername"
+        );
+        password_file = opaque_server_registration_finish(upload);
+    } else {
+        upload = opaque_client_registration_finish_with_config(
+            config,
+            password,
+            server_reg_start_result,
+            client_reg_state,
+            username
+        );
+        password_file = opaque_server_registration_finish_with_config(config, username, upload);
+    }
+
+    free_memlib(client_start.data);
+    free_memlib(client_start.state);
+    if (upload.size > 0) {
+        free_memlib(upload.data);
+    }
+    Registration registration = {
+        .server_start = server_start,
+        .password_file = password_file
+    };
+    return registration;
+}
+
+void freeRegistration(Registration registration) {
+    if (registration.server_start.size_data > 0) {
+        free_memlib(registration.server_start.data);
+        free_memlib(registration.server_start.setup);
+    }
+    if (registration.password_file.size > 0) {
+        free_memlib(registration.password_file.data);
+    }
+}
+
+// how loginUser logs in: with server_config NULL, with the identities of
+// "servername" and the login context of each side (both can be empty);
+// otherwise with the configuration of each side and, if server_identity
+// is set, with the identity of each side
+typedef struct LoginOptions {
+    Opaque server_context;
+    Opaque client_context;
+    const ProtocolConfig* server_config;
+    const ProtocolConfig* client_config;
+    const char* server_identity;
+    const char* client_identity;
+} LoginOptions;
+
+// login of username with password through the stateless API: returns
+// the size of the credential finalization, 0 on failure
+size_t loginUser(Registration registration, const char* username, const char* password, LoginOptions options) {
+    OpaqueWithState login_client_start = opaque_client_login_start(password);
+    ClientLogStartResult client_log_start_result = {
+        .data = login_client_start.data,
+        .size_data = login_client_start.size_data
+    };
+    ServerSetup server_setup = {
+        .setup = registration.server_start.setup,
+        .size_setup = registration.server_start.size_setup
+    };
+    OpaqueWithState login_server_start;
+    if (options.server_config == NULL) {
+        login_server_start = opaque_server_login_start_with_context(
+            username,
+            registration.password_file,
+            client_log_start_result,
+            server_setup,
+            "servername",
+            options.server_context
+        );
+    } else if (options.server_identity == NULL) {
+        login_server_start = opaque_server_login_start_with_config(
+            options.server_config,
+            username,
+            registration.password_file,
+            client_log_start_result,
+            server_setup
+        );
+    } else {
+        login_server_start = opaque_server_login_start_with_identity(
+            options.server_config,
+            username,
+            options.server_identity,
+            registration.password_file,
+            client_log_start_result,
+            server_setup
+        );
+    }
+
+    size_t size = 0;
+    if (login_server_start.size_data > 0) {
+        ServerLogStartResult server_log_start_result = {
+            .data = login_server_start.data,
+            .size_data = login_server_start.size_data
+        };
+        ClientLogState client_log_state = {
+            .state = login_client_start.state,
+            .size_state = login_client_start.size_state
+        };
+        Opaque login_client_finish;
+        if (options.server_config == NULL) {
+            login_client_finish = opaque_client_login_finish_with_context(
+                password,
+                server_log_start_result,
+                client_log_state,
+                username,
+                "servername",
+                options.client_context
+            );
+        } else if (options.server_identity == NULL) {
+            login_client_finish = opaque_client_login_finish_with_config(
+                options.client_config,
+                password,
+                server_log_start_result,
+                client_log_state,
+                username
+            );
+        } else {
+            login_client_finish = opaque_client_login_finish_with_identity(
+                options.client_config,
+                password,
+                server_log_start_result,
+                client_log_state,
+                username,
+                options.client_identity
+            );
+        }
+        size = login_client_finish.size;
+        if (size > 0) {
+            free_memlib(login_client_finish.data);
+        }
+        free_memlib(login_server_start.data);
+        free_memlib(login_server_start.state);
+    }
+
+    free_memlib(login_client_start.data);
+    free_memlib(login_client_start.state);
+    return size;
+}
+
+// login bound to binding_server on the server and binding_client on the
+// client: returns the size of the credential finalization, 0 on failure
+size_t bindingLogin(Registration registration, const char* binding_server, const char* binding_client) {
+    Opaque server_bytes = { .data = (const uint8_t*)binding_server, .size = strlen(binding_server) };
+    Opaque client_bytes = { .data = (const uint8_t*)binding_client, .size = strlen(binding_client) };
+    LoginOptions options = {
+        .server_context = opaque_channel_binding_context("tls-exporter", server_bytes),
+        .client_context = opaque_channel_binding_context("tls-exporter", client_bytes)
+    };
+    size_t size = loginUser(registration, "pippo", "ciao", options);
+
+    free_memlib(options.server_context.data);
+    free_memlib(options.client_context.data);
+    return size;
+}
+
+int bindingPath() {
+    const char* c_prefix = "C - LOG: ";
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s BINDINGPATH TEST\n", c_prefix);
+
+    const uint8_t privKey[] = {221, 127, 195, 24, 108, 27, 107, 254, 165, 103, 174, 90, 147, 31, 101, 144, 125, 219, 51, 171, 178, 193, 60, 21, 56, 156, 211, 69, 14, 192, 114, 12};
+    ServerRegPrivateKey privateKey = {
+        .data = privKey,
+        .size_data = 32,
+    };
+    Registration registration = registerUser(NULL, "pippo", "ciao", privateKey);
+
+    int result = 0;
+    // same TLS session on both sides
+    if (bindingLogin(registration, "exporter-a", "exporter-a") == 0) {
+        printf("%s BINDINGPATH MATCHING BINDING REFUSED \n", c_prefix);
+        result = 1;
+    }
+    // TLS terminated by someone else: each side sees its own session
+    if (bindingLogin(registration, "exporter-a", "exporter-b") != 0) {
+        printf("%s BINDINGPATH MISMATCHED BINDING ACCEPTED \n", c_prefix);
+        result = 1;
+    }
+    printf("%s Login with mismatched binding refused \n", c_prefix);
+
+    freeRegistration(registration);
+    return result;
+}
+
+int configPath() {
+    const char* c_prefix = "C - LOG: ";
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s CONFIGPATH TEST\n", c_prefix);
+
+    const uint8_t contextBytes[] = "context";
+    Opaque context = { .data = contextBytes, .size = 7 };
+    ProtocolConfig* config = opaque_protocol_config_new("servername", context);
+    ProtocolConfig* other_config = opaque_protocol_config_new("otherserver", context);
+    ServerRegPrivateKey privateKey = { .data = NULL, .size_data = 0 };
+    Registration registration = registerUser(config, "pippo", "ciao", privateKey);
+
+    int result = 0;
+    LoginOptions same = { .server_config = config, .client_config = config };
+    if (loginUser(registration, "pippo", "ciao", same) == 0) {
+        printf("%s CONFIGPATH LOGIN WITH MATCHING IDENTITIES REFUSED \n", c_prefix);
+        result = 1;
+    }
+    // the server notices before answering that the password file
+    // was registered for another server identity
+    LoginOptions other = { .server_config = other_config, .client_config = other_config };
+    if (loginUser(registration, "pippo", "ciao", other) != 0) {
+        printf("%s CONFIGPATH LOGIN WITH OTHER IDENTITIES ACCEPTED \n", c_prefix);
+        result = 1;
+    }
+    printf("%s Login with other identities refused \n", c_prefix);
+
+    freeRegistration(registration);
+    opaque_protocol_config_free(config);
+    opaque_protocol_config_free(other_config);
+
+    return result;
+}
+
+// login of the credential "user-0001" with the given identities on each
+// side: returns the size of the credential finalization, 0 on failure
+size_t identityLogin(Registration registration, const ProtocolConfig* config, const char* client_identity, const char* server_identity) {
+    LoginOptions options = {
+        .server_config = config,
+        .client_config = config,
+        .server_identity = server_identity,
+        .client_identity = client_identity
+    };
+    return loginUser(registration, "user-0001", "ciao", options);
+}
+
+int identityPath() {
+    const char* c_prefix = "C - LOG: ";
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s IDENTITYPATH TEST\n", c_prefix);
+
+    const uint8_t contextBytes[] = "context";
+    Opaque context = { .data = contextBytes, .size = 7 };
+    ProtocolConfig* config = opaque_protocol_config_new("servername", context);
+
+    // registered under the credential id, the identity isn't needed yet
+    ServerRegPrivateKey privateKey = { .data = NULL, .size_data = 0 };
+    Registration registration = registerUser(config, "user-0001", "ciao", privateKey);
+
+    int result = 0;
+    if (identityLogin(registration, config, "pippo@example.com", "pippo@example.com") == 0) {
+        printf("%s IDENTITYPATH LOGIN REFUSED \n", c_prefix);
+        result = 1;
+    }
+    // the user changed email: same password file, new identity on both sides
+    if (identityLogin(registration, config, "pippo@example.org", "pippo@example.org") == 0) {
+        printf("%s IDENTITYPATH LOGIN AFTER IDENTITY CHANGE REFUSED \n", c_prefix);
+        result = 1;
+    }
+    printf("%s Login after identity change accepted \n", c_prefix);
+    if (identityLogin(registration, config, "pippo@example.com", "pippo@example.org") != 0) {
+        printf("%s IDENTITYPATH LOGIN WITH OTHER IDENTITY ACCEPTED \n", c_prefix);
+        result = 1;
+    }
+
+    freeRegistration(registration);
+    opaque_protocol_config_free(config);
+
+    return result;
+}
+
+// register "pippo" with registration_password and log in with login_password,
+// using the object API: returns 0 if the login succeeded
+int normalizationLogin(const char* registration_password, const char* login_password) {
+    ServerSetup no_setup = { .setup = NULL, .size_setup = 0 };
+    OpaqueServer* server = opaque_server_new(no_setup, "servername", "context", NULL);
+    OpaqueClient* client = opaque_client_new("pippo", "servername", "context");
+
+    int result = objectRegister(server, client, "pippo", registration_password) &&
+        objectLogin(server, client, "pippo", login_password, "session-1") ? 0 : 1;
+
+    opaque_client_free(client);
+    opaque_server_free(server);
+    return result;
+}
+
+typedef struct NormalizationVector {
+    const char* password;
+    uint8_t normalization;
+    const char* expected;
+} NormalizationVector;
+
+int normalizationPath() {
+    const char* c_prefix = "C - LOG: ";
+    int result = 0;
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s NORMALIZATIONPATH TEST\n", c_prefix);
+
+    const NormalizationVector vectors[] = {
+        // "café" composed (U+00E9) and decomposed (e + U+0301)
+        { "caf\xC3\xA9", OPAQUE_PASSWORD_NORMALIZATION_NFC, "caf\xC3\xA9" },
+        { "cafe\xCC\x81", OPAQUE_PASSWORD_NORMALIZATION_NFC, "caf\xC3\xA9" },
+        { "cafe\xCC\x81", OPAQUE_PASSWORD_NORMALIZATION_NFKC, "caf\xC3\xA9" },
+        { "cafe\xCC\x81", OPAQUE_PASSWORD_NORMALIZATION_NONE, "cafe\xCC\x81" },
+        // Hangul syllable U+AC00 from its jamo U+1100 U+1161
+        { "\xE1\x84\x80\xE1\x85\xA1", OPAQUE_PASSWORD_NORMALIZATION_NFC, "\xEA\xB0\x80" },
+        // ANGSTROM SIGN U+212B is a singleton of U+00C5
+        { "\xE2\x84\xAB", OPAQUE_PASSWORD_NORMALIZATION_NFC, "\xC3\x85" },
+        // ligature U+FB01 and FULLWIDTH A U+FF21: kept by NFC, plain with NFKC
+        { "\xEF\xAC\x81", OPAQUE_PASSWORD_NORMALIZATION_NFC, "\xEF\xAC\x81" },
+        { "\xEF\xAC\x81", OPAQUE_PASSWORD_NORMALIZATION_NFKC, "fi" },
+        { "\xEF\xBC\xA1", OPAQUE_PASSWORD_NORMALIZATION_NFKC, "A" },
+        // NO-BREAK SPACE U+00A0 becomes a space
+        { "a\xC2\xA0" "b", OPAQUE_PASSWORD_NORMALIZATION_NFC, "a b" },
+        { "a\xC2\xA0" "b", OPAQUE_PASSWORD_NORMALIZATION_NFKC, "a b" },
+    };
+    for (size_t i = 0; i < sizeof(vectors) / sizeof(vectors[0]); i++) {
+        Opaque normalized = opaque_normalize_password(vectors[i].password, vectors[i].normalization);
+        size_t expected_size = strlen(vectors[i].expected);
+        if (normalized.size != expected_size || memcmp(normalized.data, vectors[i].expected, expected_size) != 0) {
+            printf("%s NORMALIZATIONPATH VECTOR %zu FAILED \n", c_prefix, i);
+            result = 1;
+        }
+        if (normalized.size > 0) {
+            free_memlib(normalized.data);
+        }
+    }
+
+    // registered on a system typing decomposed characters,
+    // logging in from one typing composed characters
+    if (normalizationLogin("cafe\xCC\x81", "caf\xC3\xA9") == 0) {
+        printf("%s NORMALIZATIONPATH LOGIN WITHOUT NORMALIZATION ACCEPTED \n", c_prefix);
+        result = 1;
+    }
+    opaque_set_password_normalization(OPAQUE_PASSWORD_NORMALIZATION_NFC);
+    if (normalizationLogin("cafe\xCC\x81", "caf\xC3\xA9") != 0) {
+        printf("%s NORMALIZATIONPATH COMPOSED LOGIN REFUSED \n", c_prefix);
+        result = 1;
+    }
+    // back to the default for the paths after this one
+    opaque_set_password_normalization(OPAQUE_PASSWORD_NORMALIZATION_NONE);
+    printf("%s Composed and decomposed passwords log in the same user \n", c_prefix);
+
+    return result;
+}
+
+typedef struct PasswordCheckVector {
+    const char* password;
+    const char* username;
+    uint32_t issues;
+} PasswordCheckVector;
+
+int policyPath() {
+    const char* c_prefix = "C - LOG: ";
+    int result = 0;
+
+    printf("\n--------------------------------------------------\n");
+    printf("%s POLICYPATH TEST\n", c_prefix);
+
+    const PasswordCheckVector vectors[] = {
+        { "", "pippo", OPAQUE_PASSWORD_TOO_SHORT | OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES },
+        { "x", "pippo", OPAQUE_PASSWORD_TOO_SHORT | OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES },
+        { "password", "pippo", OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES | OPAQUE_PASSWORD_COMMON },
+        { "Password1!", "pippo", OPAQUE_PASSWORD_COMMON },
+        { "Pippo-2024!", "pippo", OPAQUE_PASSWORD_CONTAINS_USERNAME },
+        { "zzzzzzzzzzzz", "pippo", OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES | OPAQUE_PASSWORD_REPETITIVE },
+        { "correct horse battery staple", "pippo", 0 },
+    };
+    for (size_t i = 0; i < sizeof(vectors) / sizeof(vectors[0]); i++) {
+        OpaquePasswordCheck check = opaque_password_check(vectors[i].password, vectors[i].username);
+        if (check.issues != vectors[i].issues) {
+            printf("%s POLICYPATH VECTOR %zu: issues %u instead of %u \n", c_prefix, i, check.issues, vectors[i].issues);
+            result = 1;
+        }
+    }
+    OpaquePasswordCheck strong = opaque_password_check("correct horse battery staple", "");
+    printf("%s Strong password score %u, %u bits \n", c_prefix, strong.score, strong.entropy_bits);
+
+    // a client with a policy refuses to register a weak password
+    OpaqueClient* client = opaque_client_new("pippo", "servername", "context");
+    opaque_client_set_default_password_policy(client);
+    Opaque weak_request = opaque_client_begin_registration(client, "ciao");
+    if (weak_request.size != 0) {
+        printf("%s POLICYPATH WEAK PASSWORD REGISTERED \n", c_prefix);
+        free_memlib(weak_request.data);
+        result = 1;
+    }
+    Opaque strong_request = opaque_client_begin_registration(client, "correct horse battery staple");
+    if (strong_request.size == 0) {
+        printf("%s POLICYPATH STRONG PASSWORD REFUSED \n", c_prefix);
+        result = 1;
+    } else {
+        free_memlib(strong_request.data);
+    }
+    opaque_client_free(client);
+
+    // the policy set for the whole process applies to the stateless API too
+    opaque_set_default_password_policy(true);
+    OpaqueWithState weak_start = opaque_client_registration_start("ciao");
+    if (weak_start.size_data != 0) {
+        printf("%s POLICYPATH WEAK PASSWORD REGISTERED WITHOUT CLIENT \n", c_prefix);
+        free_memlib(weak_start.data);
+        free_memlib(weak_start.state);
+        result = 1;
+    }
+    opaque_set_default_password_policy(false);
+    OpaqueWithState accepted_start = opaque_client_registration_start("ciao");
+    if (accepted_start.size_data == 0) {
+        printf("%s POLICYPATH PASSWORD REFUSED WITHOUT POLICY \n", c_prefix);
+        result = 1;
+    } else {
+        free_memlib(accepted_start.data);
+        free_memlib(accepted_start.state);
+    }
+    printf("%s Weak password refused without a client \n", c_prefix);
+
+    return result;
+}
+
 int main() {
     int happy = happyPath();
     if (happy != 0) {
         return 1;
     }
 
+    int object = objectPath();
+    if (object != 0) {
+        return 1;
+    }
+
+    int recovery = recoveryPath();
+    if (recovery != 0) {
+        return 1;
+    }
+
+    int binding = bindingPath();
+    if (binding != 0) {
+        return 1;
+    }
+
+    int config = configPath();
+    if (config != 0) {
+        return 1;
+    }
+
+    int identity = identityPath();
+    if (identity != 0) {
+        return 1;
+    }
+
+    int normalization = normalizationPath();
+    if (normalization != 0) {
+        return 1;
+    }
+
+    int policy = policyPath();
+    if (policy != 0) {
+        return 1;
+    }
+
     int error = errorPath();
     if (error == 0) {
         return 1;
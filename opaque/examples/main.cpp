@@ -0,0 +1,100 @@
+// same flows as examples/main.c through librust.hpp: no free_memlib calls,
+// every buffer is released by its owner
+
+#include <cstdio>
+#include <cstring>
+#include <vector>
+
+#include "librust.hpp"
+
+static const char *cpp_prefix = "C++ - LOG:";
+static const std::string servername = "servername";
+static const std::string context = "context";
+
+static bool same(const opaque::Buffer &a, const opaque::Buffer &b) {
+    return a.size() == b.size() && std::memcmp(a.data(), b.data(), a.size()) == 0;
+}
+
+static void happyPath() {
+    const std::vector<uint8_t> privKey = {221, 127, 195, 24,  108, 27,  107, 254, 165, 103, 174,
+                                          90,  147, 31,  101, 144, 125, 219, 51,  171, 178, 193,
+                                          60,  21,  56,  156, 211, 69,  14,  192, 114, 12};
+
+    auto reg_client_start = opaque::client_registration_start("ciao");
+    auto reg_server_start =
+        opaque::server_registration_start("pippo", reg_client_start.data, privKey);
+    auto upload = opaque::client_registration_finish("ciao", reg_server_start.data,
+                                                     reg_client_start.state, "pippo", servername);
+    auto password_file = opaque::server_registration_finish(upload);
+
+    auto log_client_start = opaque::client_login_start("ciao");
+    auto log_server_start =
+        opaque::server_login_start("pippo", password_file, log_client_start.data,
+                                   reg_server_start.setup, servername, context);
+    auto finalization = opaque::client_login_finish("ciao", log_server_start.data,
+                                                    log_client_start.state, "pippo", servername,
+                                                    context);
+    opaque::server_login_finish(finalization, log_server_start.state, 300, true);
+    std::printf("%s HAPPYPATH LOGIN SUCCESSFUL\n", cpp_prefix);
+}
+
+// true if the login with the wrong password is refused
+static bool errorPath() {
+    auto reg_client_start = opaque::client_registration_start("ciao");
+    auto reg_server_start = opaque::server_registration_start("pippo", reg_client_start.data);
+    auto upload = opaque::client_registration_finish("ciao", reg_server_start.data,
+                                                     reg_client_start.state, "pippo", servername);
+    auto password_file = opaque::server_registration_finish(upload);
+
+    auto envelope = opaque::envelope_encode(OPAQUE_MSG_REGISTRATION_REQUEST, reg_client_start.data);
+    try {
+        opaque::envelope_decode(OPAQUE_MSG_CREDENTIAL_REQUEST, envelope);
+        return false;
+    } catch (const opaque::Error &err) {
+        std::printf("%s Envelope message type mismatch detected\n", cpp_prefix);
+    }
+
+    auto log_client_start = opaque::client_login_start("wrong");
+    auto log_server_start =
+        opaque::server_login_start("pippo", password_file, log_client_start.data,
+                                   reg_server_start.setup, servername, context);
+    try {
+        opaque::client_login_finish("wrong", log_server_start.data, log_client_start.state,
+                                    "pippo", servername, context);
+    } catch (const opaque::Error &err) {
+        std::printf("%s ERRORPATH LOGIN FAILED: %s\n", cpp_prefix, err.what());
+        return true;
+    }
+    return false;
+}
+
+static void objectPath() {
+    opaque::Server server({}, servername, context);
+    opaque::Client client("pippo", servername, context);
+
+    auto reg_request = client.begin_registration("ciao");
+    auto reg_response = server.handle_registration_request("pippo", reg_request);
+    auto registration = client.complete_registration(reg_response);
+    server.handle_registration_upload("pippo", registration.upload);
+
+    auto cred_request = client.begin_login("ciao");
+    auto cred_response = server.handle_credential_request("pippo", "session-1", cred_request);
+    auto login = client.complete_login(cred_response);
+    auto session_key =
+        server.handle_credential_finalization("pippo", "session-1", login.credential_finalization);
+    if (!same(session_key, login.session_key)) {
+        throw opaque::Error("session keys differ");
+    }
+    std::printf("%s OBJECT API LOGIN SUCCESSFUL\n", cpp_prefix);
+}
+
+int main() {
+    try {
+        happyPath();
+        objectPath();
+    } catch (const opaque::Error &err) {
+        std::printf("%s %s\n", cpp_prefix, err.what());
+        return 1;
+    }
+    return errorPath() ? 0 : 1;
+}
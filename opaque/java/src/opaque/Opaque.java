@@ -0,0 +1,128 @@
+package opaque;
+
+/**
+ * Registration and login steps of librust, through JNI.
+ * The library must be on java.library.path (target/release after
+ * "cargo build --release --features jni").
+ */
+public final class Opaque {
+    static {
+        System.loadLibrary("rust");
+    }
+
+    private Opaque() {}
+
+    /** Message for the other side and state to keep until the next step. */
+    public static final class WithState {
+        public final byte[] data;
+        public final byte[] state;
+
+        WithState(byte[][] values) {
+            this.data = values[0];
+            this.state = values[1];
+        }
+    }
+
+    /** Registration response and server setup to keep for the logins. */
+    public static final class WithSetup {
+        public final byte[] data;
+        public final byte[] setup;
+
+        WithSetup(byte[][] values) {
+            this.data = values[0];
+            this.setup = values[1];
+        }
+    }
+
+    /** Credential finalization for the server and session key. */
+    public static final class WithKey {
+        public final byte[] data;
+        public final byte[] sessionKey;
+
+        WithKey(byte[][] values) {
+            this.data = values[0];
+            this.sessionKey = values[1];
+        }
+    }
+
+    /** Throws if the default password policy is enabled and refuses the password. */
+    public static WithState clientRegistrationStart(String password) {
+        return new WithState(nativeClientRegistrationStart(password));
+    }
+
+    /** With a null private key a new key pair is generated. */
+    public static WithSetup serverRegistrationStart(
+            String username, byte[] registrationRequest, byte[] privateKey) {
+        return new WithSetup(nativeServerRegistrationStart(username, registrationRequest, privateKey));
+    }
+
+    /** Throws if the default password policy is enabled and refuses the password. */
+    public static byte[] clientRegistrationFinish(
+            String password, byte[] registrationResponse, byte[] state,
+            String username, String servername) {
+        return nativeClientRegistrationFinish(password, registrationResponse, state, username, servername);
+    }
+
+    /** Returns the password file to store for the user. */
+    public static byte[] serverRegistrationFinish(byte[] registrationUpload) {
+        return nativeServerRegistrationFinish(registrationUpload);
+    }
+
+    public static WithState clientLoginStart(String password) {
+        return new WithState(nativeClientLoginStart(password));
+    }
+
+    public static WithState serverLoginStart(
+            String username, byte[] passwordFile, byte[] credentialRequest,
+            byte[] setup, String servername, String context) {
+        return new WithState(nativeServerLoginStart(
+                username, passwordFile, credentialRequest, setup, servername, context));
+    }
+
+    /** Throws if the password is wrong or the server isn't the expected one. */
+    public static WithKey clientLoginFinish(
+            String password, byte[] credentialResponse, byte[] state,
+            String username, String servername, String context) {
+        return new WithKey(nativeClientLoginFinish(
+                password, credentialResponse, state, username, servername, context));
+    }
+
+    /** Returns the session key, throws if the login failed. */
+    public static byte[] serverLoginFinish(byte[] credentialFinalization, byte[] state) {
+        return nativeServerLoginFinish(credentialFinalization, state);
+    }
+
+    /**
+     * Refuse, in clientRegistrationStart and clientRegistrationFinish, passwords
+     * shorter than 8 characters, of a single kind, common or containing the username:
+     * the server can't tell a weak password. Disabled by default.
+     */
+    public static void setDefaultPasswordPolicy(boolean enabled) {
+        nativeSetDefaultPasswordPolicy(enabled);
+    }
+
+    private static native byte[][] nativeClientRegistrationStart(String password);
+
+    private static native byte[][] nativeServerRegistrationStart(
+            String username, byte[] registrationRequest, byte[] privateKey);
+
+    private static native byte[] nativeClientRegistrationFinish(
+            String password, byte[] registrationResponse, byte[] state,
+            String username, String servername);
+
+    private static native byte[] nativeServerRegistrationFinish(byte[] registrationUpload);
+
+    private static native byte[][] nativeClientLoginStart(String password);
+
+    private static native byte[][] nativeServerLoginStart(
+            String username, byte[] passwordFile, byte[] credentialRequest,
+            byte[] setup, String servername, String context);
+
+    private static native byte[][] nativeClientLoginFinish(
+            String password, byte[] credentialResponse, byte[] state,
+            String username, String servername, String context);
+
+    private static native byte[] nativeServerLoginFinish(byte[] credentialFinalization, byte[] state);
+
+    private static native void nativeSetDefaultPasswordPolicy(boolean enabled);
+}
@@ -0,0 +1,8 @@
+package opaque;
+
+/** Thrown by {@link Opaque} when a step of the protocol fails. */
+public class OpaqueException extends RuntimeException {
+    public OpaqueException(String message) {
+        super(message);
+    }
+}
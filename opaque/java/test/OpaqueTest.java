@@ -0,0 +1,59 @@
+import java.util.Arrays;
+
+import opaque.Opaque;
+import opaque.OpaqueException;
+
+/** Same flows as happyPath and errorPath in examples/main.c: exits with 1 on failure. */
+public class OpaqueTest {
+    static final String PREFIX = "JAVA - LOG:";
+    static final String USERNAME = "pippo";
+    static final String SERVERNAME = "server";
+    static final String CONTEXT = "context";
+
+    // { setup, password file }
+    static byte[][] register(String password) {
+        Opaque.WithState clientStart = Opaque.clientRegistrationStart(password);
+        Opaque.WithSetup serverStart = Opaque.serverRegistrationStart(USERNAME, clientStart.data, null);
+        byte[] upload = Opaque.clientRegistrationFinish(
+                password, serverStart.data, clientStart.state, USERNAME, SERVERNAME);
+        return new byte[][] { serverStart.setup, Opaque.serverRegistrationFinish(upload) };
+    }
+
+    static boolean happyPath() {
+        byte[][] registration = register("ciao");
+
+        Opaque.WithState clientStart = Opaque.clientLoginStart("ciao");
+        Opaque.WithState serverStart = Opaque.serverLoginStart(
+                USERNAME, registration[1], clientStart.data, registration[0], SERVERNAME, CONTEXT);
+        Opaque.WithKey clientFinish = Opaque.clientLoginFinish(
+                "ciao", serverStart.data, clientStart.state, USERNAME, SERVERNAME, CONTEXT);
+        byte[] serverKey = Opaque.serverLoginFinish(clientFinish.data, serverStart.state);
+
+        boolean ok = Arrays.equals(clientFinish.sessionKey, serverKey);
+        System.out.println(PREFIX + " HAPPYPATH LOGIN " + (ok ? "SUCCESSFUL" : "FAILED"));
+        return ok;
+    }
+
+    static boolean errorPath() {
+        byte[][] registration = register("ciao");
+
+        Opaque.WithState clientStart = Opaque.clientLoginStart("wrong");
+        Opaque.WithState serverStart = Opaque.serverLoginStart(
+                USERNAME, registration[1], clientStart.data, registration[0], SERVERNAME, CONTEXT);
+        try {
+            Opaque.clientLoginFinish(
+                    "wrong", serverStart.data, clientStart.state, USERNAME, SERVERNAME, CONTEXT);
+        } catch (OpaqueException e) {
+            System.out.println(PREFIX + " ERRORPATH LOGIN FAILED: " + e.getMessage());
+            return true;
+        }
+        System.out.println(PREFIX + " ERRORPATH LOGIN SUCCESSFUL");
+        return false;
+    }
+
+    public static void main(String[] args) {
+        boolean ok = happyPath();
+        ok &= errorPath();
+        System.exit(ok ? 0 : 1);
+    }
+}
@@ -0,0 +1,169 @@
+# same flows as happyPath, errorPath and objectPath in examples/main.c
+
+import pytest
+
+import opaque
+
+USERNAME = "pippo"
+SERVERNAME = "server"
+CONTEXT = "context"
+
+
+def register(password):
+    request, client_state = opaque.client_registration_start(password)
+    response, setup = opaque.server_registration_start(USERNAME, request)
+    upload = opaque.client_registration_finish(
+        password, response, client_state, USERNAME, SERVERNAME
+    )
+    password_file = opaque.server_registration_finish(upload)
+    return setup, password_file
+
+
+def test_happy_path():
+    setup, password_file = register("ciao")
+
+    request, client_state = opaque.client_login_start("ciao")
+    response, server_state = opaque.server_login_start(
+        USERNAME, password_file, request, setup, SERVERNAME, CONTEXT
+    )
+    finalization, client_key = opaque.client_login_finish(
+        "ciao", response, client_state, USERNAME, SERVERNAME, CONTEXT
+    )
+    server_key = opaque.server_login_finish(finalization, server_state)
+
+    assert client_key == server_key
+
+
+def test_error_path():
+    setup, password_file = register("ciao")
+
+    request, client_state = opaque.client_login_start("wrong")
+    response, server_state = opaque.server_login_start(
+        USERNAME, password_file, request, setup, SERVERNAME, CONTEXT
+    )
+    with pytest.raises(opaque.OpaqueError):
+        opaque.client_login_finish(
+            "wrong", response, client_state, USERNAME, SERVERNAME, CONTEXT
+        )
+
+
+def test_malformed_message():
+    with pytest.raises(opaque.OpaqueError):
+        opaque.server_registration_finish(b"not an upload")
+
+
+def test_object_path():
+    server = opaque.Server(SERVERNAME, CONTEXT)
+    client = opaque.Client(USERNAME, SERVERNAME, CONTEXT)
+
+    request = client.begin_registration("ciao")
+    response = server.handle_registration_request(USERNAME, request)
+    upload, _, server_public_key = client.complete_registration(response)
+    server.handle_registration_upload(USERNAME, upload)
+    assert server_public_key == server.public_key()
+
+    # a registered username can't be registered again
+    request = client.begin_registration("other")
+    with pytest.raises(opaque.OpaqueError):
+        server.handle_registration_request(USERNAME, request)
+
+    request = client.begin_login("ciao")
+    response = server.handle_credential_request(USERNAME, "session-1", request)
+    finalization, client_key, _, _ = client.complete_login(response)
+    server_key = server.handle_credential_finalization(
+        USERNAME, "session-1", finalization
+    )
+    assert client_key == server_key
+
+    # a login state is used only once
+    with pytest.raises(opaque.OpaqueError):
+        server.handle_credential_finalization(USERNAME, "session-1", finalization)
+
+
+# (password, normalization, normalized password)
+NORMALIZATION_VECTORS = [
+    # "café" composed (U+00E9) and decomposed (e + U+0301)
+    ("caf\u00e9", "nfc", "caf\u00e9"),
+    ("cafe\u0301", "nfc", "caf\u00e9"),
+    ("cafe\u0301", "nfkc", "caf\u00e9"),
+    ("cafe\u0301", "none", "cafe\u0301"),
+    # Hangul syllable U+AC00 from its jamo
+    ("\u1100\u1161", "nfc", "\uac00"),
+    # ANGSTROM SIGN is a singleton of U+00C5
+    ("\u212b", "nfc", "\u00c5"),
+    # compatibility characters are only replaced by NFKC
+    ("\ufb01", "nfc", "\ufb01"),
+    ("\ufb01", "nfkc", "fi"),
+    ("\uff21", "nfkc", "A"),
+    # NO-BREAK SPACE becomes a space
+    ("a\u00a0b", "nfc", "a b"),
+    ("a\u00a0b", "nfkc", "a b"),
+]
+
+
+@pytest.mark.parametrize("password,normalization,expected", NORMALIZATION_VECTORS)
+def test_normalization_vectors(password, normalization, expected):
+    assert opaque.normalize_password(password, normalization) == expected
+
+
+def test_decomposed_registration_composed_login():
+    # none by default: NFC is opt-in
+    opaque.set_password_normalization("nfc")
+    try:
+        setup, password_file = register("cafe\u0301")
+
+        request, client_state = opaque.client_login_start("caf\u00e9")
+        response, server_state = opaque.server_login_start(
+            USERNAME, password_file, request, setup, SERVERNAME, CONTEXT
+        )
+        finalization, client_key = opaque.client_login_finish(
+            "caf\u00e9", response, client_state, USERNAME, SERVERNAME, CONTEXT
+        )
+
+        assert client_key == opaque.server_login_finish(finalization, server_state)
+    finally:
+        opaque.set_password_normalization("none")
+
+
+def test_unknown_normalization():
+    with pytest.raises(opaque.OpaqueError):
+        opaque.set_password_normalization("nfd")
+
+
+def issue_codes(password, username=USERNAME):
+    return {code for code, _ in opaque.check_password(password, username)}
+
+
+def test_check_password():
+    assert issue_codes("") == {"too_short", "too_few_character_classes"}
+    assert issue_codes("Password1!") == {"common"}
+    assert issue_codes("Pippo-2024!") == {"contains_username"}
+    assert issue_codes("zzzzzzzzzzzz") == {"too_few_character_classes", "repetitive"}
+    assert issue_codes("correct horse battery staple") == set()
+
+
+def test_client_policy_refuses_weak_password():
+    client = opaque.Client(USERNAME, SERVERNAME, CONTEXT)
+    client.set_default_password_policy()
+
+    with pytest.raises(opaque.OpaqueError, match="weak password"):
+        client.begin_registration("ciao")
+    assert client.begin_registration("correct horse battery staple")
+
+
+def test_default_policy_refuses_weak_password_without_client():
+    opaque.set_default_password_policy()
+    try:
+        with pytest.raises(opaque.OpaqueError, match="weak password"):
+            opaque.client_registration_start("ciao")
+        # registration finish knows the username
+        password = "Pippo-2024!"
+        request, client_state = opaque.client_registration_start(password)
+        response, _ = opaque.server_registration_start(USERNAME, request)
+        with pytest.raises(opaque.OpaqueError, match="weak password"):
+            opaque.client_registration_finish(
+                password, response, client_state, USERNAME, SERVERNAME
+            )
+    finally:
+        opaque.set_default_password_policy(False)
+    assert opaque.client_registration_start("ciao")
@@ -0,0 +1,431 @@
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::Arc;
+
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_seal::{reseal, seal, sealed_header, unseal, Kek, RecordType};
+use rust::opaque_server_facade::{
+    generate_setup, setup_public_key, verify_password_file, OpaqueServer,
+};
+use rust::opaque_store::{CredentialStore, FileCredentialStore};
+
+const USAGE: &str = "usage: opaque-cli [--store FILE] [--servername NAME] [--context CONTEXT]
+                  [--script FILE]
+       opaque-cli keys <generate|pubkey|reseal|verify|info> [options]
+       opaque-cli keys help";
+
+const KEYS_HELP: &str = "key management, KEK files hold 32 bytes written as 64 hex characters:
+  keys generate --out FILE [--kek FILE --kek-id N --servername NAME]
+        write a new server setup, sealed if a KEK is given
+  keys pubkey --setup FILE [--kek FILE --kek-id N --servername NAME]
+        print the public key of a server setup
+  keys reseal --setup FILE --kek FILE --kek-id N --new-kek FILE --new-kek-id N
+              --out FILE [--servername NAME]
+        seal a server setup again under a new KEK
+  keys verify --password-file FILE [--kek FILE --kek-id N --username NAME]
+        check that a password file parses for the configured ciphersuite
+  keys info --file FILE
+        report format version, record type and KEK id of a sealed record";
+
+const HELP: &str = "commands:
+  keygen                        generate a new server setup (old registrations stop working)
+  pubkey                        print the server public key
+  register <username> <password> register a user
+  login <username> <password>   log in a user
+  delete <username>             remove the password file of a user
+  script <file>                 run the commands written in file, one per line
+  expect-fail <command>         run command, failing if it succeeds (for scripts)
+  help                          show this message
+  quit                          exit";
+
+// client and server both run in this process, sharing the store on disk
+struct Session {
+    store: Arc<FileCredentialStore>,
+    servername: String,
+    context: String,
+    server: OpaqueServer,
+}
+
+fn exit_with(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
+
+// print name and size of a message, followed by its hex dump (16 bytes per line)
+fn dump(name: &str, data: &[u8]) {
+    println!("{} ({} bytes)", name, data.len());
+    for chunk in data.chunks(16) {
+        let line: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        println!("    {}", line.join(" "));
+    }
+}
+
+impl Session {
+    fn open(store_path: &str, servername: String, context: String) -> Result<Session, String> {
+        let store =
+            Arc::new(FileCredentialStore::open(store_path).map_err(|err| err.to_string())?);
+        let server = Session::new_server(&store, &servername, &context)?;
+        Ok(Session {
+            store,
+            servername,
+            context,
+            server,
+        })
+    }
+
+    // server on the setup saved in store, the store being shared with the session
+    fn new_server(
+        store: &Arc<FileCredentialStore>,
+        servername: &str,
+        context: &str,
+    ) -> Result<OpaqueServer, String> {
+        OpaqueServer::load_or_generate(
+            servername.to_string(),
+            context.to_string(),
+            Box::new(store.clone()),
+        )
+        .map_err(|err| err.to_string())
+    }
+
+    fn keygen(&mut self) -> Result<(), String> {
+        self.store
+            .put_server_setup(&generate_setup())
+            .map_err(|err| err.to_string())?;
+        self.server = Session::new_server(&self.store, &self.servername, &self.context)?;
+        dump("server public key", &self.server.public_key());
+        Ok(())
+    }
+
+    fn register(&self, username: &str, password: &str) -> Result<(), String> {
+        let mut client =
+            OpaqueClient::new(username.to_string(), self.servername.clone(), self.context.clone());
+
+        let request = client
+            .begin_registration(password)
+            .map_err(|err| err.to_string())?;
+        dump("registration request", &request);
+
+        let response = self
+            .server
+            .handle_registration_request(username, &request)
+            .map_err(|err| err.to_string())?;
+        dump("registration response", &response);
+
+        let result = client
+            .complete_registration(&response)
+            .map_err(|err| err.to_string())?;
+        dump("registration upload", &result.upload);
+        dump("export key", &result.export_key);
+        dump("server public key", &result.server_public_key);
+
+        self.server
+            .handle_registration_upload(username, &result.upload)
+            .map_err(|err| err.to_string())?;
+        println!("user {} registered", username);
+        Ok(())
+    }
+
+    fn login(&self, username: &str, password: &str) -> Result<(), String> {
+        let mut client =
+            OpaqueClient::new(username.to_string(), self.servername.clone(), self.context.clone());
+        let session_id = "opaque-cli";
+
+        let request = client.begin_login(password).map_err(|err| err.to_string())?;
+        dump("credential request", &request);
+
+        let response = self
+            .server
+            .handle_credential_request(username, session_id, &request)
+            .map_err(|err| err.to_string())?;
+        dump("credential response", &response);
+
+        let result = client
+            .complete_login(&response)
+            .map_err(|err| format!("client detected login failure: {}", err))?;
+        dump("credential finalization", &result.credential_finalization);
+        dump("client session key", &result.session_key);
+        dump("export key", &result.export_key);
+
+        let session_key = self
+            .server
+            .handle_credential_finalization(username, session_id, &result.credential_finalization)
+            .map_err(|err| format!("server detected login failure: {}", err))?;
+        dump("server session key", &session_key);
+
+        if session_key != result.session_key {
+            return Err("session keys don't match".to_string());
+        }
+        println!("user {} logged in", username);
+        Ok(())
+    }
+
+    fn delete(&self, username: &str) -> Result<(), String> {
+        match self.store.delete_password_file(username.as_bytes()) {
+            Ok(true) => {
+                println!("user {} deleted", username);
+                Ok(())
+            }
+            Ok(false) => Err(format!("user {} not registered", username)),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    fn script(&mut self, path: &str) -> Result<(), String> {
+        let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            println!("> {}", line);
+            if !self.run(line)? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    // run a single command: return false when the session must end
+    fn run(&mut self, line: &str) -> Result<bool, String> {
+        if let Some(command) = line.trim().strip_prefix("expect-fail ") {
+            return match self.run(command) {
+                Ok(_) => Err(format!("'{}' succeeded, a failure was expected", command.trim())),
+                Err(err) => {
+                    println!("failed as expected: {}", err);
+                    Ok(true)
+                }
+            };
+        }
+
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["keygen"] => self.keygen()?,
+            ["pubkey"] => dump("server public key", &self.server.public_key()),
+            ["register", username, password] => self.register(username, password)?,
+            ["login", username, password] => self.login(username, password)?,
+            ["delete", username] => self.delete(username)?,
+            ["script", path] => self.script(path)?,
+            ["help"] => println!("{}", HELP),
+            ["quit"] | ["exit"] => return Ok(false),
+            _ => return Err(format!("unknown command '{}', type help", line)),
+        }
+        Ok(true)
+    }
+}
+
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let text = text.trim();
+    if text.len() % 2 != 0 {
+        return Err("odd number of hex characters".to_string());
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&text[i..i + 2], 16).map_err(|_| "invalid hex characters".to_string())
+        })
+        .collect()
+}
+
+// options of a keys subcommand, given as --name value
+struct KeysOptions {
+    values: Vec<(String, String)>,
+}
+
+impl KeysOptions {
+    fn parse(args: &[String]) -> Result<KeysOptions, String> {
+        if args.len() % 2 != 0 || args.iter().step_by(2).any(|a| !a.starts_with("--")) {
+            return Err(KEYS_HELP.to_string());
+        }
+        Ok(KeysOptions {
+            values: args
+                .chunks(2)
+                .map(|pair| (pair[0][2..].to_string(), pair[1].clone()))
+                .collect(),
+        })
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.get(name).ok_or(format!("missing --{}", name))
+    }
+
+    fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        let path = self.required(name)?;
+        fs::read(path).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> Result<(), String> {
+        let path = self.required(name)?;
+        fs::write(path, data).map_err(|err| format!("{}: {}", path, err))
+    }
+
+    // KEK read from the file given as --<prefix> with id --<prefix>-id
+    fn kek(&self, prefix: &str) -> Result<Option<Kek>, String> {
+        let path = match self.get(prefix) {
+            Some(val) => val,
+            None => return Ok(None),
+        };
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let id = self
+            .required(&format!("{}-id", prefix))?
+            .parse::<u32>()
+            .map_err(|_| format!("--{}-id must be a number", prefix))?;
+        Kek::new(id, &parse_hex(&text)?)
+            .map(Some)
+            .map_err(|err| err.to_string())
+    }
+
+    fn servername(&self) -> &str {
+        self.get("servername").unwrap_or("servername")
+    }
+}
+
+// server setup read from --setup, unsealed when a KEK is given
+fn read_setup(opts: &KeysOptions) -> Result<Vec<u8>, String> {
+    let setup = opts.read("setup")?;
+    match opts.kek("kek")? {
+        Some(kek) => unseal(&kek, RecordType::ServerSetup, opts.servername(), &setup)
+            .map_err(|err| err.to_string()),
+        None => Ok(setup),
+    }
+}
+
+fn keys(args: &[String]) -> Result<(), String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err(KEYS_HELP.to_string()),
+    };
+    let opts = KeysOptions::parse(rest)?;
+
+    match command {
+        "generate" => {
+            let setup = generate_setup();
+            let public_key = setup_public_key(&setup).map_err(|err| err.to_string())?;
+            let output = match opts.kek("kek")? {
+                Some(kek) => seal(&kek, RecordType::ServerSetup, opts.servername(), &setup),
+                None => setup,
+            };
+            opts.write("out", &output)?;
+            dump("server public key", &public_key);
+        }
+        "pubkey" => {
+            let setup = read_setup(&opts)?;
+            let public_key = setup_public_key(&setup).map_err(|err| err.to_string())?;
+            dump("server public key", &public_key);
+        }
+        "reseal" => {
+            let sealed = opts.read("setup")?;
+            let old_kek = opts.kek("kek")?.ok_or("missing --kek")?;
+            let new_kek = opts.kek("new-kek")?.ok_or("missing --new-kek")?;
+            let resealed = reseal(
+                &old_kek,
+                &new_kek,
+                RecordType::ServerSetup,
+                opts.servername(),
+                &sealed,
+            )
+            .map_err(|err| err.to_string())?;
+            opts.write("out", &resealed)?;
+            println!("server setup sealed under KEK {}", new_kek.id);
+        }
+        "verify" => {
+            let mut password_file = opts.read("password-file")?;
+            if let Some(kek) = opts.kek("kek")? {
+                password_file = unseal(
+                    &kek,
+                    RecordType::PasswordFile,
+                    opts.required("username")?,
+                    &password_file,
+                )
+                .map_err(|err| err.to_string())?;
+            }
+            verify_password_file(&password_file).map_err(|err| err.to_string())?;
+            println!("password file is valid");
+        }
+        "info" => {
+            let record = opts.read("file")?;
+            match sealed_header(&record) {
+                Ok(header) => {
+                    println!("format version: {}", header.version);
+                    println!("record type: {:?}", header.record_type);
+                    println!("KEK id: {}", header.kek_id);
+                }
+                Err(err) => println!("not a sealed record ({}), {} bytes", err, record.len()),
+            }
+        }
+        "help" => println!("{}", KEYS_HELP),
+        _ => return Err(KEYS_HELP.to_string()),
+    }
+    Ok(())
+}
+
+fn main() {
+    // key management subcommands don't need any store
+    let all_args: Vec<String> = env::args().skip(1).collect();
+    if all_args.first().map(|a| a.as_str()) == Some("keys") {
+        if let Err(err) = keys(&all_args[1..]) {
+            exit_with(&err);
+        }
+        return;
+    }
+
+    let mut store_path = "opaque-cli.store".to_string();
+    let mut servername = "servername".to_string();
+    let mut context = "context".to_string();
+    let mut script = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(val) => val,
+            None => exit_with(USAGE),
+        };
+        match arg.as_str() {
+            "--store" => store_path = value(),
+            "--servername" => servername = value(),
+            "--context" => context = value(),
+            "--script" => script = Some(value()),
+            _ => exit_with(USAGE),
+        }
+    }
+
+    let mut session =
+        Session::open(&store_path, servername, context).unwrap_or_else(|err| exit_with(&err));
+
+    // scripted session: stop at the first failing command,
+    // unless it is prefixed by expect-fail
+    if let Some(path) = script {
+        if let Err(err) = session.script(&path) {
+            exit_with(&format!("error: {}", err));
+        }
+        return;
+    }
+
+    let mut rl = Editor::<()>::new().unwrap_or_else(|err| exit_with(&err.to_string()));
+    println!("{}", HELP);
+    loop {
+        match rl.readline("opaque> ") {
+            Ok(line) => {
+                rl.add_history_entry(line.as_str());
+                match session.run(&line) {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(err) => println!("error: {}", err),
+                }
+            }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+            Err(err) => exit_with(&err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+use rust::opaque_http::serve;
+use rust::opaque_server_facade::OpaqueServer;
+#[cfg(feature = "sqlite")]
+use rust::opaque_store_sqlite::SqliteCredentialStore;
+use rust::opaque_store::{CredentialStore, FileCredentialStore, MemoryCredentialStore};
+
+const USAGE: &str = "usage: opaque-server [--listen ADDR] [--servername NAME] [--context CONTEXT]
+                     [--store FILE | --sqlite FILE] [--workers N]";
+
+fn exit_with(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
+
+fn main() {
+    let mut listen = "127.0.0.1:8080".to_string();
+    let mut servername = "servername".to_string();
+    let mut context = "context".to_string();
+    let mut store_path = None;
+    let mut sqlite_path = None;
+    let mut workers = 4;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(val) => val,
+            None => exit_with(USAGE),
+        };
+        match arg.as_str() {
+            "--listen" => listen = value(),
+            "--servername" => servername = value(),
+            "--context" => context = value(),
+            "--store" => store_path = Some(value()),
+            "--sqlite" => sqlite_path = Some(value()),
+            "--workers" => workers = value().parse().unwrap_or_else(|_| exit_with(USAGE)),
+            _ => exit_with(USAGE),
+        }
+    }
+
+    // password files and server setup are kept in memory unless a store is given
+    let store: Box<dyn CredentialStore> = match (store_path, sqlite_path) {
+        (Some(_), Some(_)) => exit_with(USAGE),
+        (Some(path), None) => Box::new(
+            FileCredentialStore::open(&path).unwrap_or_else(|err| exit_with(&err.to_string())),
+        ),
+        #[cfg(feature = "sqlite")]
+        (None, Some(path)) => Box::new(
+            SqliteCredentialStore::open(&path).unwrap_or_else(|err| exit_with(&err.to_string())),
+        ),
+        #[cfg(not(feature = "sqlite"))]
+        (None, Some(_)) => exit_with("opaque-server built without the sqlite feature"),
+        (None, None) => Box::new(MemoryCredentialStore::new()),
+    };
+
+    let server = OpaqueServer::load_or_generate(servername, context, store)
+        .unwrap_or_else(|err| exit_with(&err.to_string()));
+    if let Err(err) = serve(Arc::new(server), &listen, workers) {
+        exit_with(&err);
+    }
+}
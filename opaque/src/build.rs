@@ -8,4 +8,21 @@ fn main() {
         .generate()
         .expect("Unable to generate binding headers")
         .write_to_file("./examples/librust.h");
+
+    #[cfg(feature = "proto")]
+    compile_proto();
+}
+
+// Rust types of proto/opaque.proto, included by src/opaque_proto.rs
+#[cfg(feature = "proto")]
+fn compile_proto() {
+    std::env::set_var(
+        "PROTOC",
+        protoc_bin_vendored::protoc_bin_path().expect("Unable to find protoc"),
+    );
+    prost_build::compile_protos(&["proto/opaque.proto"], &["proto/"])
+        .expect("Unable to generate proto/opaque.proto types");
+    // prost-build limits the reruns to the proto file: the headers above
+    // have to be generated again on every change of the sources
+    println!("cargo:rerun-if-changed=src");
 }
@@ -3,15 +3,74 @@ use std::env;
 use std::ffi::CStr;
 use std::os::raw::c_char;
 
+use opaque_channel::{Role, SecureChannel};
 use opaque_client::{
     client_login_finish, client_login_start, client_registration_finish, client_registration_start,
 };
+use opaque_channel_binding::channel_binding_context;
+use opaque_client_facade::{login_finish, registration_finish, OpaqueClient};
+use opaque_config::ProtocolConfig;
+use opaque_envelope::{decode_envelope, encode_envelope, envelope_header, MessageType};
+use opaque_error::OpaqueError;
+use opaque_password::{normalize_password_with, set_password_normalization, PasswordNormalization};
+use opaque_password_policy::{
+    check_password_policy, estimate_strength, password_issues, set_default_password_policy,
+    DefaultPasswordPolicy, PasswordIssue,
+};
+use opaque_store::{CredentialStore, FileCredentialStore, MemoryCredentialStore};
+use opaque_password_change::{
+    PasswordChangeFinalization, PasswordChangeRequest, PasswordChangeResponse,
+};
+use opaque_recovery::{
+    find_recovery_record, generate_recovery_codes, recovery_secret, recovery_verifier,
+    secret_from_export_key, take_recovery_record, unwrap_recovery_secret,
+    wrap_secret_with_export_key, RecoveryFinalization, RecoveryRequest, RecoveryResponse,
+};
+use opaque_login_state::{
+    LoginStatePolicy, MemoryReplayCache, ReplayCache, DEFAULT_LOGIN_STATE_MAX_AGE,
+};
+use opaque_seal::{sealed_header, Kek, RecordType};
 use opaque_server::{
     server_login_finish, server_login_start, server_registration_finish, server_registration_start,
 };
+use opaque_server_facade::{
+    bound_password_file_from_upload, credential_response, deserialize_setup, OpaqueServer,
+};
+use opaque_state_token::{server_login_finish_stateless, server_login_start_stateless};
 
+pub mod opaque_channel;
+pub mod opaque_channel_binding;
 mod opaque_client;
+pub mod opaque_client_facade;
+pub mod opaque_config;
+pub mod opaque_envelope;
+pub mod opaque_error;
+#[cfg(feature = "http-server")]
+pub mod opaque_http;
+#[cfg(feature = "jni")]
+mod opaque_jni;
+pub mod opaque_login_state;
+pub mod opaque_password;
+pub mod opaque_password_policy;
+pub mod opaque_password_change;
+pub mod opaque_recovery;
+pub mod opaque_seal;
+#[cfg(feature = "proto")]
+pub mod opaque_proto;
+#[cfg(feature = "python")]
+mod opaque_python;
+#[cfg(feature = "serde")]
+pub mod opaque_serde;
 mod opaque_server;
+#[cfg(feature = "async")]
+pub mod opaque_server_async;
+pub mod opaque_server_facade;
+pub mod opaque_state_token;
+pub mod opaque_store;
+#[cfg(feature = "sqlite")]
+pub mod opaque_store_sqlite;
+#[cfg(feature = "wasm")]
+pub mod opaque_wasm;
 
 // const RUST_LOG: &str = "RUST::";
 
@@ -21,8 +80,46 @@ lazy_static! {
         Ok(val) => val.parse::<bool>().unwrap(),
         Err(_) => false,
     };
+    // max age, in seconds, of the server login state accepted by opaque_server_login_finish
+    static ref LOGIN_STATE_MAX_AGE: std::time::Duration = match env::var("LOGIN_STATE_MAX_AGE") {
+        Ok(val) => match val.parse::<u64>() {
+            Ok(secs) => std::time::Duration::from_secs(secs),
+            Err(_) => {
+                println!(
+                    "RUST - LOG: Invalid LOGIN_STATE_MAX_AGE {}, using {:?}",
+                    val, DEFAULT_LOGIN_STATE_MAX_AGE
+                );
+                DEFAULT_LOGIN_STATE_MAX_AGE
+            }
+        },
+        Err(_) => DEFAULT_LOGIN_STATE_MAX_AGE,
+    };
+    // login states already finished by opaque_server_login_finish_with_policy
+    // and opaque_server_login_finish_stateless
+    static ref REPLAY_CACHE: MemoryReplayCache = MemoryReplayCache::new();
 }
 
+/// message types accepted by opaque_envelope_encode and opaque_envelope_decode
+pub const OPAQUE_MSG_REGISTRATION_REQUEST: u8 = 1;
+pub const OPAQUE_MSG_REGISTRATION_RESPONSE: u8 = 2;
+pub const OPAQUE_MSG_REGISTRATION_UPLOAD: u8 = 3;
+pub const OPAQUE_MSG_CREDENTIAL_REQUEST: u8 = 4;
+pub const OPAQUE_MSG_CREDENTIAL_RESPONSE: u8 = 5;
+pub const OPAQUE_MSG_CREDENTIAL_FINALIZATION: u8 = 6;
+
+/// normalizations accepted by opaque_set_password_normalization
+/// and opaque_normalize_password
+pub const OPAQUE_PASSWORD_NORMALIZATION_NONE: u8 = 1;
+pub const OPAQUE_PASSWORD_NORMALIZATION_NFC: u8 = 2;
+pub const OPAQUE_PASSWORD_NORMALIZATION_NFKC: u8 = 3;
+
+/// issues set in OpaquePasswordCheck by opaque_password_check
+pub const OPAQUE_PASSWORD_TOO_SHORT: u32 = 1;
+pub const OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES: u32 = 2;
+pub const OPAQUE_PASSWORD_COMMON: u32 = 4;
+pub const OPAQUE_PASSWORD_CONTAINS_USERNAME: u32 = 8;
+pub const OPAQUE_PASSWORD_REPETITIVE: u32 = 16;
+
 /// struct needed to pass byte array to C
 /// without loosing data and handling 'null
 /// bytes in the middle of a string' error
@@ -124,6 +221,193 @@ pub struct ServerLogState {
     size_state: usize,
 }
 
+/// struct needed to pass the key-encryption key
+/// used to seal password files and server setups:
+/// id is stored inside the sealed record to allow KEK rotation
+#[repr(C)]
+pub struct KeyEncryptionKey {
+    id: u32,
+    key: *const u8,
+    size_key: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_client_complete_registration
+#[repr(C)]
+pub struct OpaqueRegistrationResult {
+    upload: *const u8,
+    size_upload: usize,
+    export_key: *const u8,
+    size_export_key: usize,
+    server_public_key: *const u8,
+    size_server_public_key: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_recovery_codes_generate
+#[repr(C)]
+pub struct OpaqueRecoveryCodes {
+    codes: *const u8,
+    size_codes: usize,
+    records: *const u8,
+    size_records: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_client_begin_password_change
+#[repr(C)]
+pub struct OpaquePasswordChangeRequest {
+    credential_request: *const u8,
+    size_credential_request: usize,
+    registration_request: *const u8,
+    size_registration_request: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_server_handle_password_change_request
+#[repr(C)]
+pub struct OpaquePasswordChangeResponse {
+    credential_response: *const u8,
+    size_credential_response: usize,
+    registration_response: *const u8,
+    size_registration_response: usize,
+    wrapped_secret: *const u8,
+    size_wrapped_secret: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_client_complete_password_change
+#[repr(C)]
+pub struct OpaquePasswordChangeResult {
+    credential_finalization: *const u8,
+    size_credential_finalization: usize,
+    upload: *const u8,
+    size_upload: usize,
+    wrapped_secret: *const u8,
+    size_wrapped_secret: usize,
+    tag: *const u8,
+    size_tag: usize,
+    session_key: *const u8,
+    size_session_key: usize,
+    export_key: *const u8,
+    size_export_key: usize,
+    recovery_secret: *const u8,
+    size_recovery_secret: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_client_begin_recovery
+#[repr(C)]
+pub struct OpaqueRecoveryRequest {
+    verifier: *const u8,
+    size_verifier: usize,
+    registration_request: *const u8,
+    size_registration_request: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_server_handle_recovery_request
+#[repr(C)]
+pub struct OpaqueRecoveryResponse {
+    wrapped_secret: *const u8,
+    size_wrapped_secret: usize,
+    registration_response: *const u8,
+    size_registration_response: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_client_complete_recovery
+#[repr(C)]
+pub struct OpaqueRecoveryResult {
+    verifier: *const u8,
+    size_verifier: usize,
+    upload: *const u8,
+    size_upload: usize,
+    wrapped_secret: *const u8,
+    size_wrapped_secret: usize,
+    export_key: *const u8,
+    size_export_key: usize,
+    secret: *const u8,
+    size_secret: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_channel_open
+#[repr(C)]
+pub struct OpaqueChannelOpenResult {
+    /// false if the record was refused
+    ok: bool,
+    plaintext: *const u8,
+    size_plaintext: usize,
+}
+
+/// struct needed to return the result of
+/// opaque_password_check
+#[repr(C)]
+pub struct OpaquePasswordCheck {
+    /// OPAQUE_PASSWORD_* issues found, 0 if the password is acceptable
+    issues: u32,
+    /// 0 (very weak) to 4 (very strong)
+    score: u8,
+    entropy_bits: u32,
+}
+
+/// struct needed to return the result of
+/// opaque_client_complete_login
+#[repr(C)]
+pub struct OpaqueLoginResult {
+    credential_finalization: *const u8,
+    size_credential_finalization: usize,
+    session_key: *const u8,
+    size_session_key: usize,
+    export_key: *const u8,
+    size_export_key: usize,
+    server_public_key: *const u8,
+    size_server_public_key: usize,
+}
+
+// move a Vec<u8> to C, which becomes responsible for calling free_memlib on it
+fn opaque_from_vec(v: Vec<u8>) -> Opaque {
+    let s = v.len();
+    let ptr = Box::into_raw(v.into_boxed_slice()) as *const u8;
+    Opaque { data: ptr, size: s }
+}
+
+// bytes of an input: size 0 is an empty slice whatever data is, NULL
+// included, since from_raw_parts needs a non-null pointer even then
+unsafe fn slice_from_raw<'a>(data: *const u8, size: usize) -> &'a [u8] {
+    if size == 0 {
+        &[]
+    } else {
+        std::slice::from_raw_parts(data, size)
+    }
+}
+
+unsafe fn slice_from_opaque<'a>(input: &Opaque) -> &'a [u8] {
+    slice_from_raw(input.data, input.size)
+}
+
+// same as opaque_from_vec, for results made of a response and a state
+fn opaque_with_state_from_vecs(response: Vec<u8>, state: Vec<u8>) -> OpaqueWithState {
+    let data = opaque_from_vec(response);
+    let st = opaque_from_vec(state);
+    OpaqueWithState {
+        data: data.data,
+        size_data: data.size,
+        state: st.data,
+        size_state: st.size,
+    }
+}
+
+// read the key-encryption key passed by C
+fn kek_from_c(kek: &KeyEncryptionKey) -> Result<Kek, OpaqueError> {
+    let kek_bytes;
+    unsafe {
+        kek_bytes = slice_from_raw(kek.key, kek.size_key);
+    }
+    Kek::new(kek.id, kek_bytes)
+}
+
 /// function to deallocate Box pointers previously passed to C:
 /// MUST be called after used the pointed value in C
 #[no_mangle]
@@ -135,12 +419,17 @@ pub extern "C" fn free_memlib(ptr: *const u8) {
 
 /// first step of opaque registration: client registration start
 /// password: password typed by client
+/// empty if the policy of opaque_set_default_password_policy refuses password
 #[no_mangle]
 pub extern "C" fn opaque_client_registration_start(password: *const c_char) -> OpaqueWithState {
     let password_client;
     unsafe {
         password_client = CStr::from_ptr(password).to_str().unwrap();
     }
+    if let Err(err) = check_password_policy(password_client, "") {
+        println!("RUST - LOG: Client registration start error: {}", err);
+        return opaque_with_state_from_vecs(vec![], vec![]);
+    }
     let reg = client_registration_start(password_client.to_string());
     // println!("CLIENT REG START {:?}", reg);
 
@@ -163,7 +452,8 @@ pub extern "C" fn opaque_client_registration_start(password: *const c_char) -> O
 
 /// second step of opaque registration: server registration start
 /// registration_request: result of client registration start
-/// private_key: hexadecimal value of a 32 bytes private key
+/// private_key: hexadecimal value of a 32 bytes private key, or empty
+/// (data can be NULL) for a new random one
 #[no_mangle]
 pub extern "C" fn opaque_server_registration_start(
     username: *const c_char,
@@ -177,7 +467,7 @@ pub extern "C" fn opaque_server_registration_start(
         username_client = CStr::from_ptr(username).to_str().unwrap();
         request =
             std::slice::from_raw_parts(registration_request.data, registration_request.size_data);
-        priv_key = std::slice::from_raw_parts(private_key.data, private_key.size_data);
+        priv_key = slice_from_raw(private_key.data, private_key.size_data);
     }
     // println!("VEC SERVER REG START {:?}, {:?}", private_key.data, priv_key);
 
@@ -203,6 +493,8 @@ pub extern "C" fn opaque_server_registration_start(
 /// third step of opaque registration: client registration finish
 /// server_registration_start: result of server registration start
 /// client_reg_start_state: result of client registration start
+/// empty if the policy of opaque_set_default_password_policy refuses
+/// password for username
 #[no_mangle]
 pub extern "C" fn opaque_client_registration_finish(
     password: *const c_char,
@@ -231,6 +523,10 @@ pub extern "C" fn opaque_client_registration_finish(
         server = CStr::from_ptr(servername).to_str().unwrap();
     }
     // println!("VEC CLIENT REG FINISH {:?}", reg_response);
+    if let Err(err) = check_password_policy(password_client, user) {
+        println!("RUST - LOG: Client registration finish error: {}", err);
+        return opaque_from_vec(vec![]);
+    }
 
     let reg = client_registration_finish(
         password_client.to_string(), 
@@ -247,7 +543,8 @@ pub extern "C" fn opaque_client_registration_finish(
 }
 
 /// fourth step of opaque registration: server registration finish
-/// message: result of client registration finish
+/// message: result of client registration finish, the identities it
+/// was made with are checked only by opaque_server_registration_finish_with_config
 #[no_mangle]
 pub extern "C" fn opaque_server_registration_finish(message: Opaque) -> Opaque {
     let message_client;
@@ -383,8 +680,94 @@ pub extern "C" fn opaque_client_login_finish(
     }
 }
 
+/// same as opaque_server_login_start, with a binary context:
+/// context: e.g. result of opaque_channel_binding_context,
+/// the client MUST use the same one to finish the login
+/// every field is empty on error
+#[no_mangle]
+pub extern "C" fn opaque_server_login_start_with_context(
+    username: *const c_char,
+    password_file: Opaque,
+    credential_request: ClientLogStartResult,
+    serv_setup: ServerSetup,
+    servername: *const c_char,
+    context: Opaque,
+) -> OpaqueWithState {
+    let username_client;
+    let password_client;
+    let credential;
+    let server_setup;
+    let server;
+    let ctx;
+    unsafe {
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        password_client = std::slice::from_raw_parts(password_file.data, password_file.size);
+        credential =
+            std::slice::from_raw_parts(credential_request.data, credential_request.size_data);
+        server_setup = std::slice::from_raw_parts(serv_setup.setup, serv_setup.size_setup);
+        server = CStr::from_ptr(servername).to_str().unwrap();
+        ctx = std::slice::from_raw_parts(context.data, context.size);
+    }
+
+    let result = deserialize_setup(server_setup).and_then(|setup| {
+        credential_response(
+            &setup,
+            &ProtocolConfig::new(server, ctx),
+            username_client,
+            Some(password_client),
+            credential,
+        )
+    });
+    match result {
+        Ok((response, state)) => opaque_with_state_from_vecs(response, state),
+        Err(err) => {
+            println!("RUST - LOG: Server login start error: {}", err);
+            opaque_with_state_from_vecs(vec![], vec![])
+        }
+    }
+}
+
+/// same as opaque_client_login_finish, with a binary context:
+/// login fails if it isn't the one used by the server
+/// context: e.g. result of opaque_channel_binding_context
+#[no_mangle]
+pub extern "C" fn opaque_client_login_finish_with_context(
+    password: *const c_char,
+    login_response: ServerLogStartResult,
+    client_login_state: ClientLogState,
+    username: *const c_char,
+    servername: *const c_char,
+    context: Opaque,
+) -> Opaque {
+    let password_client;
+    let log_response;
+    let client_state;
+    let user;
+    let server;
+    let ctx;
+    unsafe {
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+        log_response = std::slice::from_raw_parts(login_response.data, login_response.size_data);
+        client_state =
+            std::slice::from_raw_parts(client_login_state.state, client_login_state.size_state);
+        user = CStr::from_ptr(username).to_str().unwrap();
+        server = CStr::from_ptr(servername).to_str().unwrap();
+        ctx = std::slice::from_raw_parts(context.data, context.size);
+    }
+
+    let config = ProtocolConfig::new(server, ctx);
+    match login_finish(password_client, log_response, client_state, user, &config) {
+        Ok(result) => opaque_from_vec(result.credential_finalization),
+        Err(err) => {
+            println!("RUST - LOG: Client detected login failure: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
 /// fourth step of opaque login: server login finish
-/// server_login_state: result of server login start
+/// server_login_state: result of server login start,
+/// refused if older than LOGIN_STATE_MAX_AGE seconds (default 300)
 #[no_mangle]
 pub extern "C" fn opaque_server_login_finish(
     credential_finalization: Opaque,
@@ -401,6 +784,1741 @@ pub extern "C" fn opaque_server_login_finish(
 
     server_login_finish(
         credential,
-        server_state
+        server_state,
+        &LoginStatePolicy {
+            max_age: *LOGIN_STATE_MAX_AGE,
+            replay_cache: None,
+        },
+    )
+}
+
+/// fourth step of opaque login: server login finish with custom checks
+/// max_age_seconds: server login state older than this is refused
+/// single_use: refuse a server login state already used in this process
+#[no_mangle]
+pub extern "C" fn opaque_server_login_finish_with_policy(
+    credential_finalization: Opaque,
+    server_login_state: ServerLogState,
+    max_age_seconds: u64,
+    single_use: bool,
+) -> bool {
+    let credential;
+    let server_state;
+    unsafe {
+        credential =
+            std::slice::from_raw_parts(credential_finalization.data, credential_finalization.size);
+        server_state =
+            std::slice::from_raw_parts(server_login_state.state, server_login_state.size_state);
+    }
+
+    let replay_cache: Option<&dyn ReplayCache> = if single_use {
+        Some(&*REPLAY_CACHE)
+    } else {
+        None
+    };
+    server_login_finish(
+        credential,
+        server_state,
+        &LoginStatePolicy {
+            max_age: std::time::Duration::from_secs(max_age_seconds),
+            replay_cache,
+        },
     )
 }
+
+// shared body of every seal function exported to C
+fn seal_record(
+    kek: KeyEncryptionKey,
+    record_type: RecordType,
+    owner: *const c_char,
+    data: &[u8],
+) -> Opaque {
+    let owner_name;
+    unsafe {
+        owner_name = CStr::from_ptr(owner).to_str().unwrap();
+    }
+
+    match kek_from_c(&kek) {
+        Ok(k) => opaque_from_vec(opaque_seal::seal(&k, record_type, owner_name, data)),
+        Err(err) => {
+            println!("RUST - LOG: Seal error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+// shared body of every unseal function exported to C
+fn unseal_record(
+    kek: KeyEncryptionKey,
+    record_type: RecordType,
+    owner: *const c_char,
+    sealed: Opaque,
+) -> Opaque {
+    let owner_name;
+    let sealed_record;
+    unsafe {
+        owner_name = CStr::from_ptr(owner).to_str().unwrap();
+        sealed_record = slice_from_opaque(&sealed);
+    }
+
+    let result = kek_from_c(&kek)
+        .and_then(|k| opaque_seal::unseal(&k, record_type, owner_name, sealed_record));
+    match result {
+        Ok(plaintext) => opaque_from_vec(plaintext),
+        Err(err) => {
+            println!("RUST - LOG: Unseal error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// seal a password file before storing it
+/// kek: 32 bytes key-encryption key
+/// username: owner of the password file, bound to the sealed record
+/// password_file: result of server registration finish
+#[no_mangle]
+pub extern "C" fn opaque_seal_password_file(
+    kek: KeyEncryptionKey,
+    username: *const c_char,
+    password_file: Opaque,
+) -> Opaque {
+    let pass;
+    unsafe {
+        pass = slice_from_opaque(&password_file);
+    }
+    seal_record(kek, RecordType::PasswordFile, username, pass)
+}
+
+/// unseal a password file previously sealed with opaque_seal_password_file
+/// sealed: result of opaque_seal_password_file
+#[no_mangle]
+pub extern "C" fn opaque_unseal_password_file(
+    kek: KeyEncryptionKey,
+    username: *const c_char,
+    sealed: Opaque,
+) -> Opaque {
+    unseal_record(kek, RecordType::PasswordFile, username, sealed)
+}
+
+/// seal a server setup before storing it
+/// kek: 32 bytes key-encryption key
+/// servername: server identity, bound to the sealed record
+/// serv_setup: result of server registration start
+#[no_mangle]
+pub extern "C" fn opaque_seal_server_setup(
+    kek: KeyEncryptionKey,
+    servername: *const c_char,
+    serv_setup: ServerSetup,
+) -> Opaque {
+    let setup;
+    unsafe {
+        setup = slice_from_raw(serv_setup.setup, serv_setup.size_setup);
+    }
+    seal_record(kek, RecordType::ServerSetup, servername, setup)
+}
+
+/// unseal a server setup previously sealed with opaque_seal_server_setup
+/// sealed: result of opaque_seal_server_setup
+#[no_mangle]
+pub extern "C" fn opaque_unseal_server_setup(
+    kek: KeyEncryptionKey,
+    servername: *const c_char,
+    sealed: Opaque,
+) -> Opaque {
+    unseal_record(kek, RecordType::ServerSetup, servername, sealed)
+}
+
+/// return the id of the KEK used to seal a record,
+/// or -1 if the record is malformed
+#[no_mangle]
+pub extern "C" fn opaque_sealed_kek_id(sealed: Opaque) -> i64 {
+    let sealed_record;
+    unsafe {
+        sealed_record = slice_from_opaque(&sealed);
+    }
+    match sealed_header(sealed_record) {
+        Ok(header) => header.kek_id as i64,
+        Err(err) => {
+            println!("RUST - LOG: Sealed record error: {}", err);
+            -1
+        }
+    }
+}
+
+/// second step of opaque login without server side session storage:
+/// same as opaque_server_login_start, but the returned state is a token
+/// sealed under kek that the client sends back with its login finish result,
+/// response and token are both empty if the login start failed
+/// ttl_seconds: validity of the token
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub extern "C" fn opaque_server_login_start_stateless(
+    kek: KeyEncryptionKey,
+    ttl_seconds: u64,
+    username: *const c_char,
+    password_file: Opaque,
+    credential_request: ClientLogStartResult,
+    serv_setup: ServerSetup,
+    servername: *const c_char,
+    context: *const c_char,
+) -> OpaqueWithState {
+    let username_client;
+    let password_client;
+    let credential;
+    let server_setup;
+    let server;
+    let ctx;
+    unsafe {
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        password_client = slice_from_opaque(&password_file);
+        credential = slice_from_raw(credential_request.data, credential_request.size_data);
+        server_setup = slice_from_raw(serv_setup.setup, serv_setup.size_setup);
+        server = CStr::from_ptr(servername).to_str().unwrap();
+        ctx = CStr::from_ptr(context).to_str().unwrap();
+    }
+
+    let key = match kek_from_c(&kek) {
+        Ok(k) => k,
+        Err(err) => {
+            println!("RUST - LOG: Server login start error: {}", err);
+            return opaque_with_state_from_vecs(vec![], vec![]);
+        }
+    };
+
+    let login_start = server_login_start_stateless(
+        &key,
+        std::time::Duration::from_secs(ttl_seconds),
+        username_client.to_string(),
+        password_client,
+        credential,
+        server_setup,
+        server.to_string(),
+        ctx.to_string(),
+    );
+    opaque_with_state_from_vecs(login_start.response, login_start.state)
+}
+
+/// fourth step of opaque login without server side session storage
+/// credential_finalization: result of client login finish
+/// token: state returned by opaque_server_login_start_stateless, accepted
+/// until the ttl given at login start and only once in this process
+#[no_mangle]
+pub extern "C" fn opaque_server_login_finish_stateless(
+    kek: KeyEncryptionKey,
+    username: *const c_char,
+    credential_finalization: Opaque,
+    token: Opaque,
+) -> bool {
+    let username_client;
+    let credential;
+    let login_token;
+    unsafe {
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        credential = slice_from_opaque(&credential_finalization);
+        login_token = slice_from_opaque(&token);
+    }
+
+    match kek_from_c(&kek) {
+        Ok(key) => server_login_finish_stateless(
+            &key,
+            username_client,
+            credential,
+            login_token,
+            Some(&*REPLAY_CACHE),
+        ),
+        Err(err) => {
+            println!("RUST - LOG: Server login finish error: {}", err);
+            false
+        }
+    }
+}
+
+/// create an OpaqueServer, which owns server setup, stored password files
+/// and login states: MUST be released with opaque_server_free
+/// serv_setup: result of server registration start, or empty to use
+/// the setup saved in the store (generating it the first time)
+/// store_path: file where password files are saved, or NULL to keep them in memory
+/// returns NULL on error
+#[no_mangle]
+pub extern "C" fn opaque_server_new(
+    serv_setup: ServerSetup,
+    servername: *const c_char,
+    context: *const c_char,
+    store_path: *const c_char,
+) -> *mut OpaqueServer {
+    let server;
+    let ctx;
+    let path;
+    unsafe {
+        server = CStr::from_ptr(servername).to_str().unwrap();
+        ctx = CStr::from_ptr(context).to_str().unwrap();
+        path = if store_path.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(store_path).to_str().unwrap())
+        };
+    }
+
+    let store: Box<dyn CredentialStore> = match path {
+        Some(p) => match FileCredentialStore::open(p) {
+            Ok(s) => Box::new(s),
+            Err(err) => {
+                println!("RUST - LOG: Server creation error: {}", err);
+                return std::ptr::null_mut();
+            }
+        },
+        None => Box::new(MemoryCredentialStore::new()),
+    };
+
+    let result = if serv_setup.size_setup == 0 {
+        OpaqueServer::load_or_generate(server.to_string(), ctx.to_string(), store)
+    } else {
+        let setup;
+        unsafe {
+            setup = std::slice::from_raw_parts(serv_setup.setup, serv_setup.size_setup);
+        }
+        OpaqueServer::new(setup, server.to_string(), ctx.to_string(), store)
+    };
+    match result {
+        Ok(s) => Box::into_raw(Box::new(s)),
+        Err(err) => {
+            println!("RUST - LOG: Server creation error: {}", err);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// release an OpaqueServer created with opaque_server_new
+#[no_mangle]
+pub extern "C" fn opaque_server_free(server: *mut OpaqueServer) {
+    if server.is_null() {
+        return;
+    }
+    unsafe {
+        let _ = Box::from_raw(server);
+    }
+}
+
+/// first server step of registration with OpaqueServer:
+/// fails if username already has a password file
+/// registration_request: result of client registration start
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_registration_request(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    registration_request: ClientRegStartResult,
+) -> Opaque {
+    let opaque_server;
+    let username_client;
+    let request;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        request =
+            std::slice::from_raw_parts(registration_request.data, registration_request.size_data);
+    }
+
+    match opaque_server.handle_registration_request(username_client, request) {
+        Ok(response) => opaque_from_vec(response),
+        Err(err) => {
+            println!("RUST - LOG: Server registration request error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// last server step of registration with OpaqueServer:
+/// the password file is saved in the server store, failing if username
+/// already has one: only a password change can replace it
+/// upload: result of client registration finish
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_registration_upload(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    upload: Opaque,
+) -> bool {
+    let opaque_server;
+    let username_client;
+    let message;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        message = std::slice::from_raw_parts(upload.data, upload.size);
+    }
+
+    match opaque_server.handle_registration_upload(username_client, message) {
+        Ok(_) => true,
+        Err(err) => {
+            println!("RUST - LOG: Server registration upload error: {}", err);
+            false
+        }
+    }
+}
+
+/// first server step of login with OpaqueServer:
+/// the login state is kept by the server under session_id
+/// credential_request: result of client login start
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_credential_request(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    session_id: *const c_char,
+    credential_request: ClientLogStartResult,
+) -> Opaque {
+    let opaque_server;
+    let username_client;
+    let session;
+    let credential;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        session = CStr::from_ptr(session_id).to_str().unwrap();
+        credential =
+            std::slice::from_raw_parts(credential_request.data, credential_request.size_data);
+    }
+
+    match opaque_server.handle_credential_request(username_client, session, credential) {
+        Ok(response) => opaque_from_vec(response),
+        Err(err) => {
+            println!("RUST - LOG: Server credential request error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// last server step of login with OpaqueServer
+/// credential_finalization: result of client login finish
+/// returns the session key, empty if login failed
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_credential_finalization(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    session_id: *const c_char,
+    credential_finalization: Opaque,
+) -> Opaque {
+    let opaque_server;
+    let username_client;
+    let session;
+    let credential;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        session = CStr::from_ptr(session_id).to_str().unwrap();
+        credential =
+            std::slice::from_raw_parts(credential_finalization.data, credential_finalization.size);
+    }
+
+    match opaque_server.handle_credential_finalization(username_client, session, credential) {
+        Ok(session_key) => opaque_from_vec(session_key),
+        Err(err) => {
+            println!("RUST - LOG: Server detected login failure: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// create an OpaqueClient, which keeps password and state between
+/// begin and complete steps: MUST be released with opaque_client_free
+#[no_mangle]
+pub extern "C" fn opaque_client_new(
+    username: *const c_char,
+    servername: *const c_char,
+    context: *const c_char,
+) -> *mut OpaqueClient {
+    let user;
+    let server;
+    let ctx;
+    unsafe {
+        user = CStr::from_ptr(username).to_str().unwrap();
+        server = CStr::from_ptr(servername).to_str().unwrap();
+        ctx = CStr::from_ptr(context).to_str().unwrap();
+    }
+    Box::into_raw(Box::new(OpaqueClient::new(
+        user.to_string(),
+        server.to_string(),
+        ctx.to_string(),
+    )))
+}
+
+/// release an OpaqueClient created with opaque_client_new,
+/// wiping password and in-flight state
+#[no_mangle]
+pub extern "C" fn opaque_client_free(client: *mut OpaqueClient) {
+    if client.is_null() {
+        return;
+    }
+    unsafe {
+        let _ = Box::from_raw(client);
+    }
+}
+
+/// first client step of registration with OpaqueClient
+/// returns the registration request for the server
+#[no_mangle]
+pub extern "C" fn opaque_client_begin_registration(
+    client: *mut OpaqueClient,
+    password: *const c_char,
+) -> Opaque {
+    let opaque_client;
+    let password_client;
+    unsafe {
+        opaque_client = &mut *client;
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+    }
+
+    match opaque_client.begin_registration(password_client) {
+        Ok(request) => opaque_from_vec(request),
+        Err(err) => {
+            println!("RUST - LOG: Client registration start error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// last client step of registration with OpaqueClient
+/// registration_response: server answer to the registration request
+/// every field is empty on error
+#[no_mangle]
+pub extern "C" fn opaque_client_complete_registration(
+    client: *mut OpaqueClient,
+    registration_response: ServerRegStartResult,
+) -> OpaqueRegistrationResult {
+    let opaque_client;
+    let response;
+    unsafe {
+        opaque_client = &mut *client;
+        response = std::slice::from_raw_parts(
+            registration_response.data,
+            registration_response.size_data,
+        );
+    }
+
+    let (upload, export_key, server_public_key) =
+        match opaque_client.complete_registration(response) {
+            Ok(res) => (res.upload, res.export_key, res.server_public_key),
+            Err(err) => {
+                println!("RUST - LOG: Client registration finish error: {}", err);
+                (vec![], vec![], vec![])
+            }
+        };
+    let upload = opaque_from_vec(upload);
+    let export_key = opaque_from_vec(export_key);
+    let server_public_key = opaque_from_vec(server_public_key);
+    OpaqueRegistrationResult {
+        upload: upload.data,
+        size_upload: upload.size,
+        export_key: export_key.data,
+        size_export_key: export_key.size,
+        server_public_key: server_public_key.data,
+        size_server_public_key: server_public_key.size,
+    }
+}
+
+/// first client step of login with OpaqueClient
+/// returns the credential request for the server
+#[no_mangle]
+pub extern "C" fn opaque_client_begin_login(
+    client: *mut OpaqueClient,
+    password: *const c_char,
+) -> Opaque {
+    let opaque_client;
+    let password_client;
+    unsafe {
+        opaque_client = &mut *client;
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+    }
+
+    match opaque_client.begin_login(password_client) {
+        Ok(request) => opaque_from_vec(request),
+        Err(err) => {
+            println!("RUST - LOG: Client login start error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// last client step of login with OpaqueClient
+/// credential_response: server answer to the credential request
+/// every field is empty if login failed
+#[no_mangle]
+pub extern "C" fn opaque_client_complete_login(
+    client: *mut OpaqueClient,
+    credential_response: ServerLogStartResult,
+) -> OpaqueLoginResult {
+    let opaque_client;
+    let response;
+    unsafe {
+        opaque_client = &mut *client;
+        response =
+            std::slice::from_raw_parts(credential_response.data, credential_response.size_data);
+    }
+
+    let (credential_finalization, session_key, export_key, server_public_key) =
+        match opaque_client.complete_login(response) {
+            Ok(res) => (
+                res.credential_finalization,
+                res.session_key,
+                res.export_key,
+                res.server_public_key,
+            ),
+            Err(err) => {
+                println!("RUST - LOG: Client detected login failure: {}", err);
+                (vec![], vec![], vec![], vec![])
+            }
+        };
+    let credential_finalization = opaque_from_vec(credential_finalization);
+    let session_key = opaque_from_vec(session_key);
+    let export_key = opaque_from_vec(export_key);
+    let server_public_key = opaque_from_vec(server_public_key);
+    OpaqueLoginResult {
+        credential_finalization: credential_finalization.data,
+        size_credential_finalization: credential_finalization.size,
+        session_key: session_key.data,
+        size_session_key: session_key.size,
+        export_key: export_key.data,
+        size_export_key: export_key.size,
+        server_public_key: server_public_key.data,
+        size_server_public_key: server_public_key.size,
+    }
+}
+
+/// put a protocol message inside a self-describing envelope
+/// (magic, version, message type, ciphersuite id, length)
+/// message_type: one of the OPAQUE_MSG_* values
+/// message: result of any registration or login step
+#[no_mangle]
+pub extern "C" fn opaque_envelope_encode(message_type: u8, message: Opaque) -> Opaque {
+    let msg;
+    unsafe {
+        msg = slice_from_opaque(&message);
+    }
+
+    match MessageType::from_byte(message_type) {
+        Some(t) => opaque_from_vec(encode_envelope(t, msg)),
+        None => {
+            println!(
+                "RUST - LOG: Envelope encode error: {}",
+                OpaqueError::UnknownMessageType(message_type)
+            );
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// give back the protocol message inside an envelope,
+/// empty if it isn't the expected message type or ciphersuite
+/// expected_type: one of the OPAQUE_MSG_* values
+#[no_mangle]
+pub extern "C" fn opaque_envelope_decode(expected_type: u8, envelope: Opaque) -> Opaque {
+    let env;
+    unsafe {
+        env = slice_from_opaque(&envelope);
+    }
+
+    let result = match MessageType::from_byte(expected_type) {
+        Some(t) => decode_envelope(t, env),
+        None => Err(OpaqueError::UnknownMessageType(expected_type)),
+    };
+    match result {
+        Ok(msg) => opaque_from_vec(msg.to_owned()),
+        Err(err) => {
+            println!("RUST - LOG: Envelope decode error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// message type (one of the OPAQUE_MSG_* values) held by an envelope,
+/// or -1 if data isn't a valid envelope
+#[no_mangle]
+pub extern "C" fn opaque_envelope_message_type(envelope: Opaque) -> i32 {
+    let env;
+    unsafe {
+        env = slice_from_opaque(&envelope);
+    }
+
+    match envelope_header(env) {
+        Ok(header) => header.message_type as i32,
+        Err(err) => {
+            println!("RUST - LOG: Envelope error: {}", err);
+            -1
+        }
+    }
+}
+
+/// first client step of a password change with OpaqueClient: login with
+/// current_password and registration of new_password at the same time
+/// every field is empty on error
+#[no_mangle]
+pub extern "C" fn opaque_client_begin_password_change(
+    client: *mut OpaqueClient,
+    current_password: *const c_char,
+    new_password: *const c_char,
+) -> OpaquePasswordChangeRequest {
+    let opaque_client;
+    let current;
+    let new;
+    unsafe {
+        opaque_client = &mut *client;
+        current = CStr::from_ptr(current_password).to_str().unwrap();
+        new = CStr::from_ptr(new_password).to_str().unwrap();
+    }
+
+    let (credential_request, registration_request) =
+        match opaque_client.begin_password_change(current, new) {
+            Ok(req) => (req.credential_request, req.registration_request),
+            Err(err) => {
+                println!("RUST - LOG: Client password change start error: {}", err);
+                (vec![], vec![])
+            }
+        };
+    let credential_request = opaque_from_vec(credential_request);
+    let registration_request = opaque_from_vec(registration_request);
+    OpaquePasswordChangeRequest {
+        credential_request: credential_request.data,
+        size_credential_request: credential_request.size,
+        registration_request: registration_request.data,
+        size_registration_request: registration_request.size,
+    }
+}
+
+/// server step of a password change with OpaqueServer, keeping
+/// the login state under session_id until the finalization
+/// credential_request, registration_request: result of client password change start
+/// every field is empty on error, wrapped_secret also while the recovery
+/// secret of the user is still the one derived from the password
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_password_change_request(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    session_id: *const c_char,
+    credential_request: Opaque,
+    registration_request: Opaque,
+) -> OpaquePasswordChangeResponse {
+    let opaque_server;
+    let username_client;
+    let session;
+    let request;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        session = CStr::from_ptr(session_id).to_str().unwrap();
+        request = PasswordChangeRequest {
+            credential_request: std::slice::from_raw_parts(
+                credential_request.data,
+                credential_request.size,
+            )
+            .to_owned(),
+            registration_request: std::slice::from_raw_parts(
+                registration_request.data,
+                registration_request.size,
+            )
+            .to_owned(),
+        };
+    }
+
+    let (credential_response, registration_response, wrapped_secret) =
+        match opaque_server.handle_password_change_request(username_client, session, &request) {
+            Ok(res) => (
+                res.credential_response,
+                res.registration_response,
+                res.wrapped_secret,
+            ),
+            Err(err) => {
+                println!("RUST - LOG: Server password change start error: {}", err);
+                (vec![], vec![], vec![])
+            }
+        };
+    let credential_response = opaque_from_vec(credential_response);
+    let registration_response = opaque_from_vec(registration_response);
+    let wrapped_secret = opaque_from_vec(wrapped_secret);
+    OpaquePasswordChangeResponse {
+        credential_response: credential_response.data,
+        size_credential_response: credential_response.size,
+        registration_response: registration_response.data,
+        size_registration_response: registration_response.size,
+        wrapped_secret: wrapped_secret.data,
+        size_wrapped_secret: wrapped_secret.size,
+    }
+}
+
+/// last client step of a password change with OpaqueClient
+/// credential_response, registration_response, wrapped_secret:
+/// server answer to the request
+/// recovery_secret: the secret of the user, the same one as before the change
+/// every field is empty if the current password is wrong or wrapped_secret
+/// doesn't open under it
+#[no_mangle]
+pub extern "C" fn opaque_client_complete_password_change(
+    client: *mut OpaqueClient,
+    credential_response: Opaque,
+    registration_response: Opaque,
+    wrapped_secret: Opaque,
+) -> OpaquePasswordChangeResult {
+    let opaque_client;
+    let response;
+    unsafe {
+        opaque_client = &mut *client;
+        response = PasswordChangeResponse {
+            credential_response: std::slice::from_raw_parts(
+                credential_response.data,
+                credential_response.size,
+            )
+            .to_owned(),
+            registration_response: std::slice::from_raw_parts(
+                registration_response.data,
+                registration_response.size,
+            )
+            .to_owned(),
+            wrapped_secret: slice_from_opaque(&wrapped_secret).to_owned(),
+        };
+    }
+
+    let (credential_finalization, upload, wrapped, tag, session_key, export_key, secret) =
+        match opaque_client.complete_password_change(&response) {
+            Ok(res) => (
+                res.finalization.credential_finalization,
+                res.finalization.upload,
+                res.finalization.wrapped_secret,
+                res.finalization.tag,
+                res.session_key,
+                res.export_key,
+                res.recovery_secret,
+            ),
+            Err(err) => {
+                println!("RUST - LOG: Client password change failure: {}", err);
+                (vec![], vec![], vec![], vec![], vec![], vec![], vec![])
+            }
+        };
+    let credential_finalization = opaque_from_vec(credential_finalization);
+    let upload = opaque_from_vec(upload);
+    let wrapped = opaque_from_vec(wrapped);
+    let tag = opaque_from_vec(tag);
+    let session_key = opaque_from_vec(session_key);
+    let export_key = opaque_from_vec(export_key);
+    let secret = opaque_from_vec(secret);
+    OpaquePasswordChangeResult {
+        credential_finalization: credential_finalization.data,
+        size_credential_finalization: credential_finalization.size,
+        upload: upload.data,
+        size_upload: upload.size,
+        wrapped_secret: wrapped.data,
+        size_wrapped_secret: wrapped.size,
+        tag: tag.data,
+        size_tag: tag.size,
+        session_key: session_key.data,
+        size_session_key: session_key.size,
+        export_key: export_key.data,
+        size_export_key: export_key.size,
+        recovery_secret: secret.data,
+        size_recovery_secret: secret.size,
+    }
+}
+
+/// last server step of a password change with OpaqueServer: the password
+/// file of username is replaced only if the login succeeds and upload
+/// is bound to its session key
+/// credential_finalization, upload, wrapped_secret, tag:
+/// result of client password change finish
+/// returns the session key, empty if the password wasn't changed
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_password_change_finalization(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    session_id: *const c_char,
+    credential_finalization: Opaque,
+    upload: Opaque,
+    wrapped_secret: Opaque,
+    tag: Opaque,
+) -> Opaque {
+    let opaque_server;
+    let username_client;
+    let session;
+    let finalization;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        session = CStr::from_ptr(session_id).to_str().unwrap();
+        finalization = PasswordChangeFinalization {
+            credential_finalization: std::slice::from_raw_parts(
+                credential_finalization.data,
+                credential_finalization.size,
+            )
+            .to_owned(),
+            upload: std::slice::from_raw_parts(upload.data, upload.size).to_owned(),
+            wrapped_secret: std::slice::from_raw_parts(wrapped_secret.data, wrapped_secret.size)
+                .to_owned(),
+            tag: std::slice::from_raw_parts(tag.data, tag.size).to_owned(),
+        };
+    }
+
+    match opaque_server.handle_password_change_finalization(username_client, session, &finalization)
+    {
+        Ok(session_key) => opaque_from_vec(session_key),
+        Err(err) => {
+            println!("RUST - LOG: Server refused password change: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// same as opaque_server_handle_registration_upload, keeping next to
+/// the password file the records of the recovery codes of the user
+/// records: result of opaque_recovery_codes_generate
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_registration_upload_with_recovery(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    upload: Opaque,
+    records: Opaque,
+) -> bool {
+    let opaque_server;
+    let username_client;
+    let message;
+    let recs;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        message = std::slice::from_raw_parts(upload.data, upload.size);
+        recs = std::slice::from_raw_parts(records.data, records.size);
+    }
+
+    match opaque_server.handle_registration_upload_with_recovery(username_client, message, recs) {
+        Ok(_) => true,
+        Err(err) => {
+            println!("RUST - LOG: Server registration upload error: {}", err);
+            false
+        }
+    }
+}
+
+/// recovery secret of username wrapped under the export key of the current
+/// password, for opaque_recovery_secret_from_export_key: send it to the
+/// client only after a successful login
+/// returns an empty value if the secret is still derived from the export key
+#[no_mangle]
+pub extern "C" fn opaque_server_recovery_wrapped_secret(
+    server: *const OpaqueServer,
+    username: *const c_char,
+) -> Opaque {
+    let opaque_server;
+    let username_client;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+    }
+
+    match opaque_server.recovery_wrapped_secret(username_client) {
+        Ok(wrapped) => opaque_from_vec(wrapped.unwrap_or_default()),
+        Err(err) => {
+            println!("RUST - LOG: Server recovery data error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// first client step of a recovery with OpaqueClient: code is one of the
+/// recovery codes of the user, new_password the one to register in place
+/// of the forgotten one
+/// every field is empty on error
+#[no_mangle]
+pub extern "C" fn opaque_client_begin_recovery(
+    client: *mut OpaqueClient,
+    code: *const c_char,
+    new_password: *const c_char,
+) -> OpaqueRecoveryRequest {
+    let opaque_client;
+    let recovery_code;
+    let new;
+    unsafe {
+        opaque_client = &mut *client;
+        recovery_code = CStr::from_ptr(code).to_str().unwrap();
+        new = CStr::from_ptr(new_password).to_str().unwrap();
+    }
+
+    let (verifier, registration_request) = match opaque_client.begin_recovery(recovery_code, new)
+    {
+        Ok(req) => (req.verifier, req.registration_request),
+        Err(err) => {
+            println!("RUST - LOG: Client recovery start error: {}", err);
+            (vec![], vec![])
+        }
+    };
+    let verifier = opaque_from_vec(verifier);
+    let registration_request = opaque_from_vec(registration_request);
+    OpaqueRecoveryRequest {
+        verifier: verifier.data,
+        size_verifier: verifier.size,
+        registration_request: registration_request.data,
+        size_registration_request: registration_request.size,
+    }
+}
+
+/// server step of a recovery with OpaqueServer: the code is not used yet
+/// verifier, registration_request: result of client recovery start
+/// every field is empty if username has no such recovery code
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_recovery_request(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    verifier: Opaque,
+    registration_request: Opaque,
+) -> OpaqueRecoveryResponse {
+    let opaque_server;
+    let username_client;
+    let request;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        request = RecoveryRequest {
+            verifier: std::slice::from_raw_parts(verifier.data, verifier.size).to_owned(),
+            registration_request: std::slice::from_raw_parts(
+                registration_request.data,
+                registration_request.size,
+            )
+            .to_owned(),
+        };
+    }
+
+    let (wrapped_secret, registration_response) =
+        match opaque_server.handle_recovery_request(username_client, &request) {
+            Ok(res) => (res.wrapped_secret, res.registration_response),
+            Err(err) => {
+                println!("RUST - LOG: Recovery refused: {}", err);
+                (vec![], vec![])
+            }
+        };
+    let wrapped_secret = opaque_from_vec(wrapped_secret);
+    let registration_response = opaque_from_vec(registration_response);
+    OpaqueRecoveryResponse {
+        wrapped_secret: wrapped_secret.data,
+        size_wrapped_secret: wrapped_secret.size,
+        registration_response: registration_response.data,
+        size_registration_response: registration_response.size,
+    }
+}
+
+/// last client step of a recovery with OpaqueClient
+/// wrapped_secret, registration_response: server answer to the request
+/// secret: the secret of the user, the same one as before the recovery
+/// every field is empty if the code doesn't open the secret
+#[no_mangle]
+pub extern "C" fn opaque_client_complete_recovery(
+    client: *mut OpaqueClient,
+    wrapped_secret: Opaque,
+    registration_response: Opaque,
+) -> OpaqueRecoveryResult {
+    let opaque_client;
+    let response;
+    unsafe {
+        opaque_client = &mut *client;
+        response = RecoveryResponse {
+            wrapped_secret: std::slice::from_raw_parts(wrapped_secret.data, wrapped_secret.size)
+                .to_owned(),
+            registration_response: std::slice::from_raw_parts(
+                registration_response.data,
+                registration_response.size,
+            )
+            .to_owned(),
+        };
+    }
+
+    let (verifier, upload, wrapped, export_key, secret) =
+        match opaque_client.complete_recovery(&response) {
+            Ok(res) => (
+                res.finalization.verifier,
+                res.finalization.upload,
+                res.finalization.wrapped_secret,
+                res.export_key,
+                res.secret,
+            ),
+            Err(err) => {
+                println!("RUST - LOG: Client recovery failure: {}", err);
+                (vec![], vec![], vec![], vec![], vec![])
+            }
+        };
+    let verifier = opaque_from_vec(verifier);
+    let upload = opaque_from_vec(upload);
+    let wrapped = opaque_from_vec(wrapped);
+    let export_key = opaque_from_vec(export_key);
+    let secret = opaque_from_vec(secret);
+    OpaqueRecoveryResult {
+        verifier: verifier.data,
+        size_verifier: verifier.size,
+        upload: upload.data,
+        size_upload: upload.size,
+        wrapped_secret: wrapped.data,
+        size_wrapped_secret: wrapped.size,
+        export_key: export_key.data,
+        size_export_key: export_key.size,
+        secret: secret.data,
+        size_secret: secret.size,
+    }
+}
+
+/// last server step of a recovery with OpaqueServer: the recovery code is
+/// removed and the password file of username replaced in a single write
+/// verifier, upload, wrapped_secret: result of client recovery finish
+/// returns false if the code is invalid or was already used
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_recovery_finalization(
+    server: *const OpaqueServer,
+    username: *const c_char,
+    verifier: Opaque,
+    upload: Opaque,
+    wrapped_secret: Opaque,
+) -> bool {
+    let opaque_server;
+    let username_client;
+    let finalization;
+    unsafe {
+        opaque_server = &*server;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        finalization = RecoveryFinalization {
+            verifier: std::slice::from_raw_parts(verifier.data, verifier.size).to_owned(),
+            upload: std::slice::from_raw_parts(upload.data, upload.size).to_owned(),
+            wrapped_secret: std::slice::from_raw_parts(wrapped_secret.data, wrapped_secret.size)
+                .to_owned(),
+        };
+    }
+
+    match opaque_server.handle_recovery_finalization(username_client, &finalization) {
+        Ok(_) => true,
+        Err(err) => {
+            println!("RUST - LOG: Recovery refused: {}", err);
+            false
+        }
+    }
+}
+
+/// secret to encrypt client data with, instead of the export key:
+/// it survives password changes and recoveries
+/// export_key: export key of the first registration
+#[no_mangle]
+pub extern "C" fn opaque_recovery_secret(export_key: Opaque) -> Opaque {
+    let key;
+    unsafe {
+        key = std::slice::from_raw_parts(export_key.data, export_key.size);
+    }
+    opaque_from_vec(recovery_secret(key))
+}
+
+/// new recovery codes for username, each one wrapping secret
+/// secret: result of opaque_recovery_secret or opaque_recovery_unwrap_secret
+/// codes: one code per line, to show to the user once
+/// records: to be kept by the server next to the password file
+#[no_mangle]
+pub extern "C" fn opaque_recovery_codes_generate(
+    secret: Opaque,
+    username: *const c_char,
+    count: u32,
+) -> OpaqueRecoveryCodes {
+    let sec;
+    let user;
+    unsafe {
+        sec = std::slice::from_raw_parts(secret.data, secret.size);
+        user = CStr::from_ptr(username).to_str().unwrap();
+    }
+
+    let generated = generate_recovery_codes(sec, user, count as usize);
+    let codes = opaque_from_vec(generated.codes.join("\n").into_bytes());
+    let records = opaque_from_vec(generated.records);
+    OpaqueRecoveryCodes {
+        codes: codes.data,
+        size_codes: codes.size,
+        records: records.data,
+        size_records: records.size,
+    }
+}
+
+/// value sent to the server to prove knowledge of a recovery code
+/// code: as typed by the user, case and dashes don't matter
+#[no_mangle]
+pub extern "C" fn opaque_recovery_verifier(code: *const c_char, username: *const c_char) -> Opaque {
+    let recovery_code;
+    let user;
+    unsafe {
+        recovery_code = CStr::from_ptr(code).to_str().unwrap();
+        user = CStr::from_ptr(username).to_str().unwrap();
+    }
+    opaque_from_vec(recovery_verifier(recovery_code, user))
+}
+
+/// server step of a recovery, before registering the new password, for
+/// servers keeping the records themselves (see opaque_server_handle_recovery_request)
+/// records: result of opaque_recovery_codes_generate
+/// verifier: result of opaque_recovery_verifier
+/// returns the wrapped secret for the client, empty if the code is invalid
+#[no_mangle]
+pub extern "C" fn opaque_recovery_find(records: Opaque, verifier: Opaque) -> Opaque {
+    let recs;
+    let ver;
+    unsafe {
+        recs = std::slice::from_raw_parts(records.data, records.size);
+        ver = std::slice::from_raw_parts(verifier.data, verifier.size);
+    }
+
+    match find_recovery_record(recs, ver) {
+        Ok(wrapped) => opaque_from_vec(wrapped),
+        Err(err) => {
+            println!("RUST - LOG: Recovery refused: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// server step of a recovery, when the new password is registered, for servers
+/// keeping the records themselves (see opaque_server_handle_recovery_finalization):
+/// the code of verifier is removed, since every code is single use
+/// returns the records to store in place of the old ones,
+/// empty if the code is invalid (the new password MUST then be refused)
+#[no_mangle]
+pub extern "C" fn opaque_recovery_take(records: Opaque, verifier: Opaque) -> Opaque {
+    let recs;
+    let ver;
+    unsafe {
+        recs = std::slice::from_raw_parts(records.data, records.size);
+        ver = std::slice::from_raw_parts(verifier.data, verifier.size);
+    }
+
+    match take_recovery_record(recs, ver) {
+        Ok((_, remaining)) => opaque_from_vec(remaining),
+        Err(err) => {
+            println!("RUST - LOG: Recovery refused: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// client step of a recovery: the secret inside the wrapped
+/// secret returned by opaque_recovery_find, empty on error
+#[no_mangle]
+pub extern "C" fn opaque_recovery_unwrap_secret(
+    code: *const c_char,
+    username: *const c_char,
+    wrapped: Opaque,
+) -> Opaque {
+    let recovery_code;
+    let user;
+    let wrap;
+    unsafe {
+        recovery_code = CStr::from_ptr(code).to_str().unwrap();
+        user = CStr::from_ptr(username).to_str().unwrap();
+        wrap = std::slice::from_raw_parts(wrapped.data, wrapped.size);
+    }
+
+    match unwrap_recovery_secret(recovery_code, user, wrap) {
+        Ok(secret) => opaque_from_vec(secret),
+        Err(err) => {
+            println!("RUST - LOG: Recovery secret error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// secret wrapped under the export key of a new password,
+/// to be kept by the server next to the password file
+#[no_mangle]
+pub extern "C" fn opaque_recovery_wrap_secret(export_key: Opaque, secret: Opaque) -> Opaque {
+    let key;
+    let sec;
+    unsafe {
+        key = std::slice::from_raw_parts(export_key.data, export_key.size);
+        sec = std::slice::from_raw_parts(secret.data, secret.size);
+    }
+    opaque_from_vec(wrap_secret_with_export_key(key, sec))
+}
+
+/// secret after a login: unwrapped with the export key if the password
+/// was changed or recovered, derived from it if wrapped is empty
+/// wrapped: result of opaque_server_recovery_wrapped_secret
+/// returns an empty value on error
+#[no_mangle]
+pub extern "C" fn opaque_recovery_secret_from_export_key(
+    export_key: Opaque,
+    wrapped: Opaque,
+) -> Opaque {
+    let key;
+    let wrap;
+    unsafe {
+        key = std::slice::from_raw_parts(export_key.data, export_key.size);
+        wrap = if wrapped.size == 0 {
+            None
+        } else {
+            Some(std::slice::from_raw_parts(wrapped.data, wrapped.size))
+        };
+    }
+
+    match secret_from_export_key(key, wrap) {
+        Ok(secret) => opaque_from_vec(secret),
+        Err(err) => {
+            println!("RUST - LOG: Recovery secret error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// create a SecureChannel protecting messages with keys derived from the
+/// session key of a login: MUST be released with opaque_channel_free
+/// session_key: result of the last login step on this side
+/// is_server: which side of the login this channel belongs to
+#[no_mangle]
+pub extern "C" fn opaque_channel_new(session_key: Opaque, is_server: bool) -> *mut SecureChannel {
+    let key;
+    unsafe {
+        key = std::slice::from_raw_parts(session_key.data, session_key.size);
+    }
+    if key.is_empty() {
+        println!("RUST - LOG: Channel creation error: empty session key");
+        return std::ptr::null_mut();
+    }
+
+    let role = if is_server { Role::Server } else { Role::Client };
+    Box::into_raw(Box::new(SecureChannel::new(key, role)))
+}
+
+/// release a SecureChannel created by opaque_channel_new
+#[no_mangle]
+pub extern "C" fn opaque_channel_free(channel: *mut SecureChannel) {
+    if channel.is_null() {
+        return;
+    }
+    unsafe {
+        let _ = Box::from_raw(channel);
+    }
+}
+
+/// protect plaintext for the other side of the channel
+/// aad: authenticated but not sent, the other side must pass the same value
+/// aad and plaintext can be empty, with data NULL
+/// returns the record to send, empty on error
+#[no_mangle]
+pub extern "C" fn opaque_channel_seal(
+    channel: *mut SecureChannel,
+    aad: Opaque,
+    plaintext: Opaque,
+) -> Opaque {
+    let secure_channel;
+    let ad;
+    let text;
+    unsafe {
+        secure_channel = &mut *channel;
+        ad = slice_from_opaque(&aad);
+        text = slice_from_opaque(&plaintext);
+    }
+
+    match secure_channel.seal(ad, text) {
+        Ok(record) => opaque_from_vec(record),
+        Err(err) => {
+            println!("RUST - LOG: Channel seal error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// plaintext of a record sealed by the other side of the channel
+/// aad: the value passed to opaque_channel_seal, can be empty with data NULL
+/// ok is false, with an empty plaintext, if the record is forged,
+/// replayed or too old
+#[no_mangle]
+pub extern "C" fn opaque_channel_open(
+    channel: *mut SecureChannel,
+    aad: Opaque,
+    record: Opaque,
+) -> OpaqueChannelOpenResult {
+    let secure_channel;
+    let ad;
+    let rec;
+    unsafe {
+        secure_channel = &mut *channel;
+        ad = slice_from_opaque(&aad);
+        rec = slice_from_opaque(&record);
+    }
+
+    let (ok, plaintext) = match secure_channel.open(ad, rec) {
+        Ok(plaintext) => (true, plaintext),
+        Err(err) => {
+            println!("RUST - LOG: Channel open error: {}", err);
+            (false, vec![])
+        }
+    };
+    let plaintext = opaque_from_vec(plaintext);
+    OpaqueChannelOpenResult {
+        ok,
+        plaintext: plaintext.data,
+        size_plaintext: plaintext.size,
+    }
+}
+
+/// login context binding the login to the channel binding bytes
+/// of an outer channel, to pass as context on both sides
+/// label: type of binding, e.g. "tls-exporter" (RFC 9266)
+/// binding: the binding bytes, e.g. the TLS exporter value
+#[no_mangle]
+pub extern "C" fn opaque_channel_binding_context(label: *const c_char, binding: Opaque) -> Opaque {
+    let binding_label;
+    let binding_bytes;
+    unsafe {
+        binding_label = CStr::from_ptr(label).to_str().unwrap();
+        binding_bytes = std::slice::from_raw_parts(binding.data, binding.size);
+    }
+    opaque_from_vec(channel_binding_context(binding_label, binding_bytes))
+}
+
+/// create a ProtocolConfig, holding the identities and the context to pass
+/// to every *_with_config step: client and server must use equal ones,
+/// MUST be released with opaque_protocol_config_free
+/// context: login context, e.g. result of opaque_channel_binding_context
+#[no_mangle]
+pub extern "C" fn opaque_protocol_config_new(
+    servername: *const c_char,
+    context: Opaque,
+) -> *mut ProtocolConfig {
+    let server;
+    let ctx;
+    unsafe {
+        server = CStr::from_ptr(servername).to_str().unwrap();
+        ctx = std::slice::from_raw_parts(context.data, context.size);
+    }
+    Box::into_raw(Box::new(ProtocolConfig::new(server, ctx)))
+}
+
+/// release a ProtocolConfig created with opaque_protocol_config_new
+#[no_mangle]
+pub extern "C" fn opaque_protocol_config_free(config: *mut ProtocolConfig) {
+    if config.is_null() {
+        return;
+    }
+    unsafe {
+        let _ = Box::from_raw(config);
+    }
+}
+
+/// same as opaque_client_registration_finish, with the identities of config
+/// empty on error
+#[no_mangle]
+pub extern "C" fn opaque_client_registration_finish_with_config(
+    config: *const ProtocolConfig,
+    password: *const c_char,
+    server_registration_start: ServerRegStartResult,
+    client_reg_start_state: ClientRegState,
+    username: *const c_char,
+) -> Opaque {
+    let conf;
+    let password_client;
+    let reg_response;
+    let client_state;
+    let user;
+    unsafe {
+        conf = &*config;
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+        reg_response = std::slice::from_raw_parts(
+            server_registration_start.data,
+            server_registration_start.size_data,
+        );
+        client_state = std::slice::from_raw_parts(
+            client_reg_start_state.state,
+            client_reg_start_state.size_state,
+        );
+        user = CStr::from_ptr(username).to_str().unwrap();
+    }
+
+    match registration_finish(password_client, reg_response, client_state, user, conf) {
+        Ok(result) => opaque_from_vec(result.upload),
+        Err(err) => {
+            println!("RUST - LOG: Client registration finish error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// same as opaque_server_registration_finish, but the password file records
+/// the identities of config: logins started with other identities
+/// are refused by every server login start
+/// empty on error, or if the client registered with other identities
+#[no_mangle]
+pub extern "C" fn opaque_server_registration_finish_with_config(
+    config: *const ProtocolConfig,
+    username: *const c_char,
+    message: Opaque,
+) -> Opaque {
+    let conf;
+    let user;
+    let message_client;
+    unsafe {
+        conf = &*config;
+        user = CStr::from_ptr(username).to_str().unwrap();
+        message_client = std::slice::from_raw_parts(message.data, message.size);
+    }
+
+    match bound_password_file_from_upload(conf, user, message_client) {
+        Ok(password_file) => opaque_from_vec(password_file),
+        Err(err) => {
+            println!("RUST - LOG: Server registration finish error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// same as opaque_server_login_start, with the identities and context of config:
+/// every field is empty on error, or if password_file was registered
+/// with other identities
+#[no_mangle]
+pub extern "C" fn opaque_server_login_start_with_config(
+    config: *const ProtocolConfig,
+    username: *const c_char,
+    password_file: Opaque,
+    credential_request: ClientLogStartResult,
+    serv_setup: ServerSetup,
+) -> OpaqueWithState {
+    let conf;
+    let username_client;
+    let password_client;
+    let credential;
+    let server_setup;
+    unsafe {
+        conf = &*config;
+        username_client = CStr::from_ptr(username).to_str().unwrap();
+        password_client = std::slice::from_raw_parts(password_file.data, password_file.size);
+        credential =
+            std::slice::from_raw_parts(credential_request.data, credential_request.size_data);
+        server_setup = std::slice::from_raw_parts(serv_setup.setup, serv_setup.size_setup);
+    }
+
+    let result = deserialize_setup(server_setup).and_then(|setup| {
+        credential_response(
+            &setup,
+            conf,
+            username_client,
+            Some(password_client),
+            credential,
+        )
+    });
+    match result {
+        Ok((response, state)) => opaque_with_state_from_vecs(response, state),
+        Err(err) => {
+            println!("RUST - LOG: Server login start error: {}", err);
+            opaque_with_state_from_vecs(vec![], vec![])
+        }
+    }
+}
+
+/// same as opaque_client_login_finish, with the identities and context of config
+#[no_mangle]
+pub extern "C" fn opaque_client_login_finish_with_config(
+    config: *const ProtocolConfig,
+    password: *const c_char,
+    login_response: ServerLogStartResult,
+    client_login_state: ClientLogState,
+    username: *const c_char,
+) -> Opaque {
+    let conf;
+    let password_client;
+    let log_response;
+    let client_state;
+    let user;
+    unsafe {
+        conf = &*config;
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+        log_response = std::slice::from_raw_parts(login_response.data, login_response.size_data);
+        client_state =
+            std::slice::from_raw_parts(client_login_state.state, client_login_state.size_state);
+        user = CStr::from_ptr(username).to_str().unwrap();
+    }
+
+    match login_finish(password_client, log_response, client_state, user, conf) {
+        Ok(result) => opaque_from_vec(result.credential_finalization),
+        Err(err) => {
+            println!("RUST - LOG: Client detected login failure: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// same as opaque_server_login_start_with_config, for a credential registered
+/// under credential_id (passed as username to the registration steps) and
+/// logging in as identity, e.g. an email address: the client must use the
+/// same identity, which can change without registering again
+#[no_mangle]
+pub extern "C" fn opaque_server_login_start_with_identity(
+    config: *const ProtocolConfig,
+    credential_id: *const c_char,
+    identity: *const c_char,
+    password_file: Opaque,
+    credential_request: ClientLogStartResult,
+    serv_setup: ServerSetup,
+) -> OpaqueWithState {
+    let identity_config;
+    unsafe {
+        identity_config = (*config).for_identity(CStr::from_ptr(identity).to_str().unwrap());
+    }
+    opaque_server_login_start_with_config(
+        &identity_config,
+        credential_id,
+        password_file,
+        credential_request,
+        serv_setup,
+    )
+}
+
+/// same as opaque_client_login_finish_with_config, for a credential
+/// registered under credential_id and logging in as identity
+#[no_mangle]
+pub extern "C" fn opaque_client_login_finish_with_identity(
+    config: *const ProtocolConfig,
+    password: *const c_char,
+    login_response: ServerLogStartResult,
+    client_login_state: ClientLogState,
+    credential_id: *const c_char,
+    identity: *const c_char,
+) -> Opaque {
+    let identity_config;
+    unsafe {
+        identity_config = (*config).for_identity(CStr::from_ptr(identity).to_str().unwrap());
+    }
+    opaque_client_login_finish_with_config(
+        &identity_config,
+        password,
+        login_response,
+        client_login_state,
+        credential_id,
+    )
+}
+
+/// same as opaque_server_handle_credential_request, for a credential
+/// registered under credential_id and logging in as identity
+#[no_mangle]
+pub extern "C" fn opaque_server_handle_credential_request_with_identity(
+    server: *const OpaqueServer,
+    credential_id: *const c_char,
+    identity: *const c_char,
+    session_id: *const c_char,
+    credential_request: ClientLogStartResult,
+) -> Opaque {
+    let opaque_server;
+    let id;
+    let identity_client;
+    let session;
+    let credential;
+    unsafe {
+        opaque_server = &*server;
+        id = CStr::from_ptr(credential_id).to_str().unwrap();
+        identity_client = CStr::from_ptr(identity).to_str().unwrap();
+        session = CStr::from_ptr(session_id).to_str().unwrap();
+        credential =
+            std::slice::from_raw_parts(credential_request.data, credential_request.size_data);
+    }
+
+    match opaque_server.handle_credential_request_with_identity(
+        id,
+        identity_client,
+        session,
+        credential,
+    ) {
+        Ok(response) => opaque_from_vec(response),
+        Err(err) => {
+            println!("RUST - LOG: Server credential request error: {}", err);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// log the OpaqueClient in as identity from the next login on: its username
+/// is then the credential id, and the server must use the same identity
+#[no_mangle]
+pub extern "C" fn opaque_client_set_identity(client: *mut OpaqueClient, identity: *const c_char) {
+    unsafe {
+        (*client).set_identity(CStr::from_ptr(identity).to_str().unwrap());
+    }
+}
+
+/// set the normalization applied to passwords by every client function:
+/// one of the OPAQUE_PASSWORD_NORMALIZATION_* values, none by default;
+/// it MUST be the same at registration and login
+/// returns false, changing nothing, if normalization is unknown
+#[no_mangle]
+pub extern "C" fn opaque_set_password_normalization(normalization: u8) -> bool {
+    match PasswordNormalization::from_byte(normalization) {
+        Some(val) => {
+            set_password_normalization(val);
+            true
+        }
+        None => {
+            println!("RUST - LOG: Unknown password normalization {}", normalization);
+            false
+        }
+    }
+}
+
+/// password as normalized by the client functions with normalization
+/// (see opaque_set_password_normalization), UTF-8 without terminator:
+/// empty if normalization is unknown
+#[no_mangle]
+pub extern "C" fn opaque_normalize_password(password: *const c_char, normalization: u8) -> Opaque {
+    let password_client;
+    unsafe {
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+    }
+    match PasswordNormalization::from_byte(normalization) {
+        Some(val) => opaque_from_vec(normalize_password_with(password_client, val).into_bytes()),
+        None => {
+            println!("RUST - LOG: Unknown password normalization {}", normalization);
+            opaque_from_vec(vec![])
+        }
+    }
+}
+
+/// check of password for username (may be empty) with the default password
+/// policy: at least 8 characters of 2 kinds, not common, not containing
+/// the username; run it before opaque_client_registration_start, the server
+/// can't tell a weak password
+#[no_mangle]
+pub extern "C" fn opaque_password_check(
+    password: *const c_char,
+    username: *const c_char,
+) -> OpaquePasswordCheck {
+    let password_client;
+    let user;
+    unsafe {
+        password_client = CStr::from_ptr(password).to_str().unwrap();
+        user = CStr::from_ptr(username).to_str().unwrap();
+    }
+
+    let issues = password_issues(&DefaultPasswordPolicy::default(), password_client, user)
+        .iter()
+        .map(|issue| match issue {
+            PasswordIssue::TooShort { .. } => OPAQUE_PASSWORD_TOO_SHORT,
+            PasswordIssue::TooFewCharacterClasses { .. } => {
+                OPAQUE_PASSWORD_TOO_FEW_CHARACTER_CLASSES
+            }
+            PasswordIssue::Common => OPAQUE_PASSWORD_COMMON,
+            PasswordIssue::ContainsUsername => OPAQUE_PASSWORD_CONTAINS_USERNAME,
+            PasswordIssue::Repetitive => OPAQUE_PASSWORD_REPETITIVE,
+        })
+        .fold(0, |acc, flag| acc | flag);
+    let strength = estimate_strength(password_client);
+    OpaquePasswordCheck {
+        issues,
+        score: strength.score as u8,
+        entropy_bits: strength.entropy_bits,
+    }
+}
+
+/// make the OpaqueClient refuse registrations and password changes to
+/// passwords with issues under the default password policy
+/// (see opaque_password_check): the begin step then returns empty
+#[no_mangle]
+pub extern "C" fn opaque_client_set_default_password_policy(client: *mut OpaqueClient) {
+    unsafe {
+        (*client).set_password_policy(Box::new(DefaultPasswordPolicy::default()));
+    }
+}
+
+/// enabled: make opaque_client_registration_start, every registration finish
+/// and the OpaqueClients without a policy of their own refuse passwords with
+/// issues under the default password policy (see opaque_password_check),
+/// returning empty; false, the default, accepts every password
+#[no_mangle]
+pub extern "C" fn opaque_set_default_password_policy(enabled: bool) {
+    set_default_password_policy(enabled);
+}
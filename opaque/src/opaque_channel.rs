@@ -0,0 +1,346 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use crate::opaque_error::OpaqueError;
+
+// Messages exchanged after a login, protected with keys derived from its
+// session key: every direction has its own key and sequence number, and the
+// sequence number is both the nonce and what the receiver checks for replays.
+//
+// record: version (1) + sequence number (8, big endian) + ciphertext
+
+pub const CHANNEL_VERSION: u8 = 1;
+
+const KEY_LEN: usize = 32;
+const HEADER_LEN: usize = 9;
+const TAG_LEN: usize = 16;
+// records older than the newest one received by this many are refused
+const REPLAY_WINDOW: u64 = 64;
+
+const CLIENT_TO_SERVER_INFO: &[u8] = b"OPAQUE traffic key client to server";
+const SERVER_TO_CLIENT_INFO: &[u8] = b"OPAQUE traffic key server to client";
+
+/// side of the login the channel belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Client,
+    Server,
+}
+
+/// keys of the two directions, as seen by one side
+pub struct TrafficKeys {
+    pub send: [u8; KEY_LEN],
+    pub receive: [u8; KEY_LEN],
+}
+
+impl Drop for TrafficKeys {
+    fn drop(&mut self) {
+        self.send.zeroize();
+        self.receive.zeroize();
+    }
+}
+
+fn expand(session_key: &[u8], info: &[u8]) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    Hkdf::<Sha256>::new(None, session_key)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// directional keys derived from the session key of a login:
+/// what one side sends with, the other receives with
+pub fn derive_traffic_keys(session_key: &[u8], role: Role) -> TrafficKeys {
+    let client_to_server = expand(session_key, CLIENT_TO_SERVER_INFO);
+    let server_to_client = expand(session_key, SERVER_TO_CLIENT_INFO);
+    match role {
+        Role::Client => TrafficKeys {
+            send: client_to_server,
+            receive: server_to_client,
+        },
+        Role::Server => TrafficKeys {
+            send: server_to_client,
+            receive: client_to_server,
+        },
+    }
+}
+
+fn nonce(seq: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&seq.to_be_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+fn header(seq: u64) -> [u8; HEADER_LEN] {
+    let mut header = [0u8; HEADER_LEN];
+    header[0] = CHANNEL_VERSION;
+    header[1..].copy_from_slice(&seq.to_be_bytes());
+    header
+}
+
+// header and caller data are both authenticated
+fn associated_data(header: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(header.len() + aad.len());
+    data.extend_from_slice(header);
+    data.extend_from_slice(aad);
+    data
+}
+
+/// one side of a protected channel: seal what goes to the other side,
+/// open what comes from it, refusing replayed and too old records
+pub struct SecureChannel {
+    send: ChaCha20Poly1305,
+    receive: ChaCha20Poly1305,
+    next_seq: u64,
+    // highest sequence number received, and which of the
+    // REPLAY_WINDOW numbers below it were received too
+    highest_seq: Option<u64>,
+    window: u64,
+}
+
+impl SecureChannel {
+    pub fn new(session_key: &[u8], role: Role) -> SecureChannel {
+        let keys = derive_traffic_keys(session_key, role);
+        SecureChannel {
+            send: ChaCha20Poly1305::new(Key::from_slice(&keys.send)),
+            receive: ChaCha20Poly1305::new(Key::from_slice(&keys.receive)),
+            next_seq: 0,
+            highest_seq: None,
+            window: 0,
+        }
+    }
+
+    /// protect plaintext for the other side: aad is authenticated
+    /// but not sent, the other side must pass the same value to open
+    pub fn seal(&mut self, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+        // a sequence number is never used twice with the same key
+        if self.next_seq == u64::MAX {
+            return Err(OpaqueError::SequenceExhausted);
+        }
+        let seq = self.next_seq;
+        let header = header(seq);
+        let ciphertext = self
+            .send
+            .encrypt(
+                &nonce(seq),
+                Payload {
+                    msg: plaintext,
+                    aad: &associated_data(&header, aad),
+                },
+            )
+            .unwrap();
+        self.next_seq += 1;
+
+        let mut record = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        record.extend_from_slice(&header);
+        record.extend_from_slice(&ciphertext);
+        Ok(record)
+    }
+
+    /// plaintext of a record sealed by the other side
+    pub fn open(&mut self, aad: &[u8], record: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+        if record.len() < HEADER_LEN + TAG_LEN {
+            return Err(OpaqueError::MalformedRecord);
+        }
+        if record[0] != CHANNEL_VERSION {
+            return Err(OpaqueError::UnsupportedVersion(record[0]));
+        }
+        let mut seq_bytes = [0u8; 8];
+        seq_bytes.copy_from_slice(&record[1..HEADER_LEN]);
+        let seq = u64::from_be_bytes(seq_bytes);
+        self.check_replay(seq)?;
+
+        let plaintext = self
+            .receive
+            .decrypt(
+                &nonce(seq),
+                Payload {
+                    msg: &record[HEADER_LEN..],
+                    aad: &associated_data(&record[..HEADER_LEN], aad),
+                },
+            )
+            .map_err(|_| OpaqueError::DecryptionFailed)?;
+
+        // only authenticated records move the window
+        self.mark_received(seq);
+        Ok(plaintext)
+    }
+
+    fn check_replay(&self, seq: u64) -> Result<(), OpaqueError> {
+        let highest = match self.highest_seq {
+            Some(val) => val,
+            None => return Ok(()),
+        };
+        if seq > highest {
+            return Ok(());
+        }
+        let age = highest - seq;
+        if age >= REPLAY_WINDOW || self.window & (1 << age) != 0 {
+            return Err(OpaqueError::MessageReplayed);
+        }
+        Ok(())
+    }
+
+    fn mark_received(&mut self, seq: u64) {
+        match self.highest_seq {
+            Some(highest) if seq <= highest => {
+                self.window |= 1 << (highest - seq);
+            }
+            Some(highest) => {
+                let shift = seq - highest;
+                self.window = if shift >= REPLAY_WINDOW {
+                    0
+                } else {
+                    self.window << shift
+                };
+                self.window |= 1;
+                self.highest_seq = Some(seq);
+            }
+            None => {
+                self.window = 1;
+                self.highest_seq = Some(seq);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION_KEY: &[u8] = &[7; 64];
+
+    fn channels() -> (SecureChannel, SecureChannel) {
+        (
+            SecureChannel::new(SESSION_KEY, Role::Client),
+            SecureChannel::new(SESSION_KEY, Role::Server),
+        )
+    }
+
+    #[test]
+    fn records_go_both_ways() {
+        let (mut client, mut server) = channels();
+
+        let record = client.seal(b"aad", b"to server").unwrap();
+        assert_eq!(server.open(b"aad", &record), Ok(b"to server".to_vec()));
+        let record = server.seal(b"aad", b"to client").unwrap();
+        assert_eq!(client.open(b"aad", &record), Ok(b"to client".to_vec()));
+    }
+
+    #[test]
+    fn directions_have_their_own_key() {
+        let (mut client, mut server) = channels();
+
+        // a record can't be reflected back to the side that sealed it
+        let record = client.seal(b"aad", b"to server").unwrap();
+        assert_eq!(
+            client.open(b"aad", &record),
+            Err(OpaqueError::DecryptionFailed)
+        );
+        let record = server.seal(b"aad", b"to client").unwrap();
+        assert_eq!(
+            server.open(b"aad", &record),
+            Err(OpaqueError::DecryptionFailed)
+        );
+
+        // nor opened with the keys of another login
+        let mut other = SecureChannel::new(&[8; 64], Role::Server);
+        let record = client.seal(b"aad", b"to server").unwrap();
+        assert_eq!(
+            other.open(b"aad", &record),
+            Err(OpaqueError::DecryptionFailed)
+        );
+    }
+
+    #[test]
+    fn aad_must_match() {
+        let (mut client, mut server) = channels();
+
+        let record = client.seal(b"aad", b"to server").unwrap();
+        assert_eq!(
+            server.open(b"other aad", &record),
+            Err(OpaqueError::DecryptionFailed)
+        );
+        // a refused record doesn't count as received
+        assert_eq!(server.open(b"aad", &record), Ok(b"to server".to_vec()));
+    }
+
+    #[test]
+    fn replayed_record_is_refused() {
+        let (mut client, mut server) = channels();
+
+        let record = client.seal(b"aad", b"to server").unwrap();
+        assert!(server.open(b"aad", &record).is_ok());
+        assert_eq!(
+            server.open(b"aad", &record),
+            Err(OpaqueError::MessageReplayed)
+        );
+    }
+
+    #[test]
+    fn records_inside_the_window_are_accepted_out_of_order() {
+        let (mut client, mut server) = channels();
+
+        let records: Vec<Vec<u8>> = (0..3).map(|i| client.seal(b"aad", &[i]).unwrap()).collect();
+        assert_eq!(server.open(b"aad", &records[2]), Ok(vec![2]));
+        assert_eq!(server.open(b"aad", &records[0]), Ok(vec![0]));
+        assert_eq!(server.open(b"aad", &records[1]), Ok(vec![1]));
+
+        for record in &records {
+            assert_eq!(
+                server.open(b"aad", record),
+                Err(OpaqueError::MessageReplayed)
+            );
+        }
+    }
+
+    #[test]
+    fn records_older_than_the_window_are_refused() {
+        let (mut client, mut server) = channels();
+
+        let records: Vec<Vec<u8>> = (0..=REPLAY_WINDOW)
+            .map(|_| client.seal(b"aad", b"to server").unwrap())
+            .collect();
+        assert!(server
+            .open(b"aad", &records[REPLAY_WINDOW as usize])
+            .is_ok());
+
+        // age REPLAY_WINDOW is out, age REPLAY_WINDOW - 1 still in
+        assert_eq!(
+            server.open(b"aad", &records[0]),
+            Err(OpaqueError::MessageReplayed)
+        );
+        assert!(server.open(b"aad", &records[1]).is_ok());
+    }
+
+    #[test]
+    fn malformed_record_is_refused() {
+        let (mut client, mut server) = channels();
+
+        let record = client.seal(b"aad", b"").unwrap();
+        assert_eq!(
+            server.open(b"aad", &record[..record.len() - 1]),
+            Err(OpaqueError::MalformedRecord)
+        );
+
+        let mut other_version = record.clone();
+        other_version[0] = CHANNEL_VERSION + 1;
+        assert_eq!(
+            server.open(b"aad", &other_version),
+            Err(OpaqueError::UnsupportedVersion(CHANNEL_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn sequence_numbers_are_not_reused() {
+        let (mut client, _) = channels();
+        client.next_seq = u64::MAX;
+        assert_eq!(
+            client.seal(b"aad", b"to server"),
+            Err(OpaqueError::SequenceExhausted)
+        );
+    }
+}
@@ -0,0 +1,39 @@
+// Login context made from channel binding bytes, so a login completes only
+// when client and server see the same outer channel (e.g. the same TLS
+// session): a man in the middle terminating TLS gets different bytes on
+// each side and the login fails on both.
+//
+// context: prefix + label length (1) + label + binding length (4, big endian) + binding
+
+const CHANNEL_BINDING_PREFIX: &[u8] = b"OPAQUE-CB";
+
+/// RFC 9266 TLS exporter value, the binding to use with TLS 1.3
+pub const TLS_EXPORTER: &str = "tls-exporter";
+/// RFC 5929 hash of the server certificate
+pub const TLS_SERVER_END_POINT: &str = "tls-server-end-point";
+/// RFC 5929 first Finished message, TLS 1.2 only
+pub const TLS_UNIQUE: &str = "tls-unique";
+
+/// label and length of the TLS exporter value to export for
+/// TLS_EXPORTER bindings, as defined by RFC 9266
+pub const TLS_EXPORTER_LABEL: &str = "EXPORTER-Channel-Binding";
+pub const TLS_EXPORTER_LEN: usize = 32;
+
+/// login context binding the login to binding, the channel binding bytes
+/// of type label (TLS_EXPORTER, or any other name agreed by both sides):
+/// pass it as context on both sides, the lengths keep label and binding
+/// from being shifted into each other
+pub fn channel_binding_context(label: &str, binding: &[u8]) -> Vec<u8> {
+    // labels are short names, longer ones are cut
+    let label = &label.as_bytes()[..label.len().min(u8::MAX as usize)];
+
+    let mut context = Vec::with_capacity(
+        CHANNEL_BINDING_PREFIX.len() + 1 + label.len() + 4 + binding.len(),
+    );
+    context.extend_from_slice(CHANNEL_BINDING_PREFIX);
+    context.push(label.len() as u8);
+    context.extend_from_slice(label);
+    context.extend_from_slice(&(binding.len() as u32).to_be_bytes());
+    context.extend_from_slice(binding);
+    context
+}
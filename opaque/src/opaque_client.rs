@@ -4,11 +4,15 @@ use opaque_ke::{
     ClientLogin, ClientLoginFinishParameters, ClientRegistration,
     ClientRegistrationFinishParameters, CredentialResponse, RegistrationResponse, Identifiers,
 };
+#[cfg(feature = "ristretto255")]
+use argon2::Argon2;
+
+use crate::opaque_password::normalize_password;
 
 // The ciphersuite trait allows to specify the underlying primitives that will
 // be used in the OPAQUE protocol
 #[allow(dead_code)]
-struct DefaultCipherSuite;
+pub(crate) struct DefaultCipherSuite;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for DefaultCipherSuite {
@@ -42,6 +46,7 @@ pub struct ClientResponse {
 
 // init changes
 pub fn client_registration_start(password: String) -> ClientResponseWithState {
+    let password = normalize_password(&password);
     let reg_start_result =
         ClientRegistration::<DefaultCipherSuite>::start(&mut OsRng, password.as_bytes()).unwrap();
 
@@ -62,6 +67,7 @@ pub fn client_registration_finish(
     username: String,
     servername: String,
 ) -> Vec<u8> {
+    let password = normalize_password(&password);
     // retrieve client registration state to allow finish procedure correctly
     let state =
         ClientRegistration::<DefaultCipherSuite>::deserialize(reg_start_result)
@@ -87,6 +93,7 @@ pub fn client_registration_finish(
 }
 
 pub fn client_login_start(password: String) -> ClientResponseWithState {
+    let password = normalize_password(&password);
     let login_start_result =
         ClientLogin::<DefaultCipherSuite>::start(&mut OsRng, password.as_bytes()).unwrap();
 
@@ -108,6 +115,7 @@ pub fn client_login_finish(
     servername: String,
     context: String,
 ) -> ClientResponse {
+    let password = normalize_password(&password);
     // retrieve client login state to allow finish procedure correctly
     let state = ClientLogin::<DefaultCipherSuite>::deserialize(login_start_result).unwrap();
 
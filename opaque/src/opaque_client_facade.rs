@@ -0,0 +1,548 @@
+use std::sync::Arc;
+
+use opaque_ke::rand::rngs::OsRng;
+use opaque_ke::{
+    ClientLogin, ClientLoginFinishParameters, ClientRegistration,
+    ClientRegistrationFinishParameters, CredentialResponse, RegistrationResponse,
+};
+use zeroize::Zeroize;
+
+use crate::opaque_client::DefaultCipherSuite;
+use crate::opaque_config::ProtocolConfig;
+use crate::opaque_error::OpaqueError;
+use crate::opaque_password::normalize_password;
+use crate::opaque_password_policy::{check_password_policy, password_policy, PasswordPolicy};
+use crate::opaque_password_change::{
+    password_change_tag, PasswordChangeFinalization, PasswordChangeRequest,
+    PasswordChangeResponse, PasswordChangeResult,
+};
+use crate::opaque_recovery::{
+    recovery_verifier, secret_from_export_key, unwrap_recovery_secret, wrap_secret_with_export_key,
+    RecoveryFinalization, RecoveryRequest, RecoveryResponse, RecoveryResult,
+};
+
+fn protocol_error(err: opaque_ke::errors::ProtocolError) -> OpaqueError {
+    OpaqueError::Protocol(err.to_string())
+}
+
+/// result of complete_registration
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RegistrationResult {
+    /// message to send to the server (registration upload)
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub upload: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub export_key: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub server_public_key: Vec<u8>,
+}
+
+/// result of complete_login
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoginResult {
+    /// message to send to the server (credential finalization)
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub credential_finalization: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub session_key: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub export_key: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub server_public_key: Vec<u8>,
+}
+
+/// login state of the client between client_login_start and
+/// client_login_finish, for callers keeping it themselves,
+/// e.g. in a web session, instead of in an OpaqueClient
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientLoginState {
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub state: Vec<u8>,
+}
+
+/// result of client_login_start
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClientLoginStart {
+    /// message to send to the server (credential request)
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub credential_request: Vec<u8>,
+    pub state: ClientLoginState,
+}
+
+/// client side of the protocol in a single object: it keeps password
+/// and state between begin and complete of registration and login,
+/// wiping them when the step completes or the client is dropped
+pub struct OpaqueClient {
+    username: String,
+    config: ProtocolConfig,
+    // identity shown to the user, when username is a credential id
+    identity: Option<String>,
+    // run before registrations and password changes,
+    // in place of the one of set_password_policy
+    policy: Option<Arc<dyn PasswordPolicy>>,
+    password: Vec<u8>,
+    // password being registered by a password change
+    new_password: Vec<u8>,
+    // code used by a recovery
+    recovery_code: String,
+    registration: Option<ClientRegistration<DefaultCipherSuite>>,
+    login: Option<ClientLogin<DefaultCipherSuite>>,
+}
+
+impl OpaqueClient {
+    pub fn new(username: String, servername: String, context: String) -> OpaqueClient {
+        OpaqueClient::with_config(username, ProtocolConfig::new(&servername, context.as_bytes()))
+    }
+
+    /// same as new, with identities and context given as a configuration:
+    /// the server must use an equal one
+    pub fn with_config(username: String, config: ProtocolConfig) -> OpaqueClient {
+        OpaqueClient {
+            username,
+            config,
+            identity: None,
+            policy: None,
+            password: vec![],
+            new_password: vec![],
+            recovery_code: String::new(),
+            registration: None,
+            login: None,
+        }
+    }
+
+    /// use context in place of the one given to new, from the next login on:
+    /// binary, so it can be a channel_binding_context
+    pub fn set_context(&mut self, context: &[u8]) {
+        self.config.context = context.to_owned();
+    }
+
+    /// same as new, for a credential registered under credential_id and
+    /// logging in as identity (e.g. an email address): the server must use
+    /// the same identity, which can change without registering again
+    pub fn with_identity(
+        credential_id: String,
+        identity: String,
+        config: ProtocolConfig,
+    ) -> OpaqueClient {
+        let mut client = OpaqueClient::with_config(credential_id, config);
+        client.identity = Some(identity);
+        client
+    }
+
+    /// log in as identity from the next login on, e.g. after the user changed it
+    pub fn set_identity(&mut self, identity: &str) {
+        self.identity = Some(identity.to_string());
+    }
+
+    /// refuse, from now on, registrations and password changes to a password
+    /// with issues under policy, e.g. a DefaultPasswordPolicy: the server
+    /// can't tell a weak password, only the client can refuse it; without
+    /// one the client runs the policy of set_password_policy, if any
+    pub fn set_password_policy(&mut self, policy: Box<dyn PasswordPolicy>) {
+        self.policy = Some(Arc::from(policy));
+    }
+
+    // password, normalized, accepted by the policy if any
+    fn check_password(&self, password: &str) -> Result<(), OpaqueError> {
+        let policy = match self.policy.clone().or_else(password_policy) {
+            Some(val) => val,
+            None => return Ok(()),
+        };
+        let username = self.identity.as_deref().unwrap_or(&self.username);
+        let issues = policy.check(password, username);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(OpaqueError::WeakPassword(issues))
+        }
+    }
+
+    /// identities and context of the client
+    pub fn config(&self) -> &ProtocolConfig {
+        &self.config
+    }
+
+    // configuration of a login: the identity, if any, goes into the context
+    fn login_config(&self) -> ProtocolConfig {
+        match &self.identity {
+            Some(identity) => self.config.for_identity(identity),
+            None => self.config.clone(),
+        }
+    }
+
+    // forget password and every in-flight state
+    fn reset(&mut self) {
+        self.password.zeroize();
+        self.password.clear();
+        self.new_password.zeroize();
+        self.new_password.clear();
+        self.recovery_code.zeroize();
+        self.recovery_code.clear();
+        self.registration = None;
+        self.login = None;
+    }
+
+    /// start a registration, returning the registration request for the server:
+    /// fails with WeakPassword if the password policy refuses password
+    pub fn begin_registration(&mut self, password: &str) -> Result<Vec<u8>, OpaqueError> {
+        self.reset();
+        let password = normalize_password(password);
+        self.check_password(&password)?;
+        let reg_start = ClientRegistration::<DefaultCipherSuite>::start(&mut OsRng, password.as_bytes())
+            .map_err(protocol_error)?;
+
+        self.password = password.as_bytes().to_owned();
+        self.registration = Some(reg_start.state);
+        Ok(reg_start.message.serialize().as_slice().to_owned())
+    }
+
+    /// complete the registration with the server registration response
+    pub fn complete_registration(
+        &mut self,
+        registration_response: &[u8],
+    ) -> Result<RegistrationResult, OpaqueError> {
+        let state = match self.registration.take() {
+            Some(val) => val,
+            None => return Err(OpaqueError::NotStarted),
+        };
+        let result = finish_registration(
+            state,
+            &self.password,
+            registration_response,
+            &self.username,
+            &self.config,
+        );
+        self.reset();
+        result
+    }
+
+    /// start a login, returning the credential request for the server
+    pub fn begin_login(&mut self, password: &str) -> Result<Vec<u8>, OpaqueError> {
+        self.reset();
+        let password = normalize_password(password);
+        let login_start = ClientLogin::<DefaultCipherSuite>::start(&mut OsRng, password.as_bytes())
+            .map_err(protocol_error)?;
+
+        self.password = password.as_bytes().to_owned();
+        self.login = Some(login_start.state);
+        Ok(login_start.message.serialize().as_slice().to_owned())
+    }
+
+    /// complete the login with the server credential response:
+    /// fails if the password is wrong or the server isn't the expected one
+    pub fn complete_login(&mut self, credential_response: &[u8]) -> Result<LoginResult, OpaqueError> {
+        let state = match self.login.take() {
+            Some(val) => val,
+            None => return Err(OpaqueError::NotStarted),
+        };
+        let result = finish_login(
+            state,
+            &self.password,
+            credential_response,
+            &self.username,
+            &self.login_config(),
+        );
+        self.reset();
+        result
+    }
+
+    /// start a password change, logging in with the current password
+    /// and registering the new one at the same time
+    pub fn begin_password_change(
+        &mut self,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<PasswordChangeRequest, OpaqueError> {
+        self.reset();
+        let current_password = normalize_password(current_password);
+        let new_password = normalize_password(new_password);
+        self.check_password(&new_password)?;
+        let login_start =
+            ClientLogin::<DefaultCipherSuite>::start(&mut OsRng, current_password.as_bytes())
+                .map_err(protocol_error)?;
+        let reg_start =
+            ClientRegistration::<DefaultCipherSuite>::start(&mut OsRng, new_password.as_bytes())
+                .map_err(protocol_error)?;
+
+        self.password = current_password.as_bytes().to_owned();
+        self.new_password = new_password.as_bytes().to_owned();
+        self.login = Some(login_start.state);
+        self.registration = Some(reg_start.state);
+        Ok(PasswordChangeRequest {
+            credential_request: login_start.message.serialize().as_slice().to_owned(),
+            registration_request: reg_start.message.serialize().as_slice().to_owned(),
+        })
+    }
+
+    /// complete the password change with the server answer: fails,
+    /// without anything for the server, if the current password is wrong
+    /// or, with DecryptionFailed, if the wrapped secret of the answer
+    /// doesn't open under it
+    pub fn complete_password_change(
+        &mut self,
+        response: &PasswordChangeResponse,
+    ) -> Result<PasswordChangeResult, OpaqueError> {
+        let (login_state, reg_state) = match (self.login.take(), self.registration.take()) {
+            (Some(login), Some(reg)) => (login, reg),
+            _ => {
+                self.reset();
+                return Err(OpaqueError::NotStarted);
+            }
+        };
+
+        let result = finish_login(
+            login_state,
+            &self.password,
+            &response.credential_response,
+            &self.username,
+            &self.login_config(),
+        )
+        .and_then(|login| {
+            let registration = finish_registration(
+                reg_state,
+                &self.new_password,
+                &response.registration_response,
+                &self.username,
+                &self.config,
+            )?;
+            // empty while the secret is still derived from the password:
+            // one that doesn't open under a verified login is an error,
+            // deriving a secret in its place would lose the user's one
+            let wrapped = if response.wrapped_secret.is_empty() {
+                None
+            } else {
+                Some(response.wrapped_secret.as_slice())
+            };
+            let secret = secret_from_export_key(&login.export_key, wrapped)?;
+            let wrapped_secret = wrap_secret_with_export_key(&registration.export_key, &secret);
+            let tag = password_change_tag(
+                &login.session_key,
+                &self.username,
+                &registration.upload,
+                &wrapped_secret,
+            );
+            Ok(PasswordChangeResult {
+                finalization: PasswordChangeFinalization {
+                    credential_finalization: login.credential_finalization,
+                    upload: registration.upload,
+                    wrapped_secret,
+                    tag,
+                },
+                session_key: login.session_key,
+                export_key: registration.export_key,
+                recovery_secret: secret,
+            })
+        });
+        self.reset();
+        result
+    }
+
+    /// start a recovery with one of the recovery codes of the user,
+    /// registering new_password in place of the forgotten one:
+    /// fails with WeakPassword if the password policy refuses new_password
+    pub fn begin_recovery(
+        &mut self,
+        code: &str,
+        new_password: &str,
+    ) -> Result<RecoveryRequest, OpaqueError> {
+        self.reset();
+        let new_password = normalize_password(new_password);
+        self.check_password(&new_password)?;
+        let reg_start =
+            ClientRegistration::<DefaultCipherSuite>::start(&mut OsRng, new_password.as_bytes())
+                .map_err(protocol_error)?;
+
+        self.new_password = new_password.as_bytes().to_owned();
+        self.recovery_code = code.to_string();
+        self.registration = Some(reg_start.state);
+        Ok(RecoveryRequest {
+            verifier: recovery_verifier(code, &self.username),
+            registration_request: reg_start.message.serialize().as_slice().to_owned(),
+        })
+    }
+
+    /// complete the recovery with the server answer: fails, without
+    /// anything for the server, if the code doesn't open the secret
+    pub fn complete_recovery(
+        &mut self,
+        response: &RecoveryResponse,
+    ) -> Result<RecoveryResult, OpaqueError> {
+        let state = match self.registration.take() {
+            Some(val) if !self.recovery_code.is_empty() => val,
+            _ => {
+                self.reset();
+                return Err(OpaqueError::NotStarted);
+            }
+        };
+
+        let result = unwrap_recovery_secret(
+            &self.recovery_code,
+            &self.username,
+            &response.wrapped_secret,
+        )
+        .and_then(|secret| {
+            let registration = finish_registration(
+                state,
+                &self.new_password,
+                &response.registration_response,
+                &self.username,
+                &self.config,
+            )?;
+            Ok(RecoveryResult {
+                finalization: RecoveryFinalization {
+                    verifier: recovery_verifier(&self.recovery_code, &self.username),
+                    upload: registration.upload,
+                    wrapped_secret: wrap_secret_with_export_key(&registration.export_key, &secret),
+                },
+                export_key: registration.export_key,
+                secret,
+            })
+        });
+        self.reset();
+        result
+    }
+}
+
+impl Drop for OpaqueClient {
+    fn drop(&mut self) {
+        self.reset();
+    }
+}
+
+fn finish_registration(
+    state: ClientRegistration<DefaultCipherSuite>,
+    password: &[u8],
+    registration_response: &[u8],
+    username: &str,
+    config: &ProtocolConfig,
+) -> Result<RegistrationResult, OpaqueError> {
+    let response = RegistrationResponse::deserialize(registration_response).map_err(protocol_error)?;
+    let reg_finish = state
+        .finish(
+            &mut OsRng,
+            password,
+            response,
+            ClientRegistrationFinishParameters::new(config.identifiers(username), None),
+        )
+        .map_err(protocol_error)?;
+
+    Ok(RegistrationResult {
+        // the server checks the identities before building the password file
+        upload: config.bind_upload(username, &reg_finish.message.serialize()),
+        export_key: reg_finish.export_key.as_slice().to_owned(),
+        server_public_key: reg_finish.server_s_pk.serialize().as_slice().to_owned(),
+    })
+}
+
+fn finish_login(
+    state: ClientLogin<DefaultCipherSuite>,
+    password: &[u8],
+    credential_response: &[u8],
+    username: &str,
+    config: &ProtocolConfig,
+) -> Result<LoginResult, OpaqueError> {
+    let response = CredentialResponse::deserialize(credential_response).map_err(protocol_error)?;
+    let login_finish = state
+        .finish(
+            password,
+            response,
+            ClientLoginFinishParameters::new(
+                Some(&config.context),
+                config.identifiers(username),
+                None,
+            ),
+        )
+        .map_err(protocol_error)?;
+
+    Ok(LoginResult {
+        credential_finalization: login_finish.message.serialize().as_slice().to_owned(),
+        session_key: login_finish.session_key.as_slice().to_owned(),
+        export_key: login_finish.export_key.as_slice().to_owned(),
+        server_public_key: login_finish.server_s_pk.serialize().as_slice().to_owned(),
+    })
+}
+
+/// start a login without an OpaqueClient: the caller keeps the state
+/// and gives it back, with the same password, to client_login_finish
+pub fn client_login_start(password: &str) -> Result<ClientLoginStart, OpaqueError> {
+    let (credential_request, state) = login_start(password)?;
+    Ok(ClientLoginStart {
+        credential_request,
+        state: ClientLoginState { state },
+    })
+}
+
+/// complete a login started by client_login_start with the server
+/// credential response: config must be the one of the server
+pub fn client_login_finish(
+    password: &str,
+    credential_response: &[u8],
+    state: &ClientLoginState,
+    username: &str,
+    config: &ProtocolConfig,
+) -> Result<LoginResult, OpaqueError> {
+    login_finish(password, credential_response, &state.state, username, config)
+}
+
+// same steps without the OpaqueClient object, for bindings where the
+// caller keeps the serialized state between start and finish; registration
+// start and finish fail with WeakPassword if the policy of set_password_policy
+// refuses the normalized password, finish also checks it against the username
+
+pub(crate) fn registration_start(password: &str) -> Result<(Vec<u8>, Vec<u8>), OpaqueError> {
+    let password = normalize_password(password);
+    check_password_policy(&password, "")?;
+    let reg_start = ClientRegistration::<DefaultCipherSuite>::start(&mut OsRng, password.as_bytes())
+        .map_err(protocol_error)?;
+    Ok((
+        reg_start.message.serialize().as_slice().to_owned(),
+        reg_start.state.serialize().as_slice().to_owned(),
+    ))
+}
+
+pub(crate) fn registration_finish(
+    password: &str,
+    registration_response: &[u8],
+    state: &[u8],
+    username: &str,
+    config: &ProtocolConfig,
+) -> Result<RegistrationResult, OpaqueError> {
+    let password = normalize_password(password);
+    check_password_policy(&password, username)?;
+    let state = ClientRegistration::<DefaultCipherSuite>::deserialize(state).map_err(protocol_error)?;
+    finish_registration(
+        state,
+        password.as_bytes(),
+        registration_response,
+        username,
+        config,
+    )
+}
+
+pub(crate) fn login_start(password: &str) -> Result<(Vec<u8>, Vec<u8>), OpaqueError> {
+    let password = normalize_password(password);
+    let login_start = ClientLogin::<DefaultCipherSuite>::start(&mut OsRng, password.as_bytes())
+        .map_err(protocol_error)?;
+    Ok((
+        login_start.message.serialize().as_slice().to_owned(),
+        login_start.state.serialize().as_slice().to_owned(),
+    ))
+}
+
+pub(crate) fn login_finish(
+    password: &str,
+    credential_response: &[u8],
+    state: &[u8],
+    username: &str,
+    config: &ProtocolConfig,
+) -> Result<LoginResult, OpaqueError> {
+    let state = ClientLogin::<DefaultCipherSuite>::deserialize(state).map_err(protocol_error)?;
+    finish_login(
+        state,
+        normalize_password(password).as_bytes(),
+        credential_response,
+        username,
+        config,
+    )
+}
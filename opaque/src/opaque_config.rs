@@ -0,0 +1,201 @@
+use opaque_ke::Identifiers;
+use sha2::{Digest, Sha256};
+
+use crate::opaque_error::OpaqueError;
+
+// Identities and context of the protocol in one place: the client binds its
+// envelope to the identities at registration finish and both sides have to
+// use the very same ones at login, or the login fails without telling why.
+//
+// The server doesn't see the identities at registration, so the client sends
+// the digest of the ones it bound the envelope to along with the upload, and
+// the server refuses the upload if they differ from its own; then it records
+// them in the password file it builds from the upload, and refuses to start
+// a login with other ones:
+// bound upload: magic (4) + version (1) + identity digest (32) + upload
+// bound password file: magic (4) + version (1) + identity digest (32) + password file
+//
+// Uploads and password files built without a configuration are accepted as they are.
+//
+// The client identity bound to the envelope is the credential identifier:
+// when users are shown a changeable identity (e.g. an email address), register
+// them under a stable credential id and add the identity to the login context
+// with for_identity, so both sides still agree on it at every login while the
+// password file keeps working after the identity changes.
+
+pub const BOUND_PASSWORD_FILE_MAGIC: &[u8; 4] = b"OPQI";
+pub const BOUND_PASSWORD_FILE_VERSION: u8 = 1;
+pub const BOUND_UPLOAD_MAGIC: &[u8; 4] = b"OPQU";
+pub const BOUND_UPLOAD_VERSION: u8 = 1;
+
+const DIGEST_LEN: usize = 32;
+const HEADER_LEN: usize = 4 + 1 + DIGEST_LEN;
+
+const IDENTITY_DIGEST_LABEL: &[u8] = b"OPAQUE identities";
+const CLIENT_IDENTITY_LABEL: &[u8] = b"OPAQUE client identity";
+
+/// identities and context shared by the four registration and login steps:
+/// client and server must use equal configurations
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolConfig {
+    /// server identity, bound to the envelope at registration
+    pub servername: String,
+    /// login context, e.g. a channel_binding_context: login only
+    pub context: Vec<u8>,
+}
+
+impl ProtocolConfig {
+    pub fn new(servername: &str, context: &[u8]) -> ProtocolConfig {
+        ProtocolConfig {
+            servername: servername.to_string(),
+            context: context.to_owned(),
+        }
+    }
+
+    /// same identities with another login context
+    pub fn with_context(&self, context: &[u8]) -> ProtocolConfig {
+        ProtocolConfig::new(&self.servername, context)
+    }
+
+    /// configuration for a login by identity, of a credential registered
+    /// under a credential id distinct from it: the identity is added to the
+    /// login context, both sides must use the same one
+    pub fn for_identity(&self, identity: &str) -> ProtocolConfig {
+        let mut context = Vec::with_capacity(
+            self.context.len() + CLIENT_IDENTITY_LABEL.len() + 4 + identity.len(),
+        );
+        context.extend_from_slice(&self.context);
+        context.extend_from_slice(CLIENT_IDENTITY_LABEL);
+        context.extend_from_slice(&(identity.len() as u32).to_be_bytes());
+        context.extend_from_slice(identity.as_bytes());
+        self.with_context(&context)
+    }
+
+    /// identifiers of username for opaque-ke
+    pub(crate) fn identifiers<'a>(&'a self, username: &'a str) -> Identifiers<'a> {
+        Identifiers {
+            client: Some(username.as_bytes()),
+            server: Some(self.servername.as_bytes()),
+        }
+    }
+
+    /// digest of the identities of username, the context is left out
+    /// since it only exists at login
+    pub fn identity_digest(&self, username: &str) -> [u8; DIGEST_LEN] {
+        let mut hasher = Sha256::new();
+        hasher.update(IDENTITY_DIGEST_LABEL);
+        hasher.update((username.len() as u32).to_be_bytes());
+        hasher.update(username.as_bytes());
+        hasher.update((self.servername.len() as u32).to_be_bytes());
+        hasher.update(self.servername.as_bytes());
+        hasher.finalize().into()
+    }
+
+    /// registration upload of username recording the identities the client
+    /// bound the envelope to
+    pub fn bind_upload(&self, username: &str, upload: &[u8]) -> Vec<u8> {
+        self.bind(BOUND_UPLOAD_MAGIC, BOUND_UPLOAD_VERSION, username, upload)
+    }
+
+    /// registration upload to build the password file of username from:
+    /// fails if the client registered with other identities than this configuration
+    pub fn check_upload<'a>(
+        &self,
+        username: &str,
+        upload: &'a [u8],
+    ) -> Result<&'a [u8], OpaqueError> {
+        self.check(
+            split_bound(BOUND_UPLOAD_MAGIC, BOUND_UPLOAD_VERSION, upload)?,
+            username,
+        )
+    }
+
+    /// password file of username recording the identities it was registered with
+    pub fn bind_password_file(&self, username: &str, password_file: &[u8]) -> Vec<u8> {
+        self.bind(
+            BOUND_PASSWORD_FILE_MAGIC,
+            BOUND_PASSWORD_FILE_VERSION,
+            username,
+            password_file,
+        )
+    }
+
+    /// password file to log username in with: fails if it was
+    /// registered with other identities than this configuration
+    pub fn check_password_file<'a>(
+        &self,
+        username: &str,
+        password_file: &'a [u8],
+    ) -> Result<&'a [u8], OpaqueError> {
+        self.check(
+            split_bound(
+                BOUND_PASSWORD_FILE_MAGIC,
+                BOUND_PASSWORD_FILE_VERSION,
+                password_file,
+            )?,
+            username,
+        )
+    }
+
+    fn bind(&self, magic: &[u8; 4], version: u8, username: &str, value: &[u8]) -> Vec<u8> {
+        let mut bound = Vec::with_capacity(HEADER_LEN + value.len());
+        bound.extend_from_slice(magic);
+        bound.push(version);
+        bound.extend_from_slice(&self.identity_digest(username));
+        bound.extend_from_slice(value);
+        bound
+    }
+
+    fn check<'a>(
+        &self,
+        split: (Option<&[u8]>, &'a [u8]),
+        username: &str,
+    ) -> Result<&'a [u8], OpaqueError> {
+        match split {
+            (Some(digest), value) => {
+                if digest != self.identity_digest(username) {
+                    return Err(OpaqueError::IdentityMismatch);
+                }
+                Ok(value)
+            }
+            (None, value) => Ok(value),
+        }
+    }
+}
+
+// identity digest, if any, and opaque-ke upload or password file; neither
+// of them serialized ever starts with a magic: they start with a public key,
+// whose first byte is 2 or 3 (P-256) or even (Ristretto255)
+fn split_bound<'a>(
+    magic: &[u8; 4],
+    version: u8,
+    bound: &'a [u8],
+) -> Result<(Option<&'a [u8]>, &'a [u8]), OpaqueError> {
+    if !bound.starts_with(magic) {
+        return Ok((None, bound));
+    }
+    if bound.len() < HEADER_LEN {
+        return Err(OpaqueError::MalformedRecord);
+    }
+    if bound[4] != version {
+        return Err(OpaqueError::UnsupportedVersion(bound[4]));
+    }
+    Ok((Some(&bound[5..HEADER_LEN]), &bound[HEADER_LEN..]))
+}
+
+/// opaque-ke registration upload inside an upload, bound or not,
+/// without checking the identities
+pub fn unbind_upload(upload: &[u8]) -> Result<&[u8], OpaqueError> {
+    split_bound(BOUND_UPLOAD_MAGIC, BOUND_UPLOAD_VERSION, upload).map(|(_, value)| value)
+}
+
+/// opaque-ke password file inside a password file, bound or not,
+/// without checking the identities
+pub fn unbind_password_file(password_file: &[u8]) -> Result<&[u8], OpaqueError> {
+    split_bound(
+        BOUND_PASSWORD_FILE_MAGIC,
+        BOUND_PASSWORD_FILE_VERSION,
+        password_file,
+    )
+    .map(|(_, value)| value)
+}
@@ -0,0 +1,141 @@
+use crate::opaque_error::OpaqueError;
+
+// every envelope starts with these bytes, so random data
+// is refused before looking at the message inside
+pub const ENVELOPE_MAGIC: &[u8; 4] = b"OPQE";
+pub const ENVELOPE_VERSION: u8 = 1;
+
+// magic (4) + version (1) + message type (1) + ciphersuite (1) + length (4)
+pub const ENVELOPE_HEADER_LEN: usize = 11;
+
+/// ciphersuite which produced the message inside an envelope
+#[cfg(not(feature = "ristretto255"))]
+pub const CIPHERSUITE_ID: u8 = 1; // P-256, TripleDH
+#[cfg(feature = "ristretto255")]
+pub const CIPHERSUITE_ID: u8 = 2; // Ristretto255, TripleDH
+
+/// protocol message held by an envelope
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MessageType {
+    /// result of client registration start
+    RegistrationRequest = 1,
+    /// result of server registration start
+    RegistrationResponse = 2,
+    /// result of client registration finish
+    RegistrationUpload = 3,
+    /// result of client login start
+    CredentialRequest = 4,
+    /// result of server login start
+    CredentialResponse = 5,
+    /// result of client login finish
+    CredentialFinalization = 6,
+}
+
+impl MessageType {
+    pub fn from_byte(byte: u8) -> Option<MessageType> {
+        match byte {
+            1 => Some(MessageType::RegistrationRequest),
+            2 => Some(MessageType::RegistrationResponse),
+            3 => Some(MessageType::RegistrationUpload),
+            4 => Some(MessageType::CredentialRequest),
+            5 => Some(MessageType::CredentialResponse),
+            6 => Some(MessageType::CredentialFinalization),
+            _ => None,
+        }
+    }
+}
+
+/// header of an envelope, readable without decoding the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeHeader {
+    pub version: u8,
+    pub message_type: MessageType,
+    pub ciphersuite: u8,
+    pub length: usize,
+}
+
+/// serialized protocol message together with its type,
+/// the typed counterpart of an envelope
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Message {
+    pub message_type: MessageType,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub data: Vec<u8>,
+}
+
+impl Message {
+    pub fn new(message_type: MessageType, data: Vec<u8>) -> Message {
+        Message { message_type, data }
+    }
+
+    /// message as an envelope
+    pub fn to_envelope(&self) -> Vec<u8> {
+        encode_envelope(self.message_type, &self.data)
+    }
+
+    /// message held by an envelope, whatever its type
+    pub fn from_envelope(envelope: &[u8]) -> Result<Message, OpaqueError> {
+        let header = envelope_header(envelope)?;
+        let data = decode_envelope(header.message_type, envelope)?;
+        Ok(Message::new(header.message_type, data.to_owned()))
+    }
+}
+
+/// put a serialized protocol message inside an envelope
+pub fn encode_envelope(message_type: MessageType, message: &[u8]) -> Vec<u8> {
+    let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LEN + message.len());
+    envelope.extend_from_slice(ENVELOPE_MAGIC);
+    envelope.push(ENVELOPE_VERSION);
+    envelope.push(message_type as u8);
+    envelope.push(CIPHERSUITE_ID);
+    envelope.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    envelope.extend_from_slice(message);
+    envelope
+}
+
+/// read and check the header of an envelope
+pub fn envelope_header(envelope: &[u8]) -> Result<EnvelopeHeader, OpaqueError> {
+    if envelope.len() < ENVELOPE_HEADER_LEN || &envelope[..4] != ENVELOPE_MAGIC {
+        return Err(OpaqueError::NotAnEnvelope);
+    }
+    if envelope[4] != ENVELOPE_VERSION {
+        return Err(OpaqueError::UnsupportedVersion(envelope[4]));
+    }
+    let message_type = match MessageType::from_byte(envelope[5]) {
+        Some(val) => val,
+        None => return Err(OpaqueError::UnknownMessageType(envelope[5])),
+    };
+    let length = u32::from_be_bytes([envelope[7], envelope[8], envelope[9], envelope[10]]) as usize;
+    if envelope.len() - ENVELOPE_HEADER_LEN != length {
+        return Err(OpaqueError::NotAnEnvelope);
+    }
+
+    Ok(EnvelopeHeader {
+        version: envelope[4],
+        message_type,
+        ciphersuite: envelope[6],
+        length,
+    })
+}
+
+/// give back the message inside an envelope, checking it is
+/// the expected message and was produced with our ciphersuite
+pub fn decode_envelope(expected: MessageType, envelope: &[u8]) -> Result<&[u8], OpaqueError> {
+    let header = envelope_header(envelope)?;
+    if header.message_type != expected {
+        return Err(OpaqueError::MessageTypeMismatch {
+            expected,
+            found: header.message_type,
+        });
+    }
+    if header.ciphersuite != CIPHERSUITE_ID {
+        return Err(OpaqueError::CiphersuiteMismatch {
+            expected: CIPHERSUITE_ID,
+            found: header.ciphersuite,
+        });
+    }
+    Ok(&envelope[ENVELOPE_HEADER_LEN..])
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+
+use crate::opaque_envelope::MessageType;
+use crate::opaque_password_policy::PasswordIssue;
+
+/// errors returned by the Rust API of the library:
+/// the C API keeps returning empty structs and
+/// logging the error, as it always did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpaqueError {
+    /// key-encryption key is not 32 bytes long
+    InvalidKek,
+    /// sealed record is too short or has a broken header
+    MalformedRecord,
+    /// sealed record or envelope was produced by an unknown format version
+    UnsupportedVersion(u8),
+    /// sealed record holds a different kind of data than requested
+    RecordTypeMismatch,
+    /// sealed record was sealed under another key-encryption key
+    KekMismatch { expected: u32, found: u32 },
+    /// authentication of the sealed record failed
+    DecryptionFailed,
+    /// login state is past its expiry time
+    Expired,
+    /// login state was already used to finish a login
+    Replayed,
+    /// the credential store failed to read or write
+    Storage(String),
+    /// the opaque protocol step failed
+    Protocol(String),
+    /// no login in progress for the given session id and username
+    UnknownSession,
+    /// complete step called without the matching begin step
+    NotStarted,
+    /// data doesn't start with an envelope header or has a wrong length
+    NotAnEnvelope,
+    /// envelope holds a message type unknown to this library
+    UnknownMessageType(u8),
+    /// envelope holds another protocol message than the expected one
+    MessageTypeMismatch {
+        expected: MessageType,
+        found: MessageType,
+    },
+    /// envelope holds a message produced with another ciphersuite
+    CiphersuiteMismatch { expected: u8, found: u8 },
+    /// recovery code unknown, already used or mistyped
+    InvalidRecoveryCode,
+    /// channel record already received, or too old to tell
+    MessageReplayed,
+    /// every sequence number of the channel was used: a new login is needed
+    SequenceExhausted,
+    /// password file was registered with other identities than the login
+    IdentityMismatch,
+    /// password refused by the password policy of the client
+    WeakPassword(Vec<PasswordIssue>),
+    /// registration of a credential id that already has a password file
+    AlreadyRegistered,
+    /// a task running a step of the library failed, e.g. panicked
+    Internal(String),
+}
+
+impl fmt::Display for OpaqueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpaqueError::InvalidKek => write!(f, "key-encryption key must be 32 bytes"),
+            OpaqueError::MalformedRecord => write!(f, "malformed sealed record"),
+            OpaqueError::UnsupportedVersion(v) => {
+                write!(f, "unsupported format version {}", v)
+            }
+            OpaqueError::RecordTypeMismatch => write!(f, "sealed record type mismatch"),
+            OpaqueError::KekMismatch { expected, found } => write!(
+                f,
+                "sealed record uses key-encryption key {} instead of {}",
+                found, expected
+            ),
+            OpaqueError::DecryptionFailed => write!(f, "sealed record authentication failed"),
+            OpaqueError::Expired => write!(f, "login state expired"),
+            OpaqueError::Replayed => write!(f, "login state already used"),
+            OpaqueError::Storage(msg) => write!(f, "credential store error: {}", msg),
+            OpaqueError::Protocol(msg) => write!(f, "opaque protocol error: {}", msg),
+            OpaqueError::UnknownSession => write!(f, "unknown login session"),
+            OpaqueError::NotStarted => write!(f, "no registration or login in progress"),
+            OpaqueError::NotAnEnvelope => write!(f, "data is not a valid message envelope"),
+            OpaqueError::UnknownMessageType(t) => write!(f, "unknown message type {}", t),
+            OpaqueError::MessageTypeMismatch { expected, found } => {
+                write!(f, "expected {:?} message, got {:?}", expected, found)
+            }
+            OpaqueError::CiphersuiteMismatch { expected, found } => write!(
+                f,
+                "message produced with ciphersuite {} instead of {}",
+                found, expected
+            ),
+            OpaqueError::InvalidRecoveryCode => write!(f, "invalid recovery code"),
+            OpaqueError::MessageReplayed => write!(f, "channel record replayed or too old"),
+            OpaqueError::SequenceExhausted => write!(f, "channel sequence numbers exhausted"),
+            OpaqueError::IdentityMismatch => {
+                write!(f, "password file registered with other identities")
+            }
+            OpaqueError::WeakPassword(issues) => {
+                let reasons: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+                write!(f, "weak password: {}", reasons.join(", "))
+            }
+            OpaqueError::AlreadyRegistered => write!(f, "credential id already registered"),
+            OpaqueError::Internal(msg) => write!(f, "internal error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for OpaqueError {}
@@ -0,0 +1,248 @@
+use std::io::Read;
+use std::sync::Arc;
+use std::thread;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::opaque_error::OpaqueError;
+use crate::opaque_server_facade::OpaqueServer;
+
+// every message is sent as standard Base64 inside a JSON body:
+//
+// POST /register/start  {username, registration_request}            -> {registration_response}
+// POST /register/finish {username, registration_upload}             -> {}
+// POST /login/start     {username, credential_request}              -> {session_id, credential_response}
+// POST /login/finish    {username, session_id, credential_finalization} -> {}
+//
+// errors are returned as {error} with a 4xx status code: 400 for a malformed
+// registration message, 401 for a failed login whatever the reason, 409 when
+// registering a username that already has a password file, 413 for a body
+// longer than MAX_BODY_LEN
+//
+// registration needs no authentication, so it can only create users: a
+// password file is never replaced through these endpoints
+
+/// longest request body accepted, far above any message of the protocol
+pub const MAX_BODY_LEN: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterStartRequest {
+    pub username: String,
+    pub registration_request: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterStartResponse {
+    pub registration_response: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RegisterFinishRequest {
+    pub username: String,
+    pub registration_upload: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginStartRequest {
+    pub username: String,
+    pub credential_request: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginStartResponse {
+    pub session_id: String,
+    pub credential_response: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct LoginFinishRequest {
+    pub username: String,
+    pub session_id: String,
+    pub credential_finalization: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+// empty JSON object, returned by the finish endpoints
+#[derive(Serialize)]
+struct Empty {}
+
+// error answered to the HTTP client: status code and message
+struct HttpError(u16, String);
+
+impl From<OpaqueError> for HttpError {
+    fn from(err: OpaqueError) -> HttpError {
+        let status = match err {
+            OpaqueError::Storage(_) | OpaqueError::Internal(_) => 500,
+            OpaqueError::UnknownSession | OpaqueError::Expired | OpaqueError::Replayed => 401,
+            OpaqueError::AlreadyRegistered => 409,
+            _ => 400,
+        };
+        HttpError(status, err.to_string())
+    }
+}
+
+// a failed login is reported without details
+fn login_error(err: OpaqueError) -> HttpError {
+    match err {
+        OpaqueError::Protocol(_) => HttpError(401, "login failed".to_string()),
+        _ => HttpError::from(err),
+    }
+}
+
+fn parse<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, HttpError> {
+    serde_json::from_slice(body).map_err(|err| HttpError(400, format!("invalid body: {}", err)))
+}
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, HttpError> {
+    STANDARD
+        .decode(value)
+        .map_err(|_| HttpError(400, format!("{} is not valid Base64", field)))
+}
+
+fn to_json<T: Serialize>(value: &T) -> Vec<u8> {
+    serde_json::to_vec(value).unwrap()
+}
+
+/// answer a single request: path and JSON body in,
+/// status code and JSON body out
+pub fn handle(server: &OpaqueServer, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+    let result = match path {
+        "/register/start" => register_start(server, body),
+        "/register/finish" => register_finish(server, body),
+        "/login/start" => login_start(server, body),
+        "/login/finish" => login_finish(server, body),
+        _ => Err(HttpError(404, format!("unknown endpoint {}", path))),
+    };
+
+    match result {
+        Ok(response) => (200, response),
+        Err(HttpError(status, error)) => (status, to_json(&ErrorResponse { error })),
+    }
+}
+
+fn register_start(server: &OpaqueServer, body: &[u8]) -> Result<Vec<u8>, HttpError> {
+    let req: RegisterStartRequest = parse(body)?;
+    let request = decode("registration_request", &req.registration_request)?;
+
+    let response = server.handle_registration_request(&req.username, &request)?;
+    Ok(to_json(&RegisterStartResponse {
+        registration_response: STANDARD.encode(response),
+    }))
+}
+
+fn register_finish(server: &OpaqueServer, body: &[u8]) -> Result<Vec<u8>, HttpError> {
+    let req: RegisterFinishRequest = parse(body)?;
+    let upload = decode("registration_upload", &req.registration_upload)?;
+
+    server.handle_registration_upload(&req.username, &upload)?;
+    Ok(to_json(&Empty {}))
+}
+
+fn login_start(server: &OpaqueServer, body: &[u8]) -> Result<Vec<u8>, HttpError> {
+    let req: LoginStartRequest = parse(body)?;
+    let request = decode("credential_request", &req.credential_request)?;
+
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    let session_id = URL_SAFE_NO_PAD.encode(id);
+
+    let response = server
+        .handle_credential_request(&req.username, &session_id, &request)
+        .map_err(login_error)?;
+    Ok(to_json(&LoginStartResponse {
+        session_id,
+        credential_response: STANDARD.encode(response),
+    }))
+}
+
+fn login_finish(server: &OpaqueServer, body: &[u8]) -> Result<Vec<u8>, HttpError> {
+    let req: LoginFinishRequest = parse(body)?;
+    let finalization = decode("credential_finalization", &req.credential_finalization)?;
+
+    // the session key stays on the server: the caller decides what to do
+    // with it, this reference server only reports the login result
+    server
+        .handle_credential_finalization(&req.username, &req.session_id, &finalization)
+        .map_err(login_error)?;
+    Ok(to_json(&Empty {}))
+}
+
+fn body_too_long() -> (u16, Vec<u8>) {
+    (
+        413,
+        to_json(&ErrorResponse {
+            error: format!("body longer than {} bytes", MAX_BODY_LEN),
+        }),
+    )
+}
+
+fn respond(server: &OpaqueServer, mut request: Request) {
+    let (status, body) = if *request.method() != Method::Post {
+        (
+            405,
+            to_json(&ErrorResponse {
+                error: "only POST is allowed".to_string(),
+            }),
+        )
+    } else if request.body_length().unwrap_or(0) > MAX_BODY_LEN {
+        body_too_long()
+    } else {
+        // the length may be missing or wrong: read one byte more to tell
+        let mut body = Vec::new();
+        match Read::take(request.as_reader(), MAX_BODY_LEN as u64 + 1).read_to_end(&mut body) {
+            Ok(_) if body.len() > MAX_BODY_LEN => body_too_long(),
+            Ok(_) => handle(server, &request.url().to_string(), &body),
+            Err(err) => (
+                400,
+                to_json(&ErrorResponse {
+                    error: err.to_string(),
+                }),
+            ),
+        }
+    };
+
+    let response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    if let Err(err) = request.respond(response) {
+        println!("RUST - LOG: HTTP response error: {}", err);
+    }
+}
+
+/// serve the endpoints above on addr with the given number of worker threads,
+/// never returning unless the listening socket can't be opened
+pub fn serve(server: Arc<OpaqueServer>, addr: &str, workers: usize) -> Result<(), String> {
+    let http = Server::http(addr).map_err(|err| err.to_string())?;
+    println!("RUST - LOG: Listening on {}", addr);
+    serve_on(server, http, workers);
+    Ok(())
+}
+
+/// same as serve, on an already listening server, e.g. one bound
+/// to port 0 whose address is read with server_addr: never returns
+pub fn serve_on(server: Arc<OpaqueServer>, http: Server, workers: usize) {
+    let http = Arc::new(http);
+    let handles: Vec<_> = (0..workers.max(1))
+        .map(|_| {
+            let http = http.clone();
+            let server = server.clone();
+            thread::spawn(move || {
+                for request in http.incoming_requests() {
+                    respond(&server, request);
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
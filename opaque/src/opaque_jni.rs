@@ -0,0 +1,260 @@
+// JNI layer of java/src/opaque/Opaque.java: every native method returns
+// byte arrays and throws opaque.OpaqueException instead of empty results
+
+use jni::objects::{JByteArray, JClass, JObject, JString};
+use jni::sys::{jboolean, jbyteArray, jobjectArray, JNI_TRUE};
+use jni::JNIEnv;
+
+use crate::opaque_client_facade::{
+    login_finish, login_start, registration_finish, registration_start,
+};
+use crate::opaque_config::ProtocolConfig;
+use crate::opaque_error::OpaqueError;
+use crate::opaque_login_state::{check_login_state, LoginStatePolicy};
+use crate::opaque_password_policy::set_default_password_policy;
+use crate::opaque_server_facade::{
+    credential_response, deserialize_setup, generate_setup, generate_setup_with_key,
+    password_file_from_upload, registration_response, session_key,
+};
+
+const OPAQUE_EXCEPTION: &str = "opaque/OpaqueException";
+
+enum Failure {
+    Opaque(OpaqueError),
+    Jni(jni::errors::Error),
+}
+
+impl From<OpaqueError> for Failure {
+    fn from(err: OpaqueError) -> Failure {
+        Failure::Opaque(err)
+    }
+}
+
+impl From<jni::errors::Error> for Failure {
+    fn from(err: jni::errors::Error) -> Failure {
+        Failure::Jni(err)
+    }
+}
+
+// leave an exception pending in the JVM: the caller returns null
+fn throw(env: &mut JNIEnv, failure: Failure) {
+    let _ = match failure {
+        Failure::Opaque(err) => env.throw_new(OPAQUE_EXCEPTION, err.to_string()),
+        // a Java exception is already pending
+        Failure::Jni(jni::errors::Error::JavaException) => Ok(()),
+        Failure::Jni(err) => env.throw_new("java/lang/RuntimeException", err.to_string()),
+    };
+}
+
+fn get_string(env: &mut JNIEnv, val: &JString) -> Result<String, Failure> {
+    Ok(env.get_string(val)?.into())
+}
+
+fn get_bytes(env: &mut JNIEnv, val: &JByteArray) -> Result<Vec<u8>, Failure> {
+    Ok(env.convert_byte_array(val)?)
+}
+
+fn new_bytes(env: &mut JNIEnv, val: &[u8]) -> Result<jbyteArray, Failure> {
+    Ok(env.byte_array_from_slice(val)?.into_raw())
+}
+
+// byte[][] holding vals in order
+fn new_bytes_array(env: &mut JNIEnv, vals: &[&[u8]]) -> Result<jobjectArray, Failure> {
+    let array = env.new_object_array(vals.len() as i32, "[B", JObject::null())?;
+    for (i, val) in vals.iter().enumerate() {
+        let element = env.byte_array_from_slice(val)?;
+        env.set_object_array_element(&array, i as i32, element)?;
+    }
+    Ok(array.into_raw())
+}
+
+fn or_throw<T>(env: &mut JNIEnv, result: Result<*mut T, Failure>) -> *mut T {
+    match result {
+        Ok(val) => val,
+        Err(failure) => {
+            throw(env, failure);
+            std::ptr::null_mut()
+        }
+    }
+}
+
+/// { registration request, client state }: throws if the policy
+/// of nativeSetDefaultPasswordPolicy refuses password
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeClientRegistrationStart<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    password: JString<'local>,
+) -> jobjectArray {
+    let result = (|| {
+        let pwd = get_string(&mut env, &password)?;
+        let (request, state) = registration_start(&pwd)?;
+        new_bytes_array(&mut env, &[&request, &state])
+    })();
+    or_throw(&mut env, result)
+}
+
+/// { registration response, server setup }: with a null private key
+/// a new key pair is generated
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeServerRegistrationStart<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    username: JString<'local>,
+    registration_request: JByteArray<'local>,
+    private_key: JByteArray<'local>,
+) -> jobjectArray {
+    let result = (|| {
+        let user = get_string(&mut env, &username)?;
+        let request = get_bytes(&mut env, &registration_request)?;
+        let setup = if private_key.is_null() {
+            generate_setup()
+        } else {
+            let key = get_bytes(&mut env, &private_key)?;
+            generate_setup_with_key(&key)?
+        };
+        let response = registration_response(&deserialize_setup(&setup)?, &user, &request)?;
+        new_bytes_array(&mut env, &[&response, &setup])
+    })();
+    or_throw(&mut env, result)
+}
+
+/// registration upload: throws if the policy of
+/// nativeSetDefaultPasswordPolicy refuses password for username
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeClientRegistrationFinish<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    password: JString<'local>,
+    registration_response: JByteArray<'local>,
+    state: JByteArray<'local>,
+    username: JString<'local>,
+    servername: JString<'local>,
+) -> jbyteArray {
+    let result = (|| {
+        let pwd = get_string(&mut env, &password)?;
+        let response = get_bytes(&mut env, &registration_response)?;
+        let client_state = get_bytes(&mut env, &state)?;
+        let user = get_string(&mut env, &username)?;
+        let server = get_string(&mut env, &servername)?;
+        let config = ProtocolConfig::new(&server, &[]);
+        let result = registration_finish(&pwd, &response, &client_state, &user, &config)?;
+        new_bytes(&mut env, &result.upload)
+    })();
+    or_throw(&mut env, result)
+}
+
+/// password file
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeServerRegistrationFinish<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    registration_upload: JByteArray<'local>,
+) -> jbyteArray {
+    let result = (|| {
+        let upload = get_bytes(&mut env, &registration_upload)?;
+        let password_file = password_file_from_upload(&upload)?;
+        new_bytes(&mut env, &password_file)
+    })();
+    or_throw(&mut env, result)
+}
+
+/// { credential request, client state }
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeClientLoginStart<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    password: JString<'local>,
+) -> jobjectArray {
+    let result = (|| {
+        let pwd = get_string(&mut env, &password)?;
+        let (request, state) = login_start(&pwd)?;
+        new_bytes_array(&mut env, &[&request, &state])
+    })();
+    or_throw(&mut env, result)
+}
+
+/// { credential response, server state }
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeServerLoginStart<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    username: JString<'local>,
+    password_file: JByteArray<'local>,
+    credential_request: JByteArray<'local>,
+    setup: JByteArray<'local>,
+    servername: JString<'local>,
+    context: JString<'local>,
+) -> jobjectArray {
+    let result = (|| {
+        let user = get_string(&mut env, &username)?;
+        let pass_file = get_bytes(&mut env, &password_file)?;
+        let request = get_bytes(&mut env, &credential_request)?;
+        let server_setup = deserialize_setup(&get_bytes(&mut env, &setup)?)?;
+        let server = get_string(&mut env, &servername)?;
+        let ctx = get_string(&mut env, &context)?;
+        let config = ProtocolConfig::new(&server, ctx.as_bytes());
+        let (response, state) =
+            credential_response(&server_setup, &config, &user, Some(&pass_file), &request)?;
+        new_bytes_array(&mut env, &[&response, &state])
+    })();
+    or_throw(&mut env, result)
+}
+
+/// { credential finalization, session key }: throws if the password
+/// is wrong or the server isn't the expected one
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeClientLoginFinish<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    password: JString<'local>,
+    credential_response: JByteArray<'local>,
+    state: JByteArray<'local>,
+    username: JString<'local>,
+    servername: JString<'local>,
+    context: JString<'local>,
+) -> jobjectArray {
+    let result = (|| {
+        let pwd = get_string(&mut env, &password)?;
+        let response = get_bytes(&mut env, &credential_response)?;
+        let client_state = get_bytes(&mut env, &state)?;
+        let user = get_string(&mut env, &username)?;
+        let server = get_string(&mut env, &servername)?;
+        let ctx = get_string(&mut env, &context)?;
+        let config = ProtocolConfig::new(&server, ctx.as_bytes());
+        let result = login_finish(&pwd, &response, &client_state, &user, &config)?;
+        new_bytes_array(
+            &mut env,
+            &[&result.credential_finalization, &result.session_key],
+        )
+    })();
+    or_throw(&mut env, result)
+}
+
+/// session key: throws if the login failed or the state is too old
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeServerLoginFinish<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    credential_finalization: JByteArray<'local>,
+    state: JByteArray<'local>,
+) -> jbyteArray {
+    let result = (|| {
+        let finalization = get_bytes(&mut env, &credential_finalization)?;
+        let wrapped_state = get_bytes(&mut env, &state)?;
+        let login_state = check_login_state(&LoginStatePolicy::default(), &wrapped_state)?;
+        let key = session_key(login_state, &finalization)?;
+        new_bytes(&mut env, &key)
+    })();
+    or_throw(&mut env, result)
+}
+
+/// DefaultPasswordPolicy run by registration start and finish if enabled
+#[no_mangle]
+pub extern "system" fn Java_opaque_Opaque_nativeSetDefaultPasswordPolicy<'local>(
+    _env: JNIEnv<'local>,
+    _class: JClass<'local>,
+    enabled: jboolean,
+) {
+    set_default_password_policy(enabled == JNI_TRUE);
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use crate::opaque_error::OpaqueError;
+
+// max age applied by opaque_server_login_finish when nothing else is configured
+pub const DEFAULT_LOGIN_STATE_MAX_AGE: Duration = Duration::from_secs(300);
+
+pub const LOGIN_STATE_ID_LEN: usize = 16;
+// creation timestamp (8) + state id (16)
+const HEADER_LEN: usize = 8 + LOGIN_STATE_ID_LEN;
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// data put in front of every serialized ServerLogin state
+/// returned by server_login_start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoginStateHeader {
+    pub created_at: u64,
+    pub id: [u8; LOGIN_STATE_ID_LEN],
+}
+
+/// add creation timestamp and a random id to a serialized ServerLogin state
+pub fn wrap_login_state(state: &[u8]) -> Vec<u8> {
+    let mut id = [0u8; LOGIN_STATE_ID_LEN];
+    OsRng.fill_bytes(&mut id);
+
+    let mut wrapped = Vec::with_capacity(HEADER_LEN + state.len());
+    wrapped.extend_from_slice(&now_secs().to_be_bytes());
+    wrapped.extend_from_slice(&id);
+    wrapped.extend_from_slice(state);
+    wrapped
+}
+
+/// split a state returned by wrap_login_state in header and ServerLogin state
+pub fn unwrap_login_state(wrapped: &[u8]) -> Result<(LoginStateHeader, &[u8]), OpaqueError> {
+    if wrapped.len() < HEADER_LEN {
+        return Err(OpaqueError::MalformedRecord);
+    }
+
+    let mut created_at = [0u8; 8];
+    created_at.copy_from_slice(&wrapped[..8]);
+    let mut id = [0u8; LOGIN_STATE_ID_LEN];
+    id.copy_from_slice(&wrapped[8..HEADER_LEN]);
+
+    Ok((
+        LoginStateHeader {
+            created_at: u64::from_be_bytes(created_at),
+            id,
+        },
+        &wrapped[HEADER_LEN..],
+    ))
+}
+
+/// storage of the login states already finished, needed to
+/// reject a state used twice: implement it on top of redis or
+/// any other shared storage when running more than one server
+pub trait ReplayCache: Send + Sync {
+    /// record id as used until expires_at (unix seconds):
+    /// return false if id was already recorded
+    fn check_and_insert(&self, id: &[u8; LOGIN_STATE_ID_LEN], expires_at: u64) -> bool;
+}
+
+/// replay cache kept in process memory: expired ids
+/// are dropped every time a new id is recorded
+#[derive(Default)]
+pub struct MemoryReplayCache {
+    seen: Mutex<HashMap<[u8; LOGIN_STATE_ID_LEN], u64>>,
+}
+
+impl MemoryReplayCache {
+    pub fn new() -> MemoryReplayCache {
+        MemoryReplayCache::default()
+    }
+}
+
+impl ReplayCache for MemoryReplayCache {
+    fn check_and_insert(&self, id: &[u8; LOGIN_STATE_ID_LEN], expires_at: u64) -> bool {
+        let now = now_secs();
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, exp| *exp >= now);
+        if seen.contains_key(id) {
+            return false;
+        }
+        seen.insert(*id, expires_at);
+        true
+    }
+}
+
+/// rules checked on a login state before finishing the login
+pub struct LoginStatePolicy<'a> {
+    pub max_age: Duration,
+    pub replay_cache: Option<&'a dyn ReplayCache>,
+}
+
+impl Default for LoginStatePolicy<'_> {
+    fn default() -> Self {
+        LoginStatePolicy {
+            max_age: DEFAULT_LOGIN_STATE_MAX_AGE,
+            replay_cache: None,
+        }
+    }
+}
+
+/// check age and single use of a state returned by wrap_login_state,
+/// giving back the ServerLogin state when all checks pass
+pub fn check_login_state<'s>(
+    policy: &LoginStatePolicy,
+    wrapped: &'s [u8],
+) -> Result<&'s [u8], OpaqueError> {
+    let (header, state) = unwrap_login_state(wrapped)?;
+
+    let expires_at = header.created_at.saturating_add(policy.max_age.as_secs());
+    if now_secs() > expires_at {
+        return Err(OpaqueError::Expired);
+    }
+
+    if let Some(cache) = policy.replay_cache {
+        if !cache.check_and_insert(&header.id, expires_at) {
+            return Err(OpaqueError::Replayed);
+        }
+    }
+
+    Ok(state)
+}
@@ -0,0 +1,108 @@
+use std::env;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use unicode_normalization::UnicodeNormalization;
+use zeroize::Zeroizing;
+
+// The same password typed on different systems can reach us as different
+// code points (macOS keyboards produce decomposed characters, Windows ones
+// composed characters), while the OPRF only sees bytes: every client function
+// normalizes the password first, so they all derive the same keys.
+//
+// The normalization is the same for the whole process: set it with
+// set_password_normalization or the PASSWORD_NORMALIZATION environment
+// variable (none, nfc or nfkc), none when neither is given, so users
+// registered with non-ASCII passwords before normalization existed keep
+// logging in. New deployments should opt in to NFC before the first
+// registration: changing it later locks those users out.
+
+/// normalization applied to passwords before they are used
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordNormalization {
+    /// password bytes used as given
+    None = 1,
+    /// NFC with non-ASCII spaces mapped to U+0020, as the PRECIS
+    /// OpaqueString profile (RFC 8265) does
+    Nfc = 2,
+    /// NFKC, as SASLprep (RFC 4013) does: compatibility characters such as
+    /// ligatures and full-width letters become their plain counterparts
+    Nfkc = 3,
+}
+
+impl PasswordNormalization {
+    pub fn from_byte(byte: u8) -> Option<PasswordNormalization> {
+        match byte {
+            1 => Some(PasswordNormalization::None),
+            2 => Some(PasswordNormalization::Nfc),
+            3 => Some(PasswordNormalization::Nfkc),
+            _ => None,
+        }
+    }
+
+    /// normalization named none, nfc or nfkc, in any case
+    pub fn from_name(name: &str) -> Option<PasswordNormalization> {
+        match name.to_ascii_lowercase().as_str() {
+            "none" => Some(PasswordNormalization::None),
+            "nfc" => Some(PasswordNormalization::Nfc),
+            "nfkc" => Some(PasswordNormalization::Nfkc),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_PASSWORD_NORMALIZATION: PasswordNormalization = PasswordNormalization::None;
+
+// 0 until set or read from the environment
+static NORMALIZATION: AtomicU8 = AtomicU8::new(0);
+
+/// normalization applied by every client function from now on
+pub fn set_password_normalization(normalization: PasswordNormalization) {
+    NORMALIZATION.store(normalization as u8, Ordering::Relaxed);
+}
+
+/// normalization currently applied by the client functions
+pub fn password_normalization() -> PasswordNormalization {
+    if let Some(val) = PasswordNormalization::from_byte(NORMALIZATION.load(Ordering::Relaxed)) {
+        return val;
+    }
+    let val = match env::var("PASSWORD_NORMALIZATION") {
+        Ok(name) => PasswordNormalization::from_name(&name).unwrap_or_else(|| {
+            println!(
+                "RUST - LOG: Unknown PASSWORD_NORMALIZATION {}, using {:?}",
+                name, DEFAULT_PASSWORD_NORMALIZATION
+            );
+            DEFAULT_PASSWORD_NORMALIZATION
+        }),
+        Err(_) => DEFAULT_PASSWORD_NORMALIZATION,
+    };
+    // a concurrent set_password_normalization wins
+    let _ = NORMALIZATION.compare_exchange(0, val as u8, Ordering::Relaxed, Ordering::Relaxed);
+    PasswordNormalization::from_byte(NORMALIZATION.load(Ordering::Relaxed)).unwrap()
+}
+
+// Zs characters other than U+0020
+fn is_non_ascii_space(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00A0}' | '\u{1680}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}'
+    )
+}
+
+/// password as given to the OPRF by the client functions
+pub fn normalize_password_with(password: &str, normalization: PasswordNormalization) -> String {
+    match normalization {
+        PasswordNormalization::None => password.to_string(),
+        PasswordNormalization::Nfc => password
+            .chars()
+            .map(|c| if is_non_ascii_space(c) { ' ' } else { c })
+            .nfc()
+            .collect(),
+        PasswordNormalization::Nfkc => password.nfkc().collect(),
+    }
+}
+
+/// password normalized with the current normalization,
+/// wiped from memory when dropped
+pub fn normalize_password(password: &str) -> Zeroizing<String> {
+    Zeroizing::new(normalize_password_with(password, password_normalization()))
+}
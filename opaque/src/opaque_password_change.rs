@@ -0,0 +1,108 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::opaque_error::OpaqueError;
+
+// a password change is a login with the current password and a registration
+// with the new one run together: the registration upload is accepted only
+// with a tag computed under the session key of that login, so it can't be
+// replayed or sent without knowing the current password. The recovery
+// secret, wrapped under the export key of the new password, goes with the
+// upload and under the same tag, so it keeps opening after the change.
+const PASSWORD_CHANGE_LABEL: &[u8] = b"OPAQUE password change";
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// first message of a password change, for the server
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PasswordChangeRequest {
+    /// login with the current password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub credential_request: Vec<u8>,
+    /// registration of the new password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub registration_request: Vec<u8>,
+}
+
+/// server answer to a PasswordChangeRequest
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PasswordChangeResponse {
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub credential_response: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub registration_response: Vec<u8>,
+    /// recovery secret wrapped under the export key of the current
+    /// password, empty if it's still the one derived from it
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub wrapped_secret: Vec<u8>,
+}
+
+/// last message of a password change, for the server
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PasswordChangeFinalization {
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub credential_finalization: Vec<u8>,
+    /// registration upload of the new password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub upload: Vec<u8>,
+    /// recovery secret wrapped under the export key of the new password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub wrapped_secret: Vec<u8>,
+    /// binds upload and wrapped secret to the session key of the login
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub tag: Vec<u8>,
+}
+
+/// result of complete_password_change
+pub struct PasswordChangeResult {
+    pub finalization: PasswordChangeFinalization,
+    pub session_key: Vec<u8>,
+    /// export key of the new password
+    pub export_key: Vec<u8>,
+    /// recovery secret, the same one as before the change
+    pub recovery_secret: Vec<u8>,
+}
+
+fn password_change_mac(
+    session_key: &[u8],
+    username: &str,
+    upload: &[u8],
+    wrapped_secret: &[u8],
+) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(session_key).expect("HMAC accepts any key length");
+    mac.update(PASSWORD_CHANGE_LABEL);
+    mac.update(&(username.len() as u32).to_be_bytes());
+    mac.update(username.as_bytes());
+    mac.update(&(upload.len() as u32).to_be_bytes());
+    mac.update(upload);
+    mac.update(wrapped_secret);
+    mac
+}
+
+/// tag binding the registration upload of the new password, and the
+/// recovery secret wrapped under it, to the session key of the login
+/// with the current one
+pub fn password_change_tag(
+    session_key: &[u8],
+    username: &str,
+    upload: &[u8],
+    wrapped_secret: &[u8],
+) -> Vec<u8> {
+    password_change_mac(session_key, username, upload, wrapped_secret)
+        .finalize()
+        .into_bytes()
+        .to_vec()
+}
+
+/// constant-time check of a tag made by password_change_tag
+pub fn verify_password_change_tag(
+    session_key: &[u8],
+    username: &str,
+    upload: &[u8],
+    wrapped_secret: &[u8],
+    tag: &[u8],
+) -> Result<(), OpaqueError> {
+    password_change_mac(session_key, username, upload, wrapped_secret)
+        .verify_slice(tag)
+        .map_err(|_| OpaqueError::Protocol("invalid password change tag".to_string()))
+}
@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use lazy_static::lazy_static;
+
+use crate::opaque_error::OpaqueError;
+use crate::opaque_password::normalize_password;
+
+// OPAQUE hides the password from the server, so only the client can refuse a
+// weak one: OpaqueClient runs its policy before starting a registration or a
+// password change, and check_password can be called by a UI as the user types.
+// Every check works on the normalized password, the one actually registered.
+//
+// Bindings without an OpaqueClient (C, Python, JNI and wasm registration
+// functions) run the policy set for the whole process with set_password_policy,
+// as do the OpaqueClients without one of their own: registration start
+// doesn't know the username yet, so registration finish checks it again.
+
+lazy_static! {
+    // None until set_password_policy
+    static ref PASSWORD_POLICY: RwLock<Option<Arc<dyn PasswordPolicy>>> = RwLock::new(None);
+    static ref COMMON_PASSWORDS: HashSet<&'static str> = include_str!("common_passwords.txt")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+}
+
+/// reason for refusing a password, for the UI to display
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PasswordIssue {
+    /// fewer characters than required
+    TooShort { min: usize, found: usize },
+    /// fewer character classes (lower case, upper case, digits,
+    /// symbols, other letters) than required
+    TooFewCharacterClasses { min: usize, found: usize },
+    /// in the list of common passwords, maybe with digits or symbols appended
+    Common,
+    /// contains the username
+    ContainsUsername,
+    /// a single character repeated
+    Repetitive,
+}
+
+impl PasswordIssue {
+    /// stable identifier of the issue, e.g. to pick a translated message
+    pub fn code(&self) -> &'static str {
+        match self {
+            PasswordIssue::TooShort { .. } => "too_short",
+            PasswordIssue::TooFewCharacterClasses { .. } => "too_few_character_classes",
+            PasswordIssue::Common => "common",
+            PasswordIssue::ContainsUsername => "contains_username",
+            PasswordIssue::Repetitive => "repetitive",
+        }
+    }
+}
+
+impl fmt::Display for PasswordIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordIssue::TooShort { min, found } => write!(
+                f,
+                "password has {} characters, at least {} are needed",
+                found, min
+            ),
+            PasswordIssue::TooFewCharacterClasses { min, found } => write!(
+                f,
+                "password uses {} kinds of characters, at least {} are needed",
+                found, min
+            ),
+            PasswordIssue::Common => write!(f, "password is too common"),
+            PasswordIssue::ContainsUsername => write!(f, "password contains the username"),
+            PasswordIssue::Repetitive => write!(f, "password repeats a single character"),
+        }
+    }
+}
+
+/// strength of a password as guessed from its composition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrengthScore {
+    VeryWeak = 0,
+    Weak = 1,
+    Fair = 2,
+    Strong = 3,
+    VeryStrong = 4,
+}
+
+/// result of estimate_strength
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PasswordStrength {
+    /// characters of the normalized password
+    pub length: usize,
+    /// character classes used, see PasswordIssue::TooFewCharacterClasses
+    pub classes: usize,
+    /// in the list of common passwords
+    pub common: bool,
+    /// bits of a brute force search over the character classes used,
+    /// 0 for common and repetitive passwords
+    pub entropy_bits: u32,
+    pub score: StrengthScore,
+}
+
+// lower case, upper case, digits, ASCII symbols, anything else
+const CLASS_SIZES: [u32; 5] = [26, 26, 10, 33, 100];
+
+fn class_of(c: char) -> usize {
+    if c.is_ascii_lowercase() {
+        0
+    } else if c.is_ascii_uppercase() {
+        1
+    } else if c.is_ascii_digit() {
+        2
+    } else if c.is_ascii() {
+        3
+    } else {
+        4
+    }
+}
+
+fn is_common(password: &str) -> bool {
+    let lower = password.to_lowercase();
+    // "Password1!" is as common as "password"
+    let stem = lower.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation());
+    COMMON_PASSWORDS.contains(lower.as_str())
+        || (!stem.is_empty() && COMMON_PASSWORDS.contains(stem))
+}
+
+fn is_repetitive(password: &str) -> bool {
+    let mut chars = password.chars();
+    match chars.next() {
+        Some(first) => chars.all(|c| c == first),
+        None => false,
+    }
+}
+
+fn strength_of(password: &str) -> PasswordStrength {
+    let mut used = [false; 5];
+    let mut length = 0;
+    for c in password.chars() {
+        used[class_of(c)] = true;
+        length += 1;
+    }
+    let classes = used.iter().filter(|val| **val).count();
+    let common = is_common(password);
+
+    let entropy_bits = if common || is_repetitive(password) {
+        0
+    } else {
+        let pool: u32 = CLASS_SIZES
+            .iter()
+            .zip(used.iter())
+            .filter(|(_, val)| **val)
+            .map(|(size, _)| size)
+            .sum();
+        (length as f64 * (pool as f64).log2()) as u32
+    };
+    let score = match entropy_bits {
+        0..=27 => StrengthScore::VeryWeak,
+        28..=35 => StrengthScore::Weak,
+        36..=59 => StrengthScore::Fair,
+        60..=127 => StrengthScore::Strong,
+        _ => StrengthScore::VeryStrong,
+    };
+
+    PasswordStrength {
+        length,
+        classes,
+        common,
+        entropy_bits,
+        score,
+    }
+}
+
+/// strength of password, normalized as the client functions do
+pub fn estimate_strength(password: &str) -> PasswordStrength {
+    strength_of(&normalize_password(password))
+}
+
+/// check run by OpaqueClient before a registration or a password change:
+/// implement it to apply other rules than DefaultPasswordPolicy
+pub trait PasswordPolicy: Send + Sync {
+    /// issues of password, normalized, for username: empty if acceptable
+    fn check(&self, password: &str, username: &str) -> Vec<PasswordIssue>;
+}
+
+/// policy built on estimate_strength
+#[derive(Debug, Clone)]
+pub struct DefaultPasswordPolicy {
+    /// minimum number of characters
+    pub min_length: usize,
+    /// minimum number of character classes
+    pub min_classes: usize,
+    /// refuse passwords in the list of common passwords
+    pub reject_common: bool,
+    /// refuse passwords containing the username
+    pub reject_username: bool,
+}
+
+impl Default for DefaultPasswordPolicy {
+    fn default() -> DefaultPasswordPolicy {
+        DefaultPasswordPolicy {
+            min_length: 8,
+            min_classes: 2,
+            reject_common: true,
+            reject_username: true,
+        }
+    }
+}
+
+impl PasswordPolicy for DefaultPasswordPolicy {
+    fn check(&self, password: &str, username: &str) -> Vec<PasswordIssue> {
+        let strength = strength_of(password);
+        let mut issues = vec![];
+
+        if strength.length < self.min_length {
+            issues.push(PasswordIssue::TooShort {
+                min: self.min_length,
+                found: strength.length,
+            });
+        }
+        if strength.classes < self.min_classes {
+            issues.push(PasswordIssue::TooFewCharacterClasses {
+                min: self.min_classes,
+                found: strength.classes,
+            });
+        }
+        if self.reject_common && strength.common {
+            issues.push(PasswordIssue::Common);
+        }
+        if self.reject_username
+            && !username.is_empty()
+            && password.to_lowercase().contains(&username.to_lowercase())
+        {
+            issues.push(PasswordIssue::ContainsUsername);
+        }
+        if is_repetitive(password) {
+            issues.push(PasswordIssue::Repetitive);
+        }
+        issues
+    }
+}
+
+/// issues of password for username under policy, once normalized
+/// as the client functions do: empty if acceptable
+pub fn password_issues(
+    policy: &dyn PasswordPolicy,
+    password: &str,
+    username: &str,
+) -> Vec<PasswordIssue> {
+    policy.check(&normalize_password(password), username)
+}
+
+/// policy run from now on by every client function starting or finishing
+/// a registration, and by the OpaqueClients without a policy of their own:
+/// None, the default, accepts every password
+pub fn set_password_policy(policy: Option<Arc<dyn PasswordPolicy>>) {
+    *PASSWORD_POLICY.write().unwrap() = policy;
+}
+
+/// policy set with set_password_policy, if any
+pub fn password_policy() -> Option<Arc<dyn PasswordPolicy>> {
+    PASSWORD_POLICY.read().unwrap().clone()
+}
+
+/// set_password_policy with a DefaultPasswordPolicy if enabled, None otherwise:
+/// the switch given to the bindings
+pub fn set_default_password_policy(enabled: bool) {
+    if enabled {
+        set_password_policy(Some(Arc::new(DefaultPasswordPolicy::default())));
+    } else {
+        set_password_policy(None);
+    }
+}
+
+/// same as password_issues, as an error for the issues found
+pub fn check_password(
+    policy: &dyn PasswordPolicy,
+    password: &str,
+    username: &str,
+) -> Result<(), OpaqueError> {
+    let issues = password_issues(policy, password, username);
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(OpaqueError::WeakPassword(issues))
+    }
+}
+
+/// same as check_password with the policy set with set_password_policy:
+/// every password is accepted if none is set
+pub fn check_password_policy(password: &str, username: &str) -> Result<(), OpaqueError> {
+    match password_policy() {
+        Some(policy) => check_password(policy.as_ref(), password, username),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,145 @@
+// Rust types of proto/opaque.proto, generated by prost-build in src/build.rs,
+// with conversions to and from the messages of the library
+
+use crate::opaque_envelope::{Message, MessageType};
+use crate::opaque_error::OpaqueError;
+
+mod generated {
+    // prost derives PartialEq alone
+    #![allow(clippy::derive_partial_eq_without_eq)]
+    include!(concat!(env!("OUT_DIR"), "/opaque.v1.rs"));
+}
+
+pub use generated::*;
+
+impl From<&OpaqueError> for ErrorResponse {
+    fn from(err: &OpaqueError) -> ErrorResponse {
+        let code = match err {
+            OpaqueError::Storage(_) => ErrorCode::Storage,
+            OpaqueError::UnknownSession | OpaqueError::Expired | OpaqueError::Replayed => {
+                ErrorCode::UnknownSession
+            }
+            // a failed login is reported without details
+            OpaqueError::Protocol(_) => {
+                return ErrorResponse {
+                    code: ErrorCode::LoginFailed as i32,
+                    message: "login failed".to_string(),
+                }
+            }
+            OpaqueError::NotAnEnvelope
+            | OpaqueError::UnknownMessageType(_)
+            | OpaqueError::MessageTypeMismatch { .. }
+            | OpaqueError::CiphersuiteMismatch { .. } => ErrorCode::InvalidMessage,
+            OpaqueError::AlreadyRegistered => ErrorCode::AlreadyRegistered,
+            _ => ErrorCode::Unspecified,
+        };
+        ErrorResponse {
+            code: code as i32,
+            message: err.to_string(),
+        }
+    }
+}
+
+// check that a message is of the type carried by a proto field
+fn expect(message: Message, expected: MessageType) -> Result<Vec<u8>, OpaqueError> {
+    if message.message_type != expected {
+        return Err(OpaqueError::MessageTypeMismatch {
+            expected,
+            found: message.message_type,
+        });
+    }
+    Ok(message.data)
+}
+
+impl RegistrationStartRequest {
+    pub fn new(username: String, message: Message) -> Result<Self, OpaqueError> {
+        Ok(RegistrationStartRequest {
+            username,
+            registration_request: expect(message, MessageType::RegistrationRequest)?,
+        })
+    }
+
+    pub fn message(&self) -> Message {
+        Message::new(
+            MessageType::RegistrationRequest,
+            self.registration_request.clone(),
+        )
+    }
+}
+
+impl RegistrationStartResponse {
+    pub fn new(message: Message) -> Result<Self, OpaqueError> {
+        Ok(RegistrationStartResponse {
+            registration_response: expect(message, MessageType::RegistrationResponse)?,
+        })
+    }
+
+    pub fn message(&self) -> Message {
+        Message::new(
+            MessageType::RegistrationResponse,
+            self.registration_response.clone(),
+        )
+    }
+}
+
+impl RegistrationFinishRequest {
+    pub fn new(username: String, message: Message) -> Result<Self, OpaqueError> {
+        Ok(RegistrationFinishRequest {
+            username,
+            registration_upload: expect(message, MessageType::RegistrationUpload)?,
+        })
+    }
+
+    pub fn message(&self) -> Message {
+        Message::new(
+            MessageType::RegistrationUpload,
+            self.registration_upload.clone(),
+        )
+    }
+}
+
+impl LoginStartRequest {
+    pub fn new(username: String, message: Message) -> Result<Self, OpaqueError> {
+        Ok(LoginStartRequest {
+            username,
+            credential_request: expect(message, MessageType::CredentialRequest)?,
+        })
+    }
+
+    pub fn message(&self) -> Message {
+        Message::new(MessageType::CredentialRequest, self.credential_request.clone())
+    }
+}
+
+impl LoginStartResponse {
+    pub fn new(session_id: String, message: Message) -> Result<Self, OpaqueError> {
+        Ok(LoginStartResponse {
+            session_id,
+            credential_response: expect(message, MessageType::CredentialResponse)?,
+        })
+    }
+
+    pub fn message(&self) -> Message {
+        Message::new(
+            MessageType::CredentialResponse,
+            self.credential_response.clone(),
+        )
+    }
+}
+
+impl LoginFinishRequest {
+    pub fn new(username: String, session_id: String, message: Message) -> Result<Self, OpaqueError> {
+        Ok(LoginFinishRequest {
+            username,
+            session_id,
+            credential_finalization: expect(message, MessageType::CredentialFinalization)?,
+        })
+    }
+
+    pub fn message(&self) -> Message {
+        Message::new(
+            MessageType::CredentialFinalization,
+            self.credential_finalization.clone(),
+        )
+    }
+}
@@ -0,0 +1,387 @@
+// Python bindings (module "opaque"): same flows as the C API in lib.rs,
+// with bytes in and out and an OpaqueError exception instead of empty results
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::opaque_client_facade::{
+    login_finish, login_start, registration_finish, registration_start, OpaqueClient,
+};
+use crate::opaque_config::ProtocolConfig;
+use crate::opaque_login_state::{check_login_state, LoginStatePolicy};
+use crate::opaque_password::{
+    self as password, normalize_password_with, password_normalization, PasswordNormalization,
+};
+use crate::opaque_password_policy::{
+    self as password_policy, password_issues, DefaultPasswordPolicy,
+};
+use crate::opaque_server_facade::{
+    credential_response, deserialize_setup, generate_setup, generate_setup_with_key,
+    password_file_from_upload, registration_response, session_key, setup_public_key,
+    OpaqueServer,
+};
+use crate::opaque_store::{CredentialStore, FileCredentialStore, MemoryCredentialStore};
+
+create_exception!(opaque, OpaqueError, PyException);
+
+fn py_err(err: crate::opaque_error::OpaqueError) -> PyErr {
+    OpaqueError::new_err(err.to_string())
+}
+
+fn bytes(py: Python, val: &[u8]) -> Py<PyBytes> {
+    PyBytes::new(py, val).into()
+}
+
+/// (registration request, client state): raises OpaqueError if the
+/// policy of set_default_password_policy refuses password
+#[pyfunction]
+fn client_registration_start(py: Python, password: &str) -> PyResult<(Py<PyBytes>, Py<PyBytes>)> {
+    let (request, state) = registration_start(password).map_err(py_err)?;
+    Ok((bytes(py, &request), bytes(py, &state)))
+}
+
+/// (registration response, server setup): without private key
+/// a new key pair is generated
+#[pyfunction]
+#[pyo3(signature = (username, registration_request, private_key = None))]
+fn server_registration_start(
+    py: Python,
+    username: &str,
+    registration_request: &[u8],
+    private_key: Option<&[u8]>,
+) -> PyResult<(Py<PyBytes>, Py<PyBytes>)> {
+    let setup = match private_key {
+        Some(key) => generate_setup_with_key(key).map_err(py_err)?,
+        None => generate_setup(),
+    };
+    let server_setup = deserialize_setup(&setup).map_err(py_err)?;
+    let response =
+        registration_response(&server_setup, username, registration_request).map_err(py_err)?;
+    Ok((bytes(py, &response), bytes(py, &setup)))
+}
+
+/// registration upload for the server
+#[pyfunction]
+fn client_registration_finish(
+    py: Python,
+    password: &str,
+    registration_response: &[u8],
+    state: &[u8],
+    username: &str,
+    servername: &str,
+) -> PyResult<Py<PyBytes>> {
+    let result = registration_finish(
+        password,
+        registration_response,
+        state,
+        username,
+        &ProtocolConfig::new(servername, &[]),
+    )
+    .map_err(py_err)?;
+    Ok(bytes(py, &result.upload))
+}
+
+/// password file to store for the user
+#[pyfunction]
+fn server_registration_finish(py: Python, registration_upload: &[u8]) -> PyResult<Py<PyBytes>> {
+    let password_file = password_file_from_upload(registration_upload).map_err(py_err)?;
+    Ok(bytes(py, &password_file))
+}
+
+/// (credential request, client state)
+#[pyfunction]
+fn client_login_start(py: Python, password: &str) -> PyResult<(Py<PyBytes>, Py<PyBytes>)> {
+    let (request, state) = login_start(password).map_err(py_err)?;
+    Ok((bytes(py, &request), bytes(py, &state)))
+}
+
+/// (credential response, server state)
+#[pyfunction]
+fn server_login_start(
+    py: Python,
+    username: &str,
+    password_file: &[u8],
+    credential_request: &[u8],
+    setup: &[u8],
+    servername: &str,
+    context: &str,
+) -> PyResult<(Py<PyBytes>, Py<PyBytes>)> {
+    let server_setup = deserialize_setup(setup).map_err(py_err)?;
+    let (response, state) = credential_response(
+        &server_setup,
+        &ProtocolConfig::new(servername, context.as_bytes()),
+        username,
+        Some(password_file),
+        credential_request,
+    )
+    .map_err(py_err)?;
+    Ok((bytes(py, &response), bytes(py, &state)))
+}
+
+/// (credential finalization, session key): raises if the password
+/// is wrong or the server isn't the expected one
+#[pyfunction]
+fn client_login_finish(
+    py: Python,
+    password: &str,
+    credential_response: &[u8],
+    state: &[u8],
+    username: &str,
+    servername: &str,
+    context: &str,
+) -> PyResult<(Py<PyBytes>, Py<PyBytes>)> {
+    let result = login_finish(
+        password,
+        credential_response,
+        state,
+        username,
+        &ProtocolConfig::new(servername, context.as_bytes()),
+    )
+    .map_err(py_err)?;
+    Ok((
+        bytes(py, &result.credential_finalization),
+        bytes(py, &result.session_key),
+    ))
+}
+
+/// session key: raises if the login failed or the state is too old
+#[pyfunction]
+fn server_login_finish(
+    py: Python,
+    credential_finalization: &[u8],
+    state: &[u8],
+) -> PyResult<Py<PyBytes>> {
+    let login_state = check_login_state(&LoginStatePolicy::default(), state).map_err(py_err)?;
+    let key = session_key(login_state, credential_finalization).map_err(py_err)?;
+    Ok(bytes(py, &key))
+}
+
+/// serialized public key of a server setup
+#[pyfunction]
+fn server_public_key(py: Python, setup: &[u8]) -> PyResult<Py<PyBytes>> {
+    let key = setup_public_key(setup).map_err(py_err)?;
+    Ok(bytes(py, &key))
+}
+
+/// password as normalized by the client functions with normalization
+/// ("none", "nfc" or "nfkc"), the one in use when not given
+#[pyfunction]
+#[pyo3(signature = (password, normalization = None))]
+fn normalize_password(password: &str, normalization: Option<&str>) -> PyResult<String> {
+    let val = match normalization {
+        Some(name) => parse_normalization(name)?,
+        None => password_normalization(),
+    };
+    Ok(normalize_password_with(password, val))
+}
+
+/// normalization ("none", "nfc" or "nfkc") applied by every client function
+/// from now on: it must be the same at registration and login
+#[pyfunction]
+fn set_password_normalization(normalization: &str) -> PyResult<()> {
+    password::set_password_normalization(parse_normalization(normalization)?);
+    Ok(())
+}
+
+fn parse_normalization(name: &str) -> PyResult<PasswordNormalization> {
+    PasswordNormalization::from_name(name)
+        .ok_or_else(|| OpaqueError::new_err(format!("unknown password normalization {}", name)))
+}
+
+/// [(code, message)] of the issues of password for username under the
+/// default password policy, empty if acceptable: run it before registering,
+/// the server can't tell a weak password
+#[pyfunction]
+#[pyo3(signature = (password, username = ""))]
+fn check_password(password: &str, username: &str) -> Vec<(&'static str, String)> {
+    password_issues(&DefaultPasswordPolicy::default(), password, username)
+        .iter()
+        .map(|issue| (issue.code(), issue.to_string()))
+        .collect()
+}
+
+/// make client_registration_start, client_registration_finish and every
+/// Client without a policy of its own refuse passwords with issues under the
+/// default password policy (see check_password); False accepts every password
+#[pyfunction]
+#[pyo3(signature = (enabled = true))]
+fn set_default_password_policy(enabled: bool) {
+    password_policy::set_default_password_policy(enabled);
+}
+
+/// OpaqueClient: keeps password and state between begin and complete
+#[pyclass(name = "Client")]
+struct PyClient {
+    inner: OpaqueClient,
+}
+
+#[pymethods]
+impl PyClient {
+    #[new]
+    fn new(username: String, servername: String, context: String) -> PyClient {
+        PyClient {
+            inner: OpaqueClient::new(username, servername, context),
+        }
+    }
+
+    /// refuse registrations and password changes to passwords with
+    /// issues under the default policy (see check_password)
+    fn set_default_password_policy(&mut self) {
+        self.inner
+            .set_password_policy(Box::new(DefaultPasswordPolicy::default()));
+    }
+
+    fn begin_registration(&mut self, py: Python, password: &str) -> PyResult<Py<PyBytes>> {
+        let request = self.inner.begin_registration(password).map_err(py_err)?;
+        Ok(bytes(py, &request))
+    }
+
+    /// (registration upload, export key, server public key)
+    fn complete_registration(
+        &mut self,
+        py: Python,
+        registration_response: &[u8],
+    ) -> PyResult<(Py<PyBytes>, Py<PyBytes>, Py<PyBytes>)> {
+        let result = self
+            .inner
+            .complete_registration(registration_response)
+            .map_err(py_err)?;
+        Ok((
+            bytes(py, &result.upload),
+            bytes(py, &result.export_key),
+            bytes(py, &result.server_public_key),
+        ))
+    }
+
+    fn begin_login(&mut self, py: Python, password: &str) -> PyResult<Py<PyBytes>> {
+        let request = self.inner.begin_login(password).map_err(py_err)?;
+        Ok(bytes(py, &request))
+    }
+
+    /// (credential finalization, session key, export key, server public key)
+    fn complete_login(
+        &mut self,
+        py: Python,
+        credential_response: &[u8],
+    ) -> PyResult<(Py<PyBytes>, Py<PyBytes>, Py<PyBytes>, Py<PyBytes>)> {
+        let result = self
+            .inner
+            .complete_login(credential_response)
+            .map_err(py_err)?;
+        Ok((
+            bytes(py, &result.credential_finalization),
+            bytes(py, &result.session_key),
+            bytes(py, &result.export_key),
+            bytes(py, &result.server_public_key),
+        ))
+    }
+}
+
+/// OpaqueServer: in memory, or saved in the file at store_path;
+/// without setup the one in the store is used (or generated)
+#[pyclass(name = "Server")]
+struct PyServer {
+    inner: OpaqueServer,
+}
+
+#[pymethods]
+impl PyServer {
+    #[new]
+    #[pyo3(signature = (servername, context, setup = None, store_path = None))]
+    fn new(
+        servername: String,
+        context: String,
+        setup: Option<&[u8]>,
+        store_path: Option<&str>,
+    ) -> PyResult<PyServer> {
+        let store: Box<dyn CredentialStore> = match store_path {
+            Some(path) => Box::new(FileCredentialStore::open(path).map_err(py_err)?),
+            None => Box::new(MemoryCredentialStore::new()),
+        };
+        let inner = match setup {
+            Some(val) => OpaqueServer::new(val, servername, context, store),
+            None => OpaqueServer::load_or_generate(servername, context, store),
+        }
+        .map_err(py_err)?;
+        Ok(PyServer { inner })
+    }
+
+    fn setup(&self, py: Python) -> Py<PyBytes> {
+        bytes(py, &self.inner.setup())
+    }
+
+    fn public_key(&self, py: Python) -> Py<PyBytes> {
+        bytes(py, &self.inner.public_key())
+    }
+
+    fn handle_registration_request(
+        &self,
+        py: Python,
+        username: &str,
+        registration_request: &[u8],
+    ) -> PyResult<Py<PyBytes>> {
+        let response = self
+            .inner
+            .handle_registration_request(username, registration_request)
+            .map_err(py_err)?;
+        Ok(bytes(py, &response))
+    }
+
+    fn handle_registration_upload(&self, username: &str, upload: &[u8]) -> PyResult<()> {
+        self.inner
+            .handle_registration_upload(username, upload)
+            .map_err(py_err)
+    }
+
+    fn handle_credential_request(
+        &self,
+        py: Python,
+        username: &str,
+        session_id: &str,
+        credential_request: &[u8],
+    ) -> PyResult<Py<PyBytes>> {
+        let response = self
+            .inner
+            .handle_credential_request(username, session_id, credential_request)
+            .map_err(py_err)?;
+        Ok(bytes(py, &response))
+    }
+
+    /// session key
+    fn handle_credential_finalization(
+        &self,
+        py: Python,
+        username: &str,
+        session_id: &str,
+        credential_finalization: &[u8],
+    ) -> PyResult<Py<PyBytes>> {
+        let key = self
+            .inner
+            .handle_credential_finalization(username, session_id, credential_finalization)
+            .map_err(py_err)?;
+        Ok(bytes(py, &key))
+    }
+}
+
+#[pymodule]
+fn opaque(py: Python, m: &PyModule) -> PyResult<()> {
+    m.add("OpaqueError", py.get_type::<OpaqueError>())?;
+    m.add_function(wrap_pyfunction!(client_registration_start, m)?)?;
+    m.add_function(wrap_pyfunction!(server_registration_start, m)?)?;
+    m.add_function(wrap_pyfunction!(client_registration_finish, m)?)?;
+    m.add_function(wrap_pyfunction!(server_registration_finish, m)?)?;
+    m.add_function(wrap_pyfunction!(client_login_start, m)?)?;
+    m.add_function(wrap_pyfunction!(server_login_start, m)?)?;
+    m.add_function(wrap_pyfunction!(client_login_finish, m)?)?;
+    m.add_function(wrap_pyfunction!(server_login_finish, m)?)?;
+    m.add_function(wrap_pyfunction!(server_public_key, m)?)?;
+    m.add_function(wrap_pyfunction!(normalize_password, m)?)?;
+    m.add_function(wrap_pyfunction!(set_password_normalization, m)?)?;
+    m.add_function(wrap_pyfunction!(check_password, m)?)?;
+    m.add_function(wrap_pyfunction!(set_default_password_policy, m)?)?;
+    m.add_class::<PyClient>()?;
+    m.add_class::<PyServer>()?;
+    Ok(())
+}
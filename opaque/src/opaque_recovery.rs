@@ -0,0 +1,338 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use crate::opaque_error::OpaqueError;
+
+// The export key changes with the password, so what the client protects
+// should be encrypted under the recovery secret instead: it is derived from
+// the export key at registration and then survives password changes, wrapped
+// under each recovery code (for the server, which can't open it) and under
+// the export key of every later password.
+//
+// For each recovery code the server keeps a record made of the SHA-256 of
+// the code verifier and the secret wrapped under the code: a client proves
+// it has a code by sending the verifier, gets the wrapped secret back and
+// registers a new password with the usual registration steps.
+//
+// OpaqueServer keeps records and wrapped secret as the RecoveryData of the
+// user: a password change or a recovery writes it together with the new
+// password file, so a code is never used twice and the wrapped secret
+// always opens with the export key of the current password.
+
+pub const RECOVERY_VERSION: u8 = 1;
+pub const DEFAULT_RECOVERY_CODE_COUNT: usize = 8;
+
+const SECRET_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+const TAG_LEN: usize = 16;
+const WRAPPED_LEN: usize = NONCE_LEN + SECRET_LEN + TAG_LEN;
+// verifier hash (32) + wrapped secret
+const RECORD_LEN: usize = 32 + WRAPPED_LEN;
+
+// 20 characters of 5 bits each: 100 bits, written as 4 groups of 5
+const CODE_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const CODE_CHARS: usize = 20;
+const CODE_GROUP: usize = 5;
+
+const SECRET_INFO: &[u8] = b"OPAQUE recovery secret";
+const CODE_KEY_INFO: &[u8] = b"OPAQUE recovery code key";
+const CODE_VERIFIER_INFO: &[u8] = b"OPAQUE recovery code verifier";
+const EXPORT_KEY_WRAP_INFO: &[u8] = b"OPAQUE recovery export key wrap";
+
+/// result of generate_recovery_codes
+pub struct RecoveryCodes {
+    /// codes to show to the user once, never sent to the server
+    pub codes: Vec<String>,
+    /// records for the server, one per code
+    pub records: Vec<u8>,
+}
+
+/// secret derived from the export key of the first registration
+pub fn recovery_secret(export_key: &[u8]) -> Vec<u8> {
+    derive(export_key, &[], SECRET_INFO)
+}
+
+/// what the server keeps next to the password file of a user: the records
+/// of the recovery codes left and, once the password was changed or
+/// recovered, the secret wrapped under the export key of the current one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryData {
+    pub records: Vec<u8>,
+    pub wrapped_secret: Option<Vec<u8>>,
+}
+
+impl RecoveryData {
+    /// data of a user without recovery codes
+    pub fn empty() -> RecoveryData {
+        RecoveryData {
+            records: vec![RECOVERY_VERSION],
+            wrapped_secret: None,
+        }
+    }
+
+    /// version, length-prefixed records, then the wrapped secret if any
+    pub fn serialize(&self) -> Vec<u8> {
+        let wrapped = self.wrapped_secret.as_deref().unwrap_or(&[]);
+        let mut data = Vec::with_capacity(1 + 4 + self.records.len() + wrapped.len());
+        data.push(RECOVERY_VERSION);
+        data.extend_from_slice(&(self.records.len() as u32).to_be_bytes());
+        data.extend_from_slice(&self.records);
+        data.extend_from_slice(wrapped);
+        data
+    }
+
+    pub fn deserialize(data: &[u8]) -> Result<RecoveryData, OpaqueError> {
+        if data.len() < 5 {
+            return Err(OpaqueError::MalformedRecord);
+        }
+        if data[0] != RECOVERY_VERSION {
+            return Err(OpaqueError::UnsupportedVersion(data[0]));
+        }
+        let len = u32::from_be_bytes([data[1], data[2], data[3], data[4]]) as usize;
+        if data.len() < 5 + len {
+            return Err(OpaqueError::MalformedRecord);
+        }
+        let records = data[5..5 + len].to_owned();
+        parse_records(&records)?;
+        let wrapped_secret = match &data[5 + len..] {
+            [] => None,
+            val => {
+                check_wrapped_secret(val)?;
+                Some(val.to_owned())
+            }
+        };
+        Ok(RecoveryData {
+            records,
+            wrapped_secret,
+        })
+    }
+}
+
+/// first message of a recovery, for the server
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecoveryRequest {
+    /// proves knowledge of the recovery code
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub verifier: Vec<u8>,
+    /// registration of the new password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub registration_request: Vec<u8>,
+}
+
+/// server answer to a RecoveryRequest
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecoveryResponse {
+    /// secret wrapped under the recovery code
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub wrapped_secret: Vec<u8>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub registration_response: Vec<u8>,
+}
+
+/// last message of a recovery, for the server
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecoveryFinalization {
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub verifier: Vec<u8>,
+    /// registration upload of the new password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub upload: Vec<u8>,
+    /// secret wrapped under the export key of the new password
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub wrapped_secret: Vec<u8>,
+}
+
+/// result of complete_recovery
+pub struct RecoveryResult {
+    pub finalization: RecoveryFinalization,
+    /// export key of the new password
+    pub export_key: Vec<u8>,
+    /// recovery secret, the same one as before the recovery
+    pub secret: Vec<u8>,
+}
+
+fn derive(ikm: &[u8], salt: &[u8], info: &[u8]) -> Vec<u8> {
+    let mut out = vec![0u8; SECRET_LEN];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut out)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    out
+}
+
+// code as typed by the user: case, spaces and dashes don't matter
+fn normalize_code(code: &str) -> Vec<u8> {
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .map(|c| c.to_ascii_uppercase() as u8)
+        .collect()
+}
+
+fn new_code() -> String {
+    let mut random = [0u8; CODE_CHARS];
+    OsRng.fill_bytes(&mut random);
+    let mut code = String::with_capacity(CODE_CHARS + CODE_CHARS / CODE_GROUP);
+    for (i, byte) in random.iter().enumerate() {
+        if i > 0 && i % CODE_GROUP == 0 {
+            code.push('-');
+        }
+        code.push(CODE_ALPHABET[(byte & 31) as usize] as char);
+    }
+    code
+}
+
+fn wrap(key: &[u8], aad: &[u8], secret: &[u8]) -> Vec<u8> {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: secret, aad })
+        .expect("encryption of a short secret can't fail");
+
+    let mut wrapped = Vec::with_capacity(WRAPPED_LEN);
+    wrapped.extend_from_slice(&nonce);
+    wrapped.extend_from_slice(&ciphertext);
+    wrapped
+}
+
+// a wrapped secret sent by a client, or read from the store, has the right length
+pub(crate) fn check_wrapped_secret(wrapped: &[u8]) -> Result<(), OpaqueError> {
+    if wrapped.len() != WRAPPED_LEN {
+        return Err(OpaqueError::MalformedRecord);
+    }
+    Ok(())
+}
+
+fn unwrap(key: &[u8], aad: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+    check_wrapped_secret(wrapped)?;
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(
+            XNonce::from_slice(&wrapped[..NONCE_LEN]),
+            Payload {
+                msg: &wrapped[NONCE_LEN..],
+                aad,
+            },
+        )
+        .map_err(|_| OpaqueError::InvalidRecoveryCode)
+}
+
+/// value sent to the server to prove knowledge of a recovery code
+pub fn recovery_verifier(code: &str, username: &str) -> Vec<u8> {
+    derive(&normalize_code(code), username.as_bytes(), CODE_VERIFIER_INFO)
+}
+
+/// new recovery codes for username, each one wrapping secret
+/// (result of recovery_secret, or of unwrap_recovery_secret after a recovery)
+pub fn generate_recovery_codes(
+    secret: &[u8],
+    username: &str,
+    count: usize,
+) -> RecoveryCodes {
+    let mut codes = Vec::with_capacity(count);
+    let mut records = Vec::with_capacity(1 + count * RECORD_LEN);
+    records.push(RECOVERY_VERSION);
+
+    for _ in 0..count {
+        let code = new_code();
+        let normalized = normalize_code(&code);
+        let key = derive(&normalized, username.as_bytes(), CODE_KEY_INFO);
+        let verifier = derive(&normalized, username.as_bytes(), CODE_VERIFIER_INFO);
+
+        records.extend_from_slice(&Sha256::digest(&verifier));
+        records.extend_from_slice(&wrap(&key, username.as_bytes(), secret));
+        codes.push(code);
+    }
+    RecoveryCodes { codes, records }
+}
+
+fn parse_records(records: &[u8]) -> Result<Vec<&[u8]>, OpaqueError> {
+    if records.is_empty() {
+        return Err(OpaqueError::MalformedRecord);
+    }
+    if records[0] != RECOVERY_VERSION {
+        return Err(OpaqueError::UnsupportedVersion(records[0]));
+    }
+    if (records.len() - 1) % RECORD_LEN != 0 {
+        return Err(OpaqueError::MalformedRecord);
+    }
+    Ok(records[1..].chunks(RECORD_LEN).collect())
+}
+
+/// number of recovery codes still usable in records
+pub fn recovery_codes_left(records: &[u8]) -> Result<usize, OpaqueError> {
+    Ok(parse_records(records)?.len())
+}
+
+/// server side of a recovery, when the new password is registered: find the
+/// record of verifier and remove it, since every code is single use; returns
+/// the wrapped secret for the client and the records to store in place of the
+/// old ones
+pub fn take_recovery_record(
+    records: &[u8],
+    verifier: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), OpaqueError> {
+    let hash = Sha256::digest(verifier);
+    let mut found = None;
+    let mut remaining = vec![RECOVERY_VERSION];
+
+    // every record is compared, so timing doesn't tell which one matched
+    for record in parse_records(records)? {
+        let diff = record[..32]
+            .iter()
+            .zip(hash.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        if diff == 0 && found.is_none() {
+            found = Some(record[32..].to_owned());
+        } else {
+            remaining.extend_from_slice(record);
+        }
+    }
+
+    match found {
+        Some(wrapped) => Ok((wrapped, remaining)),
+        None => Err(OpaqueError::InvalidRecoveryCode),
+    }
+}
+
+/// server side of a recovery, before the new password is registered:
+/// the wrapped secret of verifier, leaving records as they are
+pub fn find_recovery_record(records: &[u8], verifier: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+    take_recovery_record(records, verifier).map(|(wrapped, _)| wrapped)
+}
+
+/// client side of a recovery: the secret inside the wrapped secret
+/// returned by find_recovery_record
+pub fn unwrap_recovery_secret(
+    code: &str,
+    username: &str,
+    wrapped: &[u8],
+) -> Result<Vec<u8>, OpaqueError> {
+    let key = derive(&normalize_code(code), username.as_bytes(), CODE_KEY_INFO);
+    unwrap(&key, username.as_bytes(), wrapped)
+}
+
+/// secret wrapped under the export key of a new password,
+/// to be kept by the server next to the new password file
+pub fn wrap_secret_with_export_key(export_key: &[u8], secret: &[u8]) -> Vec<u8> {
+    let key = derive(export_key, &[], EXPORT_KEY_WRAP_INFO);
+    wrap(&key, &[], secret)
+}
+
+/// recovery secret after a login: unwrapped with the export key
+/// if the password was set by a recovery, derived from it otherwise
+pub fn secret_from_export_key(
+    export_key: &[u8],
+    wrapped: Option<&[u8]>,
+) -> Result<Vec<u8>, OpaqueError> {
+    match wrapped {
+        Some(val) => {
+            let key = derive(export_key, &[], EXPORT_KEY_WRAP_INFO);
+            unwrap(&key, &[], val).map_err(|_| OpaqueError::DecryptionFailed)
+        }
+        None => Ok(recovery_secret(export_key)),
+    }
+}
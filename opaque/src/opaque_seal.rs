@@ -0,0 +1,174 @@
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use zeroize::Zeroize;
+
+use crate::opaque_error::OpaqueError;
+
+// format version written in front of every sealed record:
+// bump it whenever the layout below changes
+pub const SEAL_VERSION: u8 = 1;
+
+const KEK_LEN: usize = 32;
+const NONCE_LEN: usize = 24;
+// version (1) + record type (1) + kek id (4)
+const HEADER_LEN: usize = 6;
+
+/// kind of data held by a sealed record, so that a sealed
+/// password file can't be unsealed as a server setup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RecordType {
+    PasswordFile = 1,
+    ServerSetup = 2,
+    LoginState = 3,
+}
+
+impl RecordType {
+    fn from_byte(byte: u8) -> Result<RecordType, OpaqueError> {
+        match byte {
+            1 => Ok(RecordType::PasswordFile),
+            2 => Ok(RecordType::ServerSetup),
+            3 => Ok(RecordType::LoginState),
+            _ => Err(OpaqueError::MalformedRecord),
+        }
+    }
+}
+
+/// key-encryption key: the id is stored in clear inside
+/// every sealed record, so we know which key to use
+/// when the KEK is rotated
+pub struct Kek {
+    pub id: u32,
+    key: [u8; KEK_LEN],
+}
+
+impl Kek {
+    pub fn new(id: u32, key: &[u8]) -> Result<Kek, OpaqueError> {
+        if key.len() != KEK_LEN {
+            return Err(OpaqueError::InvalidKek);
+        }
+        let mut kek = Kek {
+            id,
+            key: [0u8; KEK_LEN],
+        };
+        kek.key.copy_from_slice(key);
+        Ok(kek)
+    }
+}
+
+impl Drop for Kek {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// header of a sealed record, readable without the KEK
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SealedHeader {
+    pub version: u8,
+    pub record_type: RecordType,
+    pub kek_id: u32,
+}
+
+/// read the header of a sealed record, useful to pick
+/// the right KEK before calling unseal
+pub fn sealed_header(sealed: &[u8]) -> Result<SealedHeader, OpaqueError> {
+    if sealed.len() < HEADER_LEN + NONCE_LEN {
+        return Err(OpaqueError::MalformedRecord);
+    }
+    if sealed[0] != SEAL_VERSION {
+        return Err(OpaqueError::UnsupportedVersion(sealed[0]));
+    }
+    Ok(SealedHeader {
+        version: sealed[0],
+        record_type: RecordType::from_byte(sealed[1])?,
+        kek_id: u32::from_be_bytes([sealed[2], sealed[3], sealed[4], sealed[5]]),
+    })
+}
+
+// associated data: the whole header plus the length-prefixed username,
+// so a record can't be moved to another user or another record type
+fn associated_data(header: &[u8], username: &str) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(header.len() + 4 + username.len());
+    aad.extend_from_slice(header);
+    aad.extend_from_slice(&(username.len() as u32).to_be_bytes());
+    aad.extend_from_slice(username.as_bytes());
+    aad
+}
+
+/// encrypt and authenticate a password file or a server setup:
+/// username is the owner of the password file, or the servername
+/// in case of a server setup
+pub fn seal(kek: &Kek, record_type: RecordType, username: &str, plaintext: &[u8]) -> Vec<u8> {
+    let mut header = [0u8; HEADER_LEN];
+    header[0] = SEAL_VERSION;
+    header[1] = record_type as u8;
+    header[2..].copy_from_slice(&kek.id.to_be_bytes());
+
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&kek.key));
+    // encryption with a valid key and nonce can't fail
+    let ciphertext = cipher
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &associated_data(&header, username),
+            },
+        )
+        .unwrap();
+
+    let mut sealed = Vec::with_capacity(HEADER_LEN + NONCE_LEN + ciphertext.len());
+    sealed.extend_from_slice(&header);
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(&ciphertext);
+    sealed
+}
+
+/// decrypt a record previously sealed with the same KEK,
+/// record type and username
+pub fn unseal(
+    kek: &Kek,
+    record_type: RecordType,
+    username: &str,
+    sealed: &[u8],
+) -> Result<Vec<u8>, OpaqueError> {
+    let header = sealed_header(sealed)?;
+    if header.record_type != record_type {
+        return Err(OpaqueError::RecordTypeMismatch);
+    }
+    if header.kek_id != kek.id {
+        return Err(OpaqueError::KekMismatch {
+            expected: kek.id,
+            found: header.kek_id,
+        });
+    }
+
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(&kek.key));
+    cipher
+        .decrypt(
+            XNonce::from_slice(&sealed[HEADER_LEN..HEADER_LEN + NONCE_LEN]),
+            Payload {
+                msg: &sealed[HEADER_LEN + NONCE_LEN..],
+                aad: &associated_data(&sealed[..HEADER_LEN], username),
+            },
+        )
+        .map_err(|_| OpaqueError::DecryptionFailed)
+}
+
+/// unseal a record with the old KEK and seal it again with the new one
+pub fn reseal(
+    old_kek: &Kek,
+    new_kek: &Kek,
+    record_type: RecordType,
+    username: &str,
+    sealed: &[u8],
+) -> Result<Vec<u8>, OpaqueError> {
+    let plaintext = unseal(old_kek, record_type, username, sealed)?;
+    Ok(seal(new_kek, record_type, username, &plaintext))
+}
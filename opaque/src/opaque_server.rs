@@ -8,10 +8,13 @@ use opaque_ke::{
 };
 use argon2::Argon2;
 
+use crate::opaque_config::{unbind_upload, ProtocolConfig};
+use crate::opaque_login_state::{check_login_state, wrap_login_state, LoginStatePolicy};
+
 // The ciphersuite trait allows to specify the underlying primitives that will
 // be used in the OPAQUE protocol
 #[allow(dead_code)]
-struct DefaultCipherSuite;
+pub(crate) struct DefaultCipherSuite;
 
 #[cfg(feature = "ristretto255")]
 impl CipherSuite for DefaultCipherSuite {
@@ -100,8 +103,10 @@ pub fn server_registration_start(
 }
 
 pub fn server_registration_finish(message_bytes: &[u8]) -> Vec<u8> {
+    // no configuration to check the identities of a bound upload against
     let password_file = ServerRegistration::finish(
-        RegistrationUpload::<DefaultCipherSuite>::deserialize(message_bytes).unwrap(),
+        RegistrationUpload::<DefaultCipherSuite>::deserialize(unbind_upload(message_bytes).unwrap())
+            .unwrap(),
     );
     let pass = password_file.serialize();
     pass.as_slice().to_owned()
@@ -115,6 +120,18 @@ pub fn server_login_start(
     servername: String,
     context: String,
 ) -> ServerResponseWithState {
+    // password files bound to their identities are refused if these differ
+    let config = ProtocolConfig::new(&servername, context.as_bytes());
+    let password_file_bytes = match config.check_password_file(&username, password_file_bytes) {
+        Ok(val) => val,
+        Err(err) => {
+            println!("RUST - LOG: Server refused password file: {}", err);
+            return ServerResponseWithState {
+                response: vec![],
+                state: vec![],
+            };
+        }
+    };
     let password_file =
         ServerRegistration::<DefaultCipherSuite>::deserialize(password_file_bytes).unwrap();
 
@@ -137,22 +154,33 @@ pub fn server_login_start(
     )
     .unwrap();
 
-    // serialize login server state, so I can use it later
+    // serialize login server state, so I can use it later:
+    // creation time and id are added to allow expiry and replay checks
     let login_start_result_state = login_start_result.state.serialize();
     let credential_response_bytes = login_start_result.message.serialize();
 
     ServerResponseWithState {
         response: credential_response_bytes.as_slice().to_owned(),
-        state: login_start_result_state.as_slice().to_owned(),
+        state: wrap_login_state(login_start_result_state.as_slice()),
     }
 }
 
 pub fn server_login_finish(
     credential_finalization_bytes: &[u8],
     login_start_result: &[u8],
+    policy: &LoginStatePolicy,
 ) -> bool {
+    // refuse states too old or already used
+    let login_state = match check_login_state(policy, login_start_result) {
+        Ok(val) => val,
+        Err(err) => {
+            println!("RUST - LOG: Server refused login state: {}", err);
+            return false
+        }
+    };
+
     // retrieve server login state to allow finish procedure correctly
-    let state = ServerLogin::<DefaultCipherSuite>::deserialize(login_start_result).unwrap();
+    let state = ServerLogin::<DefaultCipherSuite>::deserialize(login_state).unwrap();
 
     let login_finish_result = state
         .finish(CredentialFinalization::deserialize(credential_finalization_bytes).unwrap());
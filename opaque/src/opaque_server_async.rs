@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::task;
+
+use crate::opaque_config::ProtocolConfig;
+use crate::opaque_error::OpaqueError;
+use crate::opaque_server_facade::{
+    bound_password_file_from_upload, credential_response, deserialize_setup, generate_setup,
+    public_key, registration_response, session_key, LoginSessions, Setup,
+};
+use crate::opaque_store::CredentialStore;
+
+/// same as CredentialStore for password files and server setup,
+/// for stores reached through async I/O
+#[async_trait]
+pub trait AsyncCredentialStore: Send + Sync {
+    async fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError>;
+
+    async fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError>;
+
+    async fn get_password_file(&self, credential_id: &[u8])
+        -> Result<Option<Vec<u8>>, OpaqueError>;
+
+    async fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError>;
+
+    async fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError>;
+
+    async fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError>;
+}
+
+/// any CredentialStore used as an AsyncCredentialStore: every call
+/// runs on the blocking pool, so file or SQLite I/O doesn't stall the reactor
+pub struct BlockingStore<S: CredentialStore + 'static> {
+    store: Arc<S>,
+}
+
+impl<S: CredentialStore + 'static> BlockingStore<S> {
+    pub fn new(store: S) -> BlockingStore<S> {
+        BlockingStore {
+            store: Arc::new(store),
+        }
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T, OpaqueError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&S) -> Result<T, OpaqueError> + Send + 'static,
+    {
+        let store = self.store.clone();
+        task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|err| OpaqueError::Storage(err.to_string()))?
+    }
+}
+
+#[async_trait]
+impl<S: CredentialStore + 'static> AsyncCredentialStore for BlockingStore<S> {
+    async fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let id = credential_id.to_owned();
+        let file = password_file.to_owned();
+        self.run(move |s| s.put_password_file(&id, &file)).await
+    }
+
+    async fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let id = credential_id.to_owned();
+        let file = password_file.to_owned();
+        self.run(move |s| s.create_password_file(&id, &file)).await
+    }
+
+    async fn get_password_file(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let id = credential_id.to_owned();
+        self.run(move |s| s.get_password_file(&id)).await
+    }
+
+    async fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError> {
+        let id = credential_id.to_owned();
+        self.run(move |s| s.delete_password_file(&id)).await
+    }
+
+    async fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError> {
+        let setup = setup.to_owned();
+        self.run(move |s| s.put_server_setup(&setup)).await
+    }
+
+    async fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError> {
+        self.run(|s| s.get_server_setup()).await
+    }
+}
+
+// run a protocol step on the blocking pool: a task that panicked
+// or was cancelled is an Internal error, not a failed login
+async fn offload<T, F>(f: F) -> Result<T, OpaqueError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, OpaqueError> + Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .map_err(|err| OpaqueError::Internal(err.to_string()))?
+}
+
+/// async counterpart of OpaqueServer for tokio services: storage is awaited
+/// and every protocol step runs on the blocking pool, off the reactor threads
+pub struct AsyncOpaqueServer {
+    setup: Arc<Setup>,
+    config: Arc<ProtocolConfig>,
+    store: Arc<dyn AsyncCredentialStore>,
+    sessions: LoginSessions,
+}
+
+impl AsyncOpaqueServer {
+    /// server using the serialized setup returned by server registration start
+    pub fn new(
+        setup: &[u8],
+        servername: String,
+        context: String,
+        store: Arc<dyn AsyncCredentialStore>,
+    ) -> Result<AsyncOpaqueServer, OpaqueError> {
+        AsyncOpaqueServer::with_config(
+            setup,
+            ProtocolConfig::new(&servername, context.as_bytes()),
+            store,
+        )
+    }
+
+    /// same as new, with identities and context given as a configuration
+    pub fn with_config(
+        setup: &[u8],
+        config: ProtocolConfig,
+        store: Arc<dyn AsyncCredentialStore>,
+    ) -> Result<AsyncOpaqueServer, OpaqueError> {
+        Ok(AsyncOpaqueServer {
+            setup: Arc::new(deserialize_setup(setup)?),
+            config: Arc::new(config),
+            store,
+            sessions: LoginSessions::default(),
+        })
+    }
+
+    /// server using the setup saved in store,
+    /// generating and saving a new one if missing
+    pub async fn load_or_generate(
+        servername: String,
+        context: String,
+        store: Arc<dyn AsyncCredentialStore>,
+    ) -> Result<AsyncOpaqueServer, OpaqueError> {
+        let setup = match store.get_server_setup().await? {
+            Some(val) => val,
+            None => {
+                let new_setup = offload(|| Ok(generate_setup())).await?;
+                store.put_server_setup(&new_setup).await?;
+                new_setup
+            }
+        };
+        AsyncOpaqueServer::new(&setup, servername, context, store)
+    }
+
+    /// serialized public key of the server
+    pub fn public_key(&self) -> Vec<u8> {
+        public_key(&self.setup)
+    }
+
+    /// same as OpaqueServer::handle_registration_request
+    pub async fn handle_registration_request(
+        &self,
+        username: &str,
+        registration_request: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        if self.store.get_password_file(username.as_bytes()).await?.is_some() {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        let setup = self.setup.clone();
+        let user = username.to_string();
+        let request = registration_request.to_owned();
+        offload(move || registration_response(&setup, &user, &request)).await
+    }
+
+    /// same as OpaqueServer::handle_registration_upload
+    pub async fn handle_registration_upload(
+        &self,
+        username: &str,
+        upload: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let message = upload.to_owned();
+        let config = self.config.clone();
+        let user = username.to_string();
+        let password_file =
+            offload(move || bound_password_file_from_upload(&config, &user, &message)).await?;
+        self.store
+            .create_password_file(username.as_bytes(), &password_file)
+            .await
+    }
+
+    /// same as OpaqueServer::handle_credential_request
+    pub async fn handle_credential_request(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_request: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        self.login_start(username, session_id, credential_request, self.config.clone())
+            .await
+    }
+
+    /// same as OpaqueServer::handle_credential_request_with_identity
+    pub async fn handle_credential_request_with_identity(
+        &self,
+        credential_id: &str,
+        identity: &str,
+        session_id: &str,
+        credential_request: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let config = Arc::new(self.config.for_identity(identity));
+        self.login_start(credential_id, session_id, credential_request, config)
+            .await
+    }
+
+    async fn login_start(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_request: &[u8],
+        config: Arc<ProtocolConfig>,
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let password_file = self.store.get_password_file(username.as_bytes()).await?;
+
+        let setup = self.setup.clone();
+        let user = username.to_string();
+        let request = credential_request.to_owned();
+        let (response, state) = offload(move || {
+            credential_response(
+                &setup,
+                &config,
+                &user,
+                password_file.as_deref(),
+                &request,
+            )
+        })
+        .await?;
+
+        self.sessions.insert(username, session_id, state);
+        Ok(response)
+    }
+
+    /// same as OpaqueServer::handle_credential_finalization
+    pub async fn handle_credential_finalization(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_finalization: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let state = self.sessions.take(username, session_id)?;
+        let finalization = credential_finalization.to_owned();
+        offload(move || session_key(&state, &finalization)).await
+    }
+}
@@ -0,0 +1,628 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use opaque_ke::keypair::KeyPair;
+use opaque_ke::rand::rngs::OsRng;
+use opaque_ke::{
+    CredentialFinalization, CredentialRequest, RegistrationRequest, RegistrationUpload,
+    ServerLogin, ServerLoginStartParameters, ServerRegistration, ServerSetup,
+};
+
+use crate::opaque_config::{unbind_password_file, unbind_upload, ProtocolConfig};
+use crate::opaque_error::OpaqueError;
+use crate::opaque_login_state::{
+    check_login_state, now_secs, unwrap_login_state, wrap_login_state, LoginStateHeader,
+    LoginStatePolicy, DEFAULT_LOGIN_STATE_MAX_AGE,
+};
+use crate::opaque_password_change::{
+    verify_password_change_tag, PasswordChangeFinalization, PasswordChangeRequest,
+    PasswordChangeResponse,
+};
+use crate::opaque_recovery::{
+    check_wrapped_secret, find_recovery_record, recovery_codes_left, take_recovery_record,
+    RecoveryData, RecoveryFinalization, RecoveryRequest, RecoveryResponse,
+};
+use crate::opaque_server::DefaultCipherSuite;
+use crate::opaque_store::CredentialStore;
+
+pub(crate) type Setup = ServerSetup<DefaultCipherSuite>;
+
+fn protocol_error(err: opaque_ke::errors::ProtocolError) -> OpaqueError {
+    OpaqueError::Protocol(err.to_string())
+}
+
+/// new serialized server setup, with a random OPRF seed and key pair
+pub fn generate_setup() -> Vec<u8> {
+    Setup::new(&mut OsRng).serialize().as_slice().to_owned()
+}
+
+/// new serialized server setup using the given private key,
+/// so the server public key stays the same across setups
+pub fn generate_setup_with_key(private_key: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+    let keypair = KeyPair::from_private_key_slice(private_key).map_err(protocol_error)?;
+    Ok(Setup::new_with_key(&mut OsRng, keypair)
+        .serialize()
+        .as_slice()
+        .to_owned())
+}
+
+/// serialized public key of a serialized server setup
+pub fn setup_public_key(setup: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+    Ok(public_key(&deserialize_setup(setup)?))
+}
+
+/// check that a password file was produced with the ciphersuite of this library
+pub fn verify_password_file(password_file: &[u8]) -> Result<(), OpaqueError> {
+    ServerRegistration::<DefaultCipherSuite>::deserialize(unbind_password_file(password_file)?)
+        .map(|_| ())
+        .map_err(protocol_error)
+}
+
+pub(crate) fn deserialize_setup(setup: &[u8]) -> Result<Setup, OpaqueError> {
+    Setup::deserialize(setup).map_err(protocol_error)
+}
+
+pub(crate) fn public_key(setup: &Setup) -> Vec<u8> {
+    setup.keypair().public().serialize().as_slice().to_owned()
+}
+
+// protocol steps shared by OpaqueServer and AsyncOpaqueServer:
+// they only deal with bytes, storage is up to the caller
+
+pub(crate) fn registration_response(
+    setup: &Setup,
+    username: &str,
+    registration_request: &[u8],
+) -> Result<Vec<u8>, OpaqueError> {
+    let request = RegistrationRequest::deserialize(registration_request).map_err(protocol_error)?;
+    let reg_start =
+        ServerRegistration::<DefaultCipherSuite>::start(setup, request, username.as_bytes())
+            .map_err(protocol_error)?;
+    Ok(reg_start.message.serialize().as_slice().to_owned())
+}
+
+// password file built from an upload, bound or not, without checking the identities
+pub(crate) fn password_file_from_upload(upload: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+    let message = RegistrationUpload::<DefaultCipherSuite>::deserialize(unbind_upload(upload)?)
+        .map_err(protocol_error)?;
+    let password_file = ServerRegistration::finish(message);
+    Ok(password_file.serialize().as_slice().to_owned())
+}
+
+// password file of username bound to the identities of config, built from
+// an upload: fails with IdentityMismatch if the client used other identities
+pub(crate) fn bound_password_file_from_upload(
+    config: &ProtocolConfig,
+    username: &str,
+    upload: &[u8],
+) -> Result<Vec<u8>, OpaqueError> {
+    let password_file = password_file_from_upload(config.check_upload(username, upload)?)?;
+    Ok(config.bind_password_file(username, &password_file))
+}
+
+// returns credential response and login state, the latter
+// already wrapped with creation time and id
+pub(crate) fn credential_response(
+    setup: &Setup,
+    config: &ProtocolConfig,
+    username: &str,
+    password_file: Option<&[u8]>,
+    credential_request: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>), OpaqueError> {
+    let pass_file = match password_file {
+        Some(val) => Some(
+            ServerRegistration::<DefaultCipherSuite>::deserialize(
+                config.check_password_file(username, val)?,
+            )
+            .map_err(protocol_error)?,
+        ),
+        None => None,
+    };
+    let request = CredentialRequest::deserialize(credential_request).map_err(protocol_error)?;
+
+    let login_start = ServerLogin::start(
+        &mut OsRng,
+        setup,
+        pass_file,
+        request,
+        username.as_bytes(),
+        ServerLoginStartParameters {
+            context: Some(&config.context),
+            identifiers: config.identifiers(username),
+        },
+    )
+    .map_err(protocol_error)?;
+
+    Ok((
+        login_start.message.serialize().as_slice().to_owned(),
+        wrap_login_state(login_start.state.serialize().as_slice()),
+    ))
+}
+
+pub(crate) fn session_key(
+    login_state: &[u8],
+    credential_finalization: &[u8],
+) -> Result<Vec<u8>, OpaqueError> {
+    let state =
+        ServerLogin::<DefaultCipherSuite>::deserialize(login_state).map_err(protocol_error)?;
+    let finalization =
+        CredentialFinalization::deserialize(credential_finalization).map_err(protocol_error)?;
+
+    let login_finish = state.finish(finalization).map_err(protocol_error)?;
+    Ok(login_finish.session_key.as_slice().to_owned())
+}
+
+/// login state of the server between handle_credential_request_stateless
+/// and handle_credential_finalization_stateless, for servers keeping it
+/// themselves, e.g. in a shared cache, instead of in the OpaqueServer
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ServerLoginState {
+    /// serialized state, preceded by a LoginStateHeader
+    #[cfg_attr(feature = "serde", serde(with = "crate::opaque_serde::base64_bytes"))]
+    pub state: Vec<u8>,
+}
+
+impl ServerLoginState {
+    /// creation time and id of the state
+    pub fn header(&self) -> Result<LoginStateHeader, OpaqueError> {
+        unwrap_login_state(&self.state).map(|(header, _)| header)
+    }
+}
+
+// login states kept between the two login steps, keyed by username and
+// session id: a client can't replace the pending login of another user
+// by starting one with the same session id
+#[derive(Default)]
+pub(crate) struct LoginSessions {
+    pending: Mutex<HashMap<(String, String), Vec<u8>>>,
+}
+
+impl LoginSessions {
+    pub(crate) fn insert(&self, username: &str, session_id: &str, state: Vec<u8>) {
+        let mut pending = self.pending.lock().unwrap();
+        // drop logins never finalized
+        let now = now_secs();
+        pending.retain(|_, login_state| match unwrap_login_state(login_state) {
+            Ok((header, _)) => header.created_at + DEFAULT_LOGIN_STATE_MAX_AGE.as_secs() >= now,
+            Err(_) => false,
+        });
+        pending.insert((username.to_string(), session_id.to_string()), state);
+    }
+
+    // a login state is removed as soon as it is used, even if login fails
+    pub(crate) fn take(&self, username: &str, session_id: &str) -> Result<Vec<u8>, OpaqueError> {
+        let key = (username.to_string(), session_id.to_string());
+        let login_state = match self.pending.lock().unwrap().remove(&key) {
+            Some(val) => val,
+            None => return Err(OpaqueError::UnknownSession),
+        };
+
+        // no replay cache needed: the state was just removed
+        let state = check_login_state(
+            &LoginStatePolicy {
+                max_age: DEFAULT_LOGIN_STATE_MAX_AGE,
+                replay_cache: None,
+            },
+            &login_state,
+        )?;
+        Ok(state.to_owned())
+    }
+}
+
+/// server side of the protocol in a single object: it owns the server setup,
+/// the credential store and the login states between the two login steps,
+/// so the caller only passes messages around
+///
+/// username is both the credential identifier, under which the password file
+/// is stored, and the client identity: see the *_with_identity methods
+/// to keep them apart
+pub struct OpaqueServer {
+    setup: Setup,
+    config: ProtocolConfig,
+    store: Box<dyn CredentialStore>,
+    sessions: LoginSessions,
+}
+
+impl OpaqueServer {
+    /// server using the serialized setup returned by server registration start
+    pub fn new(
+        setup: &[u8],
+        servername: String,
+        context: String,
+        store: Box<dyn CredentialStore>,
+    ) -> Result<OpaqueServer, OpaqueError> {
+        OpaqueServer::with_config(
+            setup,
+            ProtocolConfig::new(&servername, context.as_bytes()),
+            store,
+        )
+    }
+
+    /// same as new, with identities and context given as a configuration
+    pub fn with_config(
+        setup: &[u8],
+        config: ProtocolConfig,
+        store: Box<dyn CredentialStore>,
+    ) -> Result<OpaqueServer, OpaqueError> {
+        Ok(OpaqueServer {
+            setup: deserialize_setup(setup)?,
+            config,
+            store,
+            sessions: LoginSessions::default(),
+        })
+    }
+
+    /// server using the setup saved in store,
+    /// generating and saving a new one if missing
+    pub fn load_or_generate(
+        servername: String,
+        context: String,
+        store: Box<dyn CredentialStore>,
+    ) -> Result<OpaqueServer, OpaqueError> {
+        let setup = match store.get_server_setup()? {
+            Some(val) => val,
+            None => {
+                let new_setup = generate_setup();
+                store.put_server_setup(&new_setup)?;
+                new_setup
+            }
+        };
+        OpaqueServer::new(&setup, servername, context, store)
+    }
+
+    /// serialized server setup, to save it somewhere else
+    pub fn setup(&self) -> Vec<u8> {
+        self.setup.serialize().as_slice().to_owned()
+    }
+
+    /// identities and context of the server
+    pub fn config(&self) -> &ProtocolConfig {
+        &self.config
+    }
+
+    /// serialized public key of the server, the one clients
+    /// get as server_public_key at the end of registration and login
+    pub fn public_key(&self) -> Vec<u8> {
+        public_key(&self.setup)
+    }
+
+    /// answer the registration request of username
+    /// (result of client registration start):
+    /// fails with AlreadyRegistered if username has a password file
+    pub fn handle_registration_request(
+        &self,
+        username: &str,
+        registration_request: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        if self.store.get_password_file(username.as_bytes())?.is_some() {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        registration_response(&self.setup, username, registration_request)
+    }
+
+    /// save the password file of username built from the registration upload
+    /// (result of client registration finish), bound to the identities
+    /// of the server configuration: fails with IdentityMismatch if the client
+    /// registered with other identities, and with AlreadyRegistered if username
+    /// has a password file, only a password change can replace it
+    pub fn handle_registration_upload(
+        &self,
+        username: &str,
+        upload: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let password_file = bound_password_file_from_upload(&self.config, username, upload)?;
+        self.store.create_password_file(username.as_bytes(), &password_file)
+    }
+
+    /// same as handle_registration_upload, keeping next to the password file
+    /// the records of the recovery codes generated by the client
+    /// (see opaque_recovery::generate_recovery_codes)
+    pub fn handle_registration_upload_with_recovery(
+        &self,
+        username: &str,
+        upload: &[u8],
+        records: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let password_file = bound_password_file_from_upload(&self.config, username, upload)?;
+        let data = RecoveryData {
+            records: records.to_owned(),
+            wrapped_secret: None,
+        };
+        // refuse records the server couldn't read back
+        recovery_codes_left(records)?;
+        self.store.create_credential(username.as_bytes(), &password_file, &data.serialize())
+    }
+
+    /// recovery codes username can still use, 0 if none
+    pub fn recovery_codes_left(&self, username: &str) -> Result<usize, OpaqueError> {
+        match self.recovery_data(username)? {
+            Some(data) => recovery_codes_left(&data.records),
+            None => Ok(0),
+        }
+    }
+
+    /// recovery secret of username wrapped under the export key of the current
+    /// password, None if it's still derived from it: to be sent to the client
+    /// only after a successful login, for opaque_recovery::secret_from_export_key
+    pub fn recovery_wrapped_secret(&self, username: &str) -> Result<Option<Vec<u8>>, OpaqueError> {
+        Ok(self
+            .recovery_data(username)?
+            .and_then(|data| data.wrapped_secret))
+    }
+
+    fn recovery_data(&self, username: &str) -> Result<Option<RecoveryData>, OpaqueError> {
+        match self.store.get_recovery_data(username.as_bytes())? {
+            Some(val) => Ok(Some(RecoveryData::deserialize(&val)?)),
+            None => Ok(None),
+        }
+    }
+
+    // replace the password file of username, already bound to the identities
+    // of the server configuration, and its recovery data, made by
+    // update from the current one: if another request changed the recovery
+    // data in the meantime update runs again on the new one, so a recovery
+    // code taken by update is never taken twice
+    fn replace_credential<F>(
+        &self,
+        username: &str,
+        password_file: &[u8],
+        update: F,
+    ) -> Result<(), OpaqueError>
+    where
+        F: Fn(Option<RecoveryData>) -> Result<RecoveryData, OpaqueError>,
+    {
+        loop {
+            let current = self.store.get_recovery_data(username.as_bytes())?;
+            let data = match &current {
+                Some(val) => Some(RecoveryData::deserialize(val)?),
+                None => None,
+            };
+            let new_data = update(data)?;
+            if self.store.replace_credential(
+                username.as_bytes(),
+                current.as_deref(),
+                password_file,
+                &new_data.serialize(),
+            )? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// first server step of a recovery: the secret of username wrapped under
+    /// the recovery code of request.verifier, and the registration of the new
+    /// password; fails with InvalidRecoveryCode, without using the code, if
+    /// username has no such code
+    pub fn handle_recovery_request(
+        &self,
+        username: &str,
+        request: &RecoveryRequest,
+    ) -> Result<RecoveryResponse, OpaqueError> {
+        let data = match self.recovery_data(username)? {
+            Some(val) => val,
+            None => return Err(OpaqueError::InvalidRecoveryCode),
+        };
+        let wrapped_secret = find_recovery_record(&data.records, &request.verifier)?;
+        let registration_response =
+            registration_response(&self.setup, username, &request.registration_request)?;
+        Ok(RecoveryResponse {
+            wrapped_secret,
+            registration_response,
+        })
+    }
+
+    /// last server step of a recovery: the code of finalization.verifier is
+    /// removed and the password file of username replaced, in a single store
+    /// write; fails with InvalidRecoveryCode if the code was already used
+    pub fn handle_recovery_finalization(
+        &self,
+        username: &str,
+        finalization: &RecoveryFinalization,
+    ) -> Result<(), OpaqueError> {
+        check_wrapped_secret(&finalization.wrapped_secret)?;
+        let password_file =
+            bound_password_file_from_upload(&self.config, username, &finalization.upload)?;
+        self.replace_credential(username, &password_file, |data| {
+            let data = data.ok_or(OpaqueError::InvalidRecoveryCode)?;
+            let (_, records) = take_recovery_record(&data.records, &finalization.verifier)?;
+            Ok(RecoveryData {
+                records,
+                wrapped_secret: Some(finalization.wrapped_secret.clone()),
+            })
+        })
+    }
+
+    /// answer the credential request of username (result of client login start),
+    /// keeping the login state under session_id until the finalization:
+    /// an unknown username gets a fake answer, so it can't be told apart
+    pub fn handle_credential_request(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_request: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        self.login_start(username, session_id, credential_request, &self.config)
+    }
+
+    /// same as handle_credential_request, with a context for this login only
+    /// in place of the server one, e.g. the channel_binding_context
+    /// of the connection the request came from
+    pub fn handle_credential_request_with_context(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_request: &[u8],
+        context: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        self.login_start(
+            username,
+            session_id,
+            credential_request,
+            &self.config.with_context(context),
+        )
+    }
+
+    /// same as handle_credential_request, for a credential registered under
+    /// credential_id and logging in as identity: the client must pass the
+    /// same identity, which can change without touching the password file
+    pub fn handle_credential_request_with_identity(
+        &self,
+        credential_id: &str,
+        identity: &str,
+        session_id: &str,
+        credential_request: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        self.login_start(
+            credential_id,
+            session_id,
+            credential_request,
+            &self.config.for_identity(identity),
+        )
+    }
+
+    fn login_start(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_request: &[u8],
+        config: &ProtocolConfig,
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let password_file = self.store.get_password_file(username.as_bytes())?;
+        let (response, state) = credential_response(
+            &self.setup,
+            config,
+            username,
+            password_file.as_deref(),
+            credential_request,
+        )?;
+
+        self.sessions.insert(username, session_id, state);
+        Ok(response)
+    }
+
+    /// finish the login of session_id with the credential finalization
+    /// (result of client login finish), returning the session key
+    pub fn handle_credential_finalization(
+        &self,
+        username: &str,
+        session_id: &str,
+        credential_finalization: &[u8],
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let state = self.sessions.take(username, session_id)?;
+        session_key(&state, credential_finalization)
+    }
+
+    /// same as handle_credential_request, giving the login state back
+    /// to the caller instead of keeping it
+    pub fn handle_credential_request_stateless(
+        &self,
+        username: &str,
+        credential_request: &[u8],
+    ) -> Result<(Vec<u8>, ServerLoginState), OpaqueError> {
+        let password_file = self.store.get_password_file(username.as_bytes())?;
+        let (response, state) = credential_response(
+            &self.setup,
+            &self.config,
+            username,
+            password_file.as_deref(),
+            credential_request,
+        )?;
+        Ok((response, ServerLoginState { state }))
+    }
+
+    /// finish the login of state, returned by
+    /// handle_credential_request_stateless, with the credential finalization:
+    /// policy tells how old the state can be and whether it was already used
+    pub fn handle_credential_finalization_stateless(
+        &self,
+        state: &ServerLoginState,
+        credential_finalization: &[u8],
+        policy: &LoginStatePolicy,
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let login_state = check_login_state(policy, &state.state)?;
+        session_key(login_state, credential_finalization)
+    }
+
+    /// answer the password change request of username, keeping the
+    /// login state under session_id until the finalization
+    pub fn handle_password_change_request(
+        &self,
+        username: &str,
+        session_id: &str,
+        request: &PasswordChangeRequest,
+    ) -> Result<PasswordChangeResponse, OpaqueError> {
+        self.password_change_start(username, session_id, request, &self.config)
+    }
+
+    /// same as handle_password_change_request, for a credential registered
+    /// under credential_id and logging in as identity
+    pub fn handle_password_change_request_with_identity(
+        &self,
+        credential_id: &str,
+        identity: &str,
+        session_id: &str,
+        request: &PasswordChangeRequest,
+    ) -> Result<PasswordChangeResponse, OpaqueError> {
+        self.password_change_start(
+            credential_id,
+            session_id,
+            request,
+            &self.config.for_identity(identity),
+        )
+    }
+
+    fn password_change_start(
+        &self,
+        username: &str,
+        session_id: &str,
+        request: &PasswordChangeRequest,
+        config: &ProtocolConfig,
+    ) -> Result<PasswordChangeResponse, OpaqueError> {
+        let registration_response =
+            registration_response(&self.setup, username, &request.registration_request)?;
+        let credential_response =
+            self.login_start(username, session_id, &request.credential_request, config)?;
+        // sent before the login is verified: it opens only under the
+        // export key of the current password, and it's empty for unknown
+        // users as for the ones whose secret is still derived from it
+        let wrapped_secret = self.recovery_wrapped_secret(username)?.unwrap_or_default();
+        Ok(PasswordChangeResponse {
+            credential_response,
+            registration_response,
+            wrapped_secret,
+        })
+    }
+
+    /// finish the login of session_id and, only if it succeeds and the
+    /// upload is bound to its session key, replace the password file
+    /// of username, keeping the recovery secret wrapped under the new
+    /// password next to it: returns the session key
+    pub fn handle_password_change_finalization(
+        &self,
+        username: &str,
+        session_id: &str,
+        finalization: &PasswordChangeFinalization,
+    ) -> Result<Vec<u8>, OpaqueError> {
+        let state = self.sessions.take(username, session_id)?;
+        let key = session_key(&state, &finalization.credential_finalization)?;
+        verify_password_change_tag(
+            &key,
+            username,
+            &finalization.upload,
+            &finalization.wrapped_secret,
+            &finalization.tag,
+        )?;
+        check_wrapped_secret(&finalization.wrapped_secret)?;
+
+        let password_file =
+            bound_password_file_from_upload(&self.config, username, &finalization.upload)?;
+        self.replace_credential(username, &password_file, |data| {
+            Ok(RecoveryData {
+                wrapped_secret: Some(finalization.wrapped_secret.clone()),
+                ..data.unwrap_or_else(RecoveryData::empty)
+            })
+        })?;
+        Ok(key)
+    }
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use crate::opaque_error::OpaqueError;
+use crate::opaque_login_state::{now_secs, unwrap_login_state, LoginStatePolicy, ReplayCache};
+use crate::opaque_seal::{seal, unseal, Kek, RecordType};
+use crate::opaque_server::{server_login_finish, server_login_start, ServerResponseWithState};
+
+// length of the expiry timestamp put in front of the login state
+const EXPIRY_LEN: usize = 8;
+
+/// seal a serialized ServerLogin state into a token that can be
+/// handed to the client and sent back with the credential finalization:
+/// the token is bound to username and expires after ttl
+pub fn seal_login_state(key: &Kek, username: &str, state: &[u8], ttl: Duration) -> Vec<u8> {
+    let expires_at = now_secs() + ttl.as_secs();
+
+    let mut plaintext = Vec::with_capacity(EXPIRY_LEN + state.len());
+    plaintext.extend_from_slice(&expires_at.to_be_bytes());
+    plaintext.extend_from_slice(state);
+    seal(key, RecordType::LoginState, username, &plaintext)
+}
+
+/// give back the serialized ServerLogin state held by a token,
+/// checking it belongs to username and isn't expired
+pub fn unseal_login_state(key: &Kek, username: &str, token: &[u8]) -> Result<Vec<u8>, OpaqueError> {
+    open_token(key, username, token).map(|(_, state)| state)
+}
+
+// expiry timestamp and serialized ServerLogin state of a token
+fn open_token(key: &Kek, username: &str, token: &[u8]) -> Result<(u64, Vec<u8>), OpaqueError> {
+    let plaintext = unseal(key, RecordType::LoginState, username, token)?;
+    if plaintext.len() < EXPIRY_LEN {
+        return Err(OpaqueError::MalformedRecord);
+    }
+
+    let mut expiry = [0u8; EXPIRY_LEN];
+    expiry.copy_from_slice(&plaintext[..EXPIRY_LEN]);
+    let expires_at = u64::from_be_bytes(expiry);
+    if now_secs() > expires_at {
+        return Err(OpaqueError::Expired);
+    }
+
+    Ok((expires_at, plaintext[EXPIRY_LEN..].to_owned()))
+}
+
+/// same as server_login_start, but the returned state is a sealed token:
+/// the server doesn't need to store anything between the two login steps;
+/// response and token are both empty if the login start failed
+#[allow(clippy::too_many_arguments)]
+pub fn server_login_start_stateless(
+    key: &Kek,
+    ttl: Duration,
+    username: String,
+    password_file_bytes: &[u8],
+    credential_request_bytes: &[u8],
+    serv_setup: &[u8],
+    servername: String,
+    context: String,
+) -> ServerResponseWithState {
+    let login_start = server_login_start(
+        username.clone(),
+        password_file_bytes,
+        credential_request_bytes,
+        serv_setup,
+        servername,
+        context,
+    );
+    // nothing to seal: an empty state would still make a valid token
+    if login_start.state.is_empty() {
+        return ServerResponseWithState {
+            response: vec![],
+            state: vec![],
+        };
+    }
+
+    ServerResponseWithState {
+        response: login_start.response,
+        state: seal_login_state(key, &username, &login_start.state, ttl),
+    }
+}
+
+/// same as server_login_finish, but the login state is read
+/// from the token returned by server_login_start_stateless:
+/// it is accepted until the ttl given at login start and,
+/// with a replay_cache, only once
+pub fn server_login_finish_stateless(
+    key: &Kek,
+    username: &str,
+    credential_finalization_bytes: &[u8],
+    token: &[u8],
+    replay_cache: Option<&dyn ReplayCache>,
+) -> bool {
+    let (expires_at, state) = match open_token(key, username, token) {
+        Ok(val) => val,
+        Err(err) => {
+            println!("RUST - LOG: Invalid login state token: {}", err);
+            return false;
+        }
+    };
+
+    // the state inside the token carries its creation time:
+    // its max age is the ttl the token was sealed with
+    let created_at = match unwrap_login_state(&state) {
+        Ok((header, _)) => header.created_at,
+        Err(err) => {
+            println!("RUST - LOG: Invalid login state token: {}", err);
+            return false;
+        }
+    };
+    let policy = LoginStatePolicy {
+        max_age: Duration::from_secs(expires_at.saturating_sub(created_at)),
+        replay_cache,
+    };
+    server_login_finish(credential_finalization_bytes, &state, &policy)
+}
@@ -0,0 +1,487 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::opaque_error::OpaqueError;
+
+/// storage of everything the server needs between registration and login:
+/// password files, keyed by credential identifier, the recovery data kept
+/// next to them (see opaque_recovery) and the server setup
+pub trait CredentialStore: Send + Sync {
+    /// save the password file of credential_id, replacing the previous one
+    fn put_password_file(&self, credential_id: &[u8], password_file: &[u8])
+        -> Result<(), OpaqueError>;
+
+    /// save the password file of credential_id: fails with AlreadyRegistered,
+    /// leaving the store as it is, if credential_id already has one
+    fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError>;
+
+    /// give back the password file of credential_id, if registered
+    fn get_password_file(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError>;
+
+    /// remove the password file of credential_id, and its recovery data:
+    /// return false if there was nothing to remove
+    fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError>;
+
+    /// same as create_password_file, saving recovery_data in the same write
+    fn create_credential(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<(), OpaqueError>;
+
+    /// replace password file and recovery data of credential_id in a single
+    /// write, only if the recovery data is still expected (None: missing):
+    /// return false, leaving the store as it is, if it was changed meanwhile
+    fn replace_credential(
+        &self,
+        credential_id: &[u8],
+        expected_recovery_data: Option<&[u8]>,
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<bool, OpaqueError>;
+
+    /// give back the recovery data of credential_id, if any
+    fn get_recovery_data(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError>;
+
+    /// save the server setup, replacing the previous one
+    fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError>;
+
+    /// give back the server setup, if already saved
+    fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError>;
+}
+
+// a store shared with other owners, e.g. by a server and the code
+// managing its users, is a store as well
+impl<S: CredentialStore + ?Sized> CredentialStore for Arc<S> {
+    fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        (**self).put_password_file(credential_id, password_file)
+    }
+
+    fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        (**self).create_password_file(credential_id, password_file)
+    }
+
+    fn get_password_file(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        (**self).get_password_file(credential_id)
+    }
+
+    fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError> {
+        (**self).delete_password_file(credential_id)
+    }
+
+    fn create_credential(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<(), OpaqueError> {
+        (**self).create_credential(credential_id, password_file, recovery_data)
+    }
+
+    fn replace_credential(
+        &self,
+        credential_id: &[u8],
+        expected_recovery_data: Option<&[u8]>,
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<bool, OpaqueError> {
+        (**self).replace_credential(
+            credential_id,
+            expected_recovery_data,
+            password_file,
+            recovery_data,
+        )
+    }
+
+    fn get_recovery_data(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        (**self).get_recovery_data(credential_id)
+    }
+
+    fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError> {
+        (**self).put_server_setup(setup)
+    }
+
+    fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError> {
+        (**self).get_server_setup()
+    }
+}
+
+#[derive(Default)]
+struct Records {
+    password_files: HashMap<Vec<u8>, Vec<u8>>,
+    recovery_data: HashMap<Vec<u8>, Vec<u8>>,
+    setup: Option<Vec<u8>>,
+}
+
+impl Records {
+    fn put_credential(
+        &mut self,
+        credential_id: &[u8],
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) {
+        self.password_files
+            .insert(credential_id.to_owned(), password_file.to_owned());
+        self.recovery_data
+            .insert(credential_id.to_owned(), recovery_data.to_owned());
+    }
+
+    fn delete_credential(&mut self, credential_id: &[u8]) -> bool {
+        self.recovery_data.remove(credential_id);
+        self.password_files.remove(credential_id).is_some()
+    }
+}
+
+/// credential store kept in process memory, lost on restart
+#[derive(Default)]
+pub struct MemoryCredentialStore {
+    records: Mutex<Records>,
+}
+
+impl MemoryCredentialStore {
+    pub fn new() -> MemoryCredentialStore {
+        MemoryCredentialStore::default()
+    }
+}
+
+impl CredentialStore for MemoryCredentialStore {
+    fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut records = self.records.lock().unwrap();
+        records
+            .password_files
+            .insert(credential_id.to_owned(), password_file.to_owned());
+        Ok(())
+    }
+
+    fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut records = self.records.lock().unwrap();
+        match records.password_files.entry(credential_id.to_owned()) {
+            Entry::Occupied(_) => Err(OpaqueError::AlreadyRegistered),
+            Entry::Vacant(entry) => {
+                entry.insert(password_file.to_owned());
+                Ok(())
+            }
+        }
+    }
+
+    fn get_password_file(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let records = self.records.lock().unwrap();
+        Ok(records.password_files.get(credential_id).cloned())
+    }
+
+    fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError> {
+        let mut records = self.records.lock().unwrap();
+        Ok(records.delete_credential(credential_id))
+    }
+
+    fn create_credential(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut records = self.records.lock().unwrap();
+        if records.password_files.contains_key(credential_id) {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        records.put_credential(credential_id, password_file, recovery_data);
+        Ok(())
+    }
+
+    fn replace_credential(
+        &self,
+        credential_id: &[u8],
+        expected_recovery_data: Option<&[u8]>,
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<bool, OpaqueError> {
+        let mut records = self.records.lock().unwrap();
+        if records.recovery_data.get(credential_id).map(Vec::as_slice) != expected_recovery_data {
+            return Ok(false);
+        }
+        records.put_credential(credential_id, password_file, recovery_data);
+        Ok(true)
+    }
+
+    fn get_recovery_data(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let records = self.records.lock().unwrap();
+        Ok(records.recovery_data.get(credential_id).cloned())
+    }
+
+    fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError> {
+        let mut records = self.records.lock().unwrap();
+        records.setup = Some(setup.to_owned());
+        Ok(())
+    }
+
+    fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let records = self.records.lock().unwrap();
+        Ok(records.setup.clone())
+    }
+}
+
+// operations written in the log of FileCredentialStore
+const OP_PUT_PASSWORD_FILE: u8 = 1;
+const OP_DELETE_PASSWORD_FILE: u8 = 2;
+const OP_PUT_SERVER_SETUP: u8 = 3;
+// value: length-prefixed password file, then recovery data
+const OP_PUT_CREDENTIAL: u8 = 4;
+
+fn storage_error(err: std::io::Error) -> OpaqueError {
+    OpaqueError::Storage(err.to_string())
+}
+
+impl Records {
+    // apply an operation of the log of FileCredentialStore
+    fn apply(&mut self, op: u8, key: &[u8], value: &[u8]) -> Result<(), OpaqueError> {
+        match op {
+            OP_PUT_PASSWORD_FILE => {
+                self.password_files.insert(key.to_owned(), value.to_owned());
+            }
+            OP_DELETE_PASSWORD_FILE => {
+                self.delete_credential(key);
+            }
+            OP_PUT_SERVER_SETUP => self.setup = Some(value.to_owned()),
+            OP_PUT_CREDENTIAL => {
+                let mut pos = 0;
+                let password_file = read_field(value, &mut pos).ok_or_else(|| {
+                    OpaqueError::Storage("malformed credential log entry".to_string())
+                })?;
+                self.put_credential(key, password_file, &value[pos..]);
+            }
+            _ => return Err(OpaqueError::Storage(format!("unknown operation {}", op))),
+        }
+        Ok(())
+    }
+}
+
+// log file and the records replayed from it, always changed together
+struct FileLog {
+    file: File,
+    // length of the log up to the last whole entry
+    len: u64,
+    records: Records,
+}
+
+// value of an OP_PUT_CREDENTIAL entry
+fn credential_entry(password_file: &[u8], recovery_data: &[u8]) -> Vec<u8> {
+    let mut value = Vec::with_capacity(4 + password_file.len() + recovery_data.len());
+    value.extend_from_slice(&(password_file.len() as u32).to_be_bytes());
+    value.extend_from_slice(password_file);
+    value.extend_from_slice(recovery_data);
+    value
+}
+
+impl FileLog {
+    // append an operation to the log, flush it to disk and apply it
+    fn commit(&mut self, op: u8, key: &[u8], value: &[u8]) -> Result<(), OpaqueError> {
+        let mut entry = Vec::with_capacity(1 + 8 + key.len() + value.len());
+        entry.push(op);
+        entry.extend_from_slice(&(key.len() as u32).to_be_bytes());
+        entry.extend_from_slice(key);
+        entry.extend_from_slice(&(value.len() as u32).to_be_bytes());
+        entry.extend_from_slice(value);
+
+        let written = self
+            .file
+            .write_all(&entry)
+            .and_then(|_| self.file.sync_data());
+        if let Err(err) = written {
+            // don't leave part of the entry in front of the next one
+            let _ = self.file.set_len(self.len);
+            return Err(storage_error(err));
+        }
+        self.len += entry.len() as u64;
+        self.records.apply(op, key, value)
+    }
+}
+
+/// credential store saved in a single append-only file:
+/// every change is appended as (operation, key, value), each one
+/// preceded by its length, and the file is replayed in memory on open
+pub struct FileCredentialStore {
+    log: Mutex<FileLog>,
+}
+
+impl FileCredentialStore {
+    /// open the log at path, creating it if missing: an entry cut short
+    /// by a crash while it was appended is dropped, since it was never
+    /// acknowledged to the caller
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileCredentialStore, OpaqueError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(storage_error)?;
+
+        let mut log = Vec::new();
+        BufReader::new(&mut file)
+            .read_to_end(&mut log)
+            .map_err(storage_error)?;
+
+        let mut records = Records::default();
+        let mut pos = 0;
+        while pos < log.len() {
+            let start = pos;
+            match read_entry(&log, &mut pos) {
+                Some((op, key, value)) => records.apply(op, key, value)?,
+                None => {
+                    println!(
+                        "RUST - LOG: Dropping truncated credential log entry at byte {}",
+                        start
+                    );
+                    file.set_len(start as u64).map_err(storage_error)?;
+                    pos = start;
+                    break;
+                }
+            }
+        }
+
+        Ok(FileCredentialStore {
+            log: Mutex::new(FileLog {
+                file,
+                len: pos as u64,
+                records,
+            }),
+        })
+    }
+}
+
+// read an entry of the log: None if it is cut short
+fn read_entry<'a>(log: &'a [u8], pos: &mut usize) -> Option<(u8, &'a [u8], &'a [u8])> {
+    let op = *log.get(*pos)?;
+    *pos += 1;
+    let key = read_field(log, pos)?;
+    let value = read_field(log, pos)?;
+    Some((op, key, value))
+}
+
+// read a length-prefixed field of the log
+fn read_field<'a>(log: &'a [u8], pos: &mut usize) -> Option<&'a [u8]> {
+    if log.len() < *pos + 4 {
+        return None;
+    }
+    let len = u32::from_be_bytes([log[*pos], log[*pos + 1], log[*pos + 2], log[*pos + 3]]) as usize;
+    *pos += 4;
+    if log.len() < *pos + len {
+        return None;
+    }
+    let field = &log[*pos..*pos + len];
+    *pos += len;
+    Some(field)
+}
+
+impl CredentialStore for FileCredentialStore {
+    fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut log = self.log.lock().unwrap();
+        log.commit(OP_PUT_PASSWORD_FILE, credential_id, password_file)
+    }
+
+    fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut log = self.log.lock().unwrap();
+        if log.records.password_files.contains_key(credential_id) {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        log.commit(OP_PUT_PASSWORD_FILE, credential_id, password_file)
+    }
+
+    fn get_password_file(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let log = self.log.lock().unwrap();
+        Ok(log.records.password_files.get(credential_id).cloned())
+    }
+
+    fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError> {
+        let mut log = self.log.lock().unwrap();
+        if !log.records.password_files.contains_key(credential_id) {
+            return Ok(false);
+        }
+        log.commit(OP_DELETE_PASSWORD_FILE, credential_id, &[])?;
+        Ok(true)
+    }
+
+    fn create_credential(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut log = self.log.lock().unwrap();
+        if log.records.password_files.contains_key(credential_id) {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        log.commit(
+            OP_PUT_CREDENTIAL,
+            credential_id,
+            &credential_entry(password_file, recovery_data),
+        )
+    }
+
+    fn replace_credential(
+        &self,
+        credential_id: &[u8],
+        expected_recovery_data: Option<&[u8]>,
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<bool, OpaqueError> {
+        let mut log = self.log.lock().unwrap();
+        if log.records.recovery_data.get(credential_id).map(Vec::as_slice) != expected_recovery_data
+        {
+            return Ok(false);
+        }
+        log.commit(
+            OP_PUT_CREDENTIAL,
+            credential_id,
+            &credential_entry(password_file, recovery_data),
+        )?;
+        Ok(true)
+    }
+
+    fn get_recovery_data(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let log = self.log.lock().unwrap();
+        Ok(log.records.recovery_data.get(credential_id).cloned())
+    }
+
+    fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError> {
+        let mut log = self.log.lock().unwrap();
+        log.commit(OP_PUT_SERVER_SETUP, &[], setup)
+    }
+
+    fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let log = self.log.lock().unwrap();
+        Ok(log.records.setup.clone())
+    }
+}
@@ -0,0 +1,206 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::opaque_error::OpaqueError;
+use crate::opaque_store::CredentialStore;
+
+fn storage_error(err: rusqlite::Error) -> OpaqueError {
+    OpaqueError::Storage(err.to_string())
+}
+
+/// credential store saved in an SQLite database
+pub struct SqliteCredentialStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteCredentialStore {
+    /// open the database at path, creating the needed tables if missing
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<SqliteCredentialStore, OpaqueError> {
+        SqliteCredentialStore::from_connection(Connection::open(path).map_err(storage_error)?)
+    }
+
+    /// database kept in memory, lost on drop
+    pub fn open_in_memory() -> Result<SqliteCredentialStore, OpaqueError> {
+        SqliteCredentialStore::from_connection(Connection::open_in_memory().map_err(storage_error)?)
+    }
+
+    fn from_connection(conn: Connection) -> Result<SqliteCredentialStore, OpaqueError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS password_files (
+                credential_id BLOB PRIMARY KEY,
+                password_file BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS recovery_data (
+                credential_id BLOB PRIMARY KEY,
+                recovery_data BLOB NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS server_setup (
+                id INTEGER PRIMARY KEY CHECK (id = 0),
+                setup BLOB NOT NULL
+            );",
+        )
+        .map_err(storage_error)?;
+
+        Ok(SqliteCredentialStore {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+fn get_recovery_data(
+    conn: &Connection,
+    credential_id: &[u8],
+) -> Result<Option<Vec<u8>>, OpaqueError> {
+    conn.query_row(
+        "SELECT recovery_data FROM recovery_data WHERE credential_id = ?1",
+        params![credential_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(storage_error)
+}
+
+fn put_recovery_data(
+    conn: &Connection,
+    credential_id: &[u8],
+    recovery_data: &[u8],
+) -> Result<(), OpaqueError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO recovery_data (credential_id, recovery_data) VALUES (?1, ?2)",
+        params![credential_id, recovery_data],
+    )
+    .map_err(storage_error)?;
+    Ok(())
+}
+
+impl CredentialStore for SqliteCredentialStore {
+    fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO password_files (credential_id, password_file) VALUES (?1, ?2)",
+            params![credential_id, password_file],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let conn = self.conn.lock().unwrap();
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO password_files (credential_id, password_file) VALUES (?1, ?2)",
+                params![credential_id, password_file],
+            )
+            .map_err(storage_error)?;
+        if inserted == 0 {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        Ok(())
+    }
+
+    fn get_password_file(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT password_file FROM password_files WHERE credential_id = ?1",
+            params![credential_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(storage_error)
+    }
+
+    fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        tx.execute(
+            "DELETE FROM recovery_data WHERE credential_id = ?1",
+            params![credential_id],
+        )
+        .map_err(storage_error)?;
+        let deleted = tx
+            .execute(
+                "DELETE FROM password_files WHERE credential_id = ?1",
+                params![credential_id],
+            )
+            .map_err(storage_error)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(deleted > 0)
+    }
+
+    fn create_credential(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<(), OpaqueError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        let inserted = tx
+            .execute(
+                "INSERT OR IGNORE INTO password_files (credential_id, password_file) VALUES (?1, ?2)",
+                params![credential_id, password_file],
+            )
+            .map_err(storage_error)?;
+        if inserted == 0 {
+            return Err(OpaqueError::AlreadyRegistered);
+        }
+        put_recovery_data(&tx, credential_id, recovery_data)?;
+        tx.commit().map_err(storage_error)
+    }
+
+    fn replace_credential(
+        &self,
+        credential_id: &[u8],
+        expected_recovery_data: Option<&[u8]>,
+        password_file: &[u8],
+        recovery_data: &[u8],
+    ) -> Result<bool, OpaqueError> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(storage_error)?;
+        if get_recovery_data(&tx, credential_id)?.as_deref() != expected_recovery_data {
+            return Ok(false);
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO password_files (credential_id, password_file) VALUES (?1, ?2)",
+            params![credential_id, password_file],
+        )
+        .map_err(storage_error)?;
+        put_recovery_data(&tx, credential_id, recovery_data)?;
+        tx.commit().map_err(storage_error)?;
+        Ok(true)
+    }
+
+    fn get_recovery_data(&self, credential_id: &[u8]) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let conn = self.conn.lock().unwrap();
+        get_recovery_data(&conn, credential_id)
+    }
+
+    fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO server_setup (id, setup) VALUES (0, ?1)",
+            params![setup],
+        )
+        .map_err(storage_error)?;
+        Ok(())
+    }
+
+    fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row("SELECT setup FROM server_setup WHERE id = 0", [], |row| {
+            row.get(0)
+        })
+        .optional()
+        .map_err(storage_error)
+    }
+}
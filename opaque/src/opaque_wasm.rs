@@ -0,0 +1,201 @@
+// WebAssembly bindings of the client side, for browsers and Node:
+// Uint8Array in and out, the finish steps (where the key stretching
+// function runs) return a Promise
+
+use wasm_bindgen::prelude::*;
+
+use crate::opaque_client_facade::{
+    login_finish, login_start, registration_finish, registration_start, LoginResult,
+    RegistrationResult,
+};
+use crate::opaque_config::ProtocolConfig;
+use crate::opaque_error::OpaqueError;
+use crate::opaque_password_policy::{
+    self as password_policy, estimate_strength, password_issues, DefaultPasswordPolicy,
+    PasswordIssue,
+};
+
+fn js_err(err: OpaqueError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// result of a start step: message for the server and
+/// state to pass back to the matching finish step
+#[wasm_bindgen]
+pub struct ClientStart {
+    response: Vec<u8>,
+    state: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl ClientStart {
+    #[wasm_bindgen(getter)]
+    pub fn response(&self) -> Vec<u8> {
+        self.response.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn state(&self) -> Vec<u8> {
+        self.state.clone()
+    }
+}
+
+#[wasm_bindgen(js_name = RegistrationResult)]
+pub struct WasmRegistrationResult(RegistrationResult);
+
+#[wasm_bindgen(js_class = RegistrationResult)]
+impl WasmRegistrationResult {
+    /// message for the server (registration upload)
+    #[wasm_bindgen(getter)]
+    pub fn upload(&self) -> Vec<u8> {
+        self.0.upload.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = exportKey)]
+    pub fn export_key(&self) -> Vec<u8> {
+        self.0.export_key.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = serverPublicKey)]
+    pub fn server_public_key(&self) -> Vec<u8> {
+        self.0.server_public_key.clone()
+    }
+}
+
+#[wasm_bindgen(js_name = LoginResult)]
+pub struct WasmLoginResult(LoginResult);
+
+#[wasm_bindgen(js_class = LoginResult)]
+impl WasmLoginResult {
+    /// message for the server (credential finalization)
+    #[wasm_bindgen(getter, js_name = credentialFinalization)]
+    pub fn credential_finalization(&self) -> Vec<u8> {
+        self.0.credential_finalization.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = sessionKey)]
+    pub fn session_key(&self) -> Vec<u8> {
+        self.0.session_key.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = exportKey)]
+    pub fn export_key(&self) -> Vec<u8> {
+        self.0.export_key.clone()
+    }
+
+    #[wasm_bindgen(getter, js_name = serverPublicKey)]
+    pub fn server_public_key(&self) -> Vec<u8> {
+        self.0.server_public_key.clone()
+    }
+}
+
+/// throws if the policy of setDefaultPasswordPolicy refuses password
+#[wasm_bindgen(js_name = clientRegistrationStart)]
+pub fn client_registration_start(password: &str) -> Result<ClientStart, JsError> {
+    let (response, state) = registration_start(password).map_err(js_err)?;
+    Ok(ClientStart { response, state })
+}
+
+/// rejected if the policy of setDefaultPasswordPolicy refuses password for username
+#[wasm_bindgen(js_name = clientRegistrationFinish)]
+pub async fn client_registration_finish(
+    password: String,
+    registration_response: Vec<u8>,
+    state: Vec<u8>,
+    username: String,
+    servername: String,
+) -> Result<WasmRegistrationResult, JsError> {
+    registration_finish(
+        &password,
+        &registration_response,
+        &state,
+        &username,
+        &ProtocolConfig::new(&servername, &[]),
+    )
+    .map(WasmRegistrationResult)
+    .map_err(js_err)
+}
+
+#[wasm_bindgen(js_name = clientLoginStart)]
+pub fn client_login_start(password: &str) -> Result<ClientStart, JsError> {
+    let (response, state) = login_start(password).map_err(js_err)?;
+    Ok(ClientStart { response, state })
+}
+
+/// rejected if the password is wrong or the server isn't the expected one
+#[wasm_bindgen(js_name = clientLoginFinish)]
+pub async fn client_login_finish(
+    password: String,
+    credential_response: Vec<u8>,
+    state: Vec<u8>,
+    username: String,
+    servername: String,
+    context: String,
+) -> Result<WasmLoginResult, JsError> {
+    login_finish(
+        &password,
+        &credential_response,
+        &state,
+        &username,
+        &ProtocolConfig::new(&servername, context.as_bytes()),
+    )
+    .map(WasmLoginResult)
+    .map_err(js_err)
+}
+
+/// result of checkPassword: codes and messages of the issues found,
+/// in the same order, for the UI to display
+#[wasm_bindgen]
+pub struct PasswordCheck {
+    issues: Vec<PasswordIssue>,
+    score: u8,
+}
+
+#[wasm_bindgen]
+impl PasswordCheck {
+    /// true if the password has no issues
+    #[wasm_bindgen(getter)]
+    pub fn ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// 0 (very weak) to 4 (very strong)
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> u8 {
+        self.score
+    }
+
+    /// e.g. "too_short", "common"
+    #[wasm_bindgen(getter)]
+    pub fn codes(&self) -> Box<[JsValue]> {
+        self.issues
+            .iter()
+            .map(|issue| JsValue::from_str(issue.code()))
+            .collect()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn messages(&self) -> Box<[JsValue]> {
+        self.issues
+            .iter()
+            .map(|issue| JsValue::from_str(&issue.to_string()))
+            .collect()
+    }
+}
+
+/// check of password for username with the default password policy,
+/// to run before clientRegistrationStart: the server can't tell a weak password
+#[wasm_bindgen(js_name = checkPassword)]
+pub fn check_password(password: &str, username: &str) -> PasswordCheck {
+    PasswordCheck {
+        issues: password_issues(&DefaultPasswordPolicy::default(), password, username),
+        score: estimate_strength(password).score as u8,
+    }
+}
+
+/// make clientRegistrationStart and clientRegistrationFinish refuse passwords
+/// with issues under the default password policy (see checkPassword)
+#[wasm_bindgen(js_name = setDefaultPasswordPolicy)]
+pub fn set_default_password_policy(enabled: bool) {
+    password_policy::set_default_password_policy(enabled);
+}
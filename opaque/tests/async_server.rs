@@ -0,0 +1,209 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::time;
+
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_error::OpaqueError;
+use rust::opaque_server_async::{AsyncCredentialStore, AsyncOpaqueServer};
+use rust::opaque_store::{CredentialStore, MemoryCredentialStore};
+
+const LATENCY: Duration = Duration::from_millis(100);
+
+// store answering after LATENCY, as a remote database would,
+// failing every call once broken is set
+struct LatentStore {
+    records: MemoryCredentialStore,
+    broken: AtomicBool,
+}
+
+impl LatentStore {
+    fn new() -> LatentStore {
+        LatentStore {
+            records: MemoryCredentialStore::new(),
+            broken: AtomicBool::new(false),
+        }
+    }
+
+    async fn wait(&self) -> Result<(), OpaqueError> {
+        time::sleep(LATENCY).await;
+        if self.broken.load(Ordering::Relaxed) {
+            return Err(OpaqueError::Storage("connection lost".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsyncCredentialStore for LatentStore {
+    async fn put_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        self.wait().await?;
+        self.records.put_password_file(credential_id, password_file)
+    }
+
+    async fn create_password_file(
+        &self,
+        credential_id: &[u8],
+        password_file: &[u8],
+    ) -> Result<(), OpaqueError> {
+        self.wait().await?;
+        self.records.create_password_file(credential_id, password_file)
+    }
+
+    async fn get_password_file(
+        &self,
+        credential_id: &[u8],
+    ) -> Result<Option<Vec<u8>>, OpaqueError> {
+        self.wait().await?;
+        self.records.get_password_file(credential_id)
+    }
+
+    async fn delete_password_file(&self, credential_id: &[u8]) -> Result<bool, OpaqueError> {
+        self.wait().await?;
+        self.records.delete_password_file(credential_id)
+    }
+
+    async fn put_server_setup(&self, setup: &[u8]) -> Result<(), OpaqueError> {
+        self.wait().await?;
+        self.records.put_server_setup(setup)
+    }
+
+    async fn get_server_setup(&self) -> Result<Option<Vec<u8>>, OpaqueError> {
+        self.wait().await?;
+        self.records.get_server_setup()
+    }
+}
+
+fn new_client(username: &str) -> OpaqueClient {
+    OpaqueClient::new(
+        username.to_string(),
+        "servername".to_string(),
+        "context".to_string(),
+    )
+}
+
+async fn new_server(store: Arc<LatentStore>) -> Arc<AsyncOpaqueServer> {
+    let server =
+        AsyncOpaqueServer::load_or_generate("servername".to_string(), "context".to_string(), store)
+            .await
+            .unwrap();
+    Arc::new(server)
+}
+
+async fn register(server: &AsyncOpaqueServer, username: &str, password: &str) {
+    let mut client = new_client(username);
+    let request = client.begin_registration(password).unwrap();
+    let response = server
+        .handle_registration_request(username, &request)
+        .await
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    server
+        .handle_registration_upload(username, &result.upload)
+        .await
+        .unwrap();
+}
+
+async fn login(
+    server: &AsyncOpaqueServer,
+    username: &str,
+    password: &str,
+) -> Result<(), OpaqueError> {
+    let mut client = new_client(username);
+    let session_id = format!("session-{}", username);
+    let request = client.begin_login(password)?;
+    let response = server
+        .handle_credential_request(username, &session_id, &request)
+        .await?;
+    let result = client.complete_login(&response)?;
+    let session_key = server
+        .handle_credential_finalization(username, &session_id, &result.credential_finalization)
+        .await?;
+    assert_eq!(session_key, result.session_key);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn register_and_login() {
+    let server = new_server(Arc::new(LatentStore::new())).await;
+    register(&server, "pippo", "ciao").await;
+    login(&server, "pippo", "ciao").await.unwrap();
+    assert!(login(&server, "pippo", "ciaoSbagliato").await.is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_logins_wait_for_the_store_together() {
+    let server = new_server(Arc::new(LatentStore::new())).await;
+    let usernames: Vec<String> = (0..8).map(|i| format!("user{}", i)).collect();
+    for username in &usernames {
+        register(&server, username, "ciao").await;
+    }
+
+    let start = Instant::now();
+    let logins: Vec<_> = usernames
+        .iter()
+        .map(|username| {
+            let server = server.clone();
+            let username = username.clone();
+            tokio::spawn(async move { login(&server, &username, "ciao").await })
+        })
+        .collect();
+    for handle in logins {
+        handle.await.unwrap().unwrap();
+    }
+
+    // one store access per login: one after the other they would take 8 times LATENCY
+    assert!(start.elapsed() < LATENCY * 4, "took {:?}", start.elapsed());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn protocol_steps_dont_stall_the_reactor() {
+    let server = new_server(Arc::new(LatentStore::new())).await;
+    register(&server, "pippo", "ciao").await;
+
+    // a single reactor thread: the heartbeat ticks only if every
+    // protocol step runs somewhere else
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let heartbeat = {
+        let ticks = ticks.clone();
+        tokio::spawn(async move {
+            let mut interval = time::interval(Duration::from_millis(10));
+            loop {
+                interval.tick().await;
+                ticks.fetch_add(1, Ordering::Relaxed);
+            }
+        })
+    };
+
+    let start = Instant::now();
+    login(&server, "pippo", "ciao").await.unwrap();
+    let elapsed = start.elapsed();
+    heartbeat.abort();
+
+    let expected = (elapsed.as_millis() / 10) as usize;
+    assert!(
+        ticks.load(Ordering::Relaxed) * 2 >= expected,
+        "{} ticks in {:?}",
+        ticks.load(Ordering::Relaxed),
+        elapsed
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn store_errors_are_reported() {
+    let store = Arc::new(LatentStore::new());
+    let server = new_server(store.clone()).await;
+    register(&server, "pippo", "ciao").await;
+
+    store.broken.store(true, Ordering::Relaxed);
+    assert!(matches!(
+        login(&server, "pippo", "ciao").await,
+        Err(OpaqueError::Storage(_))
+    ));
+}
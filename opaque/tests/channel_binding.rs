@@ -0,0 +1,94 @@
+use rust::opaque_channel_binding::{channel_binding_context, TLS_EXPORTER, TLS_SERVER_END_POINT};
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_server_facade::OpaqueServer;
+use rust::opaque_store::MemoryCredentialStore;
+
+fn registered_server() -> OpaqueServer {
+    let server = OpaqueServer::load_or_generate(
+        "server".to_string(),
+        "context".to_string(),
+        Box::new(MemoryCredentialStore::new()),
+    )
+    .unwrap();
+
+    let mut client = new_client();
+    let request = client.begin_registration("ciao").unwrap();
+    let response = server
+        .handle_registration_request("pippo", &request)
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    server
+        .handle_registration_upload("pippo", &result.upload)
+        .unwrap();
+    server
+}
+
+fn new_client() -> OpaqueClient {
+    OpaqueClient::new(
+        "pippo".to_string(),
+        "server".to_string(),
+        "context".to_string(),
+    )
+}
+
+// login with the channel binding each side sees
+fn login(server: &OpaqueServer, client_binding: &[u8], server_binding: &[u8]) -> bool {
+    let mut client = new_client();
+    client.set_context(&channel_binding_context(TLS_EXPORTER, client_binding));
+    let request = client.begin_login("ciao").unwrap();
+    let response = server
+        .handle_credential_request_with_context(
+            "pippo",
+            "login",
+            &request,
+            &channel_binding_context(TLS_EXPORTER, server_binding),
+        )
+        .unwrap();
+    match client.complete_login(&response) {
+        Ok(result) => server
+            .handle_credential_finalization("pippo", "login", &result.credential_finalization)
+            .map(|session_key| session_key == result.session_key)
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn same_channel_logs_in() {
+    let server = registered_server();
+    assert!(login(&server, b"exporter", b"exporter"));
+}
+
+#[test]
+fn other_channel_fails_the_login() {
+    let server = registered_server();
+
+    // a man in the middle terminating TLS: each side sees its own channel
+    let mut client = new_client();
+    client.set_context(&channel_binding_context(TLS_EXPORTER, b"client side"));
+    let request = client.begin_login("ciao").unwrap();
+    let response = server
+        .handle_credential_request_with_context(
+            "pippo",
+            "login",
+            &request,
+            &channel_binding_context(TLS_EXPORTER, b"server side"),
+        )
+        .unwrap();
+    assert!(client.complete_login(&response).is_err());
+
+    assert!(!login(&server, b"client side", b"server side"));
+    assert!(login(&server, b"server side", b"server side"));
+}
+
+#[test]
+fn label_and_binding_are_kept_apart() {
+    assert_ne!(
+        channel_binding_context(TLS_EXPORTER, b"binding"),
+        channel_binding_context(TLS_SERVER_END_POINT, b"binding")
+    );
+    assert_ne!(
+        channel_binding_context("ab", b"c"),
+        channel_binding_context("a", b"bc")
+    );
+}
@@ -0,0 +1,185 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process;
+
+use rust::opaque_error::OpaqueError;
+use rust::opaque_store::{CredentialStore, FileCredentialStore, MemoryCredentialStore};
+#[cfg(feature = "sqlite")]
+use rust::opaque_store_sqlite::SqliteCredentialStore;
+
+// fresh path in the temporary directory, removed if left by a previous run
+fn temp_path(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("opaque-test-{}-{}", process::id(), name));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+// behaviour every CredentialStore must have, starting empty
+fn conformance(store: &dyn CredentialStore) {
+    assert_eq!(store.get_password_file(b"pippo").unwrap(), None);
+    assert_eq!(store.get_server_setup().unwrap(), None);
+
+    store.put_password_file(b"pippo", b"password file 1").unwrap();
+    assert_eq!(
+        store.get_password_file(b"pippo").unwrap(),
+        Some(b"password file 1".to_vec())
+    );
+    store.put_password_file(b"pippo", b"password file 2").unwrap();
+    assert_eq!(
+        store.get_password_file(b"pippo").unwrap(),
+        Some(b"password file 2".to_vec())
+    );
+
+    // credential ids and password files are bytes, not strings
+    store.put_password_file(b"\x00\xff", b"").unwrap();
+    assert_eq!(store.get_password_file(b"\x00\xff").unwrap(), Some(vec![]));
+    assert_eq!(
+        store.get_password_file(b"pippo").unwrap(),
+        Some(b"password file 2".to_vec())
+    );
+
+    // registration never replaces a password file
+    assert_eq!(
+        store.create_password_file(b"pippo", b"password file 3"),
+        Err(OpaqueError::AlreadyRegistered)
+    );
+    assert_eq!(
+        store.get_password_file(b"pippo").unwrap(),
+        Some(b"password file 2".to_vec())
+    );
+    store.create_password_file(b"pluto", b"password file").unwrap();
+    assert_eq!(
+        store.get_password_file(b"pluto").unwrap(),
+        Some(b"password file".to_vec())
+    );
+
+    assert!(store.delete_password_file(b"pippo").unwrap());
+    assert!(!store.delete_password_file(b"pippo").unwrap());
+    assert_eq!(store.get_password_file(b"pippo").unwrap(), None);
+    assert_eq!(store.get_password_file(b"\x00\xff").unwrap(), Some(vec![]));
+
+    // recovery data is written together with the password file
+    assert_eq!(store.get_recovery_data(b"pluto").unwrap(), None);
+    assert_eq!(
+        store.create_credential(b"pluto", b"other file", b"recovery"),
+        Err(OpaqueError::AlreadyRegistered)
+    );
+    assert_eq!(store.get_recovery_data(b"pluto").unwrap(), None);
+    store
+        .create_credential(b"topolino", b"password file 1", b"recovery 1")
+        .unwrap();
+    assert_eq!(
+        store.get_recovery_data(b"topolino").unwrap(),
+        Some(b"recovery 1".to_vec())
+    );
+
+    // and replaced only if still the expected one
+    assert!(!store
+        .replace_credential(b"topolino", Some(b"recovery 0"), b"password file 2", b"recovery 2")
+        .unwrap());
+    assert!(!store
+        .replace_credential(b"topolino", None, b"password file 2", b"recovery 2")
+        .unwrap());
+    assert_eq!(
+        store.get_password_file(b"topolino").unwrap(),
+        Some(b"password file 1".to_vec())
+    );
+    assert!(store
+        .replace_credential(b"topolino", Some(b"recovery 1"), b"password file 2", b"recovery 2")
+        .unwrap());
+    assert_eq!(
+        store.get_password_file(b"topolino").unwrap(),
+        Some(b"password file 2".to_vec())
+    );
+    assert_eq!(
+        store.get_recovery_data(b"topolino").unwrap(),
+        Some(b"recovery 2".to_vec())
+    );
+    assert!(store
+        .replace_credential(b"pluto", None, b"password file", b"recovery")
+        .unwrap());
+
+    assert!(store.delete_password_file(b"topolino").unwrap());
+    assert_eq!(store.get_recovery_data(b"topolino").unwrap(), None);
+
+    store.put_server_setup(b"setup 1").unwrap();
+    store.put_server_setup(b"setup 2").unwrap();
+    assert_eq!(store.get_server_setup().unwrap(), Some(b"setup 2".to_vec()));
+}
+
+// what conformance leaves behind, once the store is opened again
+fn check_reopened(store: &dyn CredentialStore) {
+    assert_eq!(store.get_password_file(b"pippo").unwrap(), None);
+    assert_eq!(
+        store.get_password_file(b"pluto").unwrap(),
+        Some(b"password file".to_vec())
+    );
+    assert_eq!(store.get_password_file(b"\x00\xff").unwrap(), Some(vec![]));
+    assert_eq!(
+        store.get_recovery_data(b"pluto").unwrap(),
+        Some(b"recovery".to_vec())
+    );
+    assert_eq!(store.get_password_file(b"topolino").unwrap(), None);
+    assert_eq!(store.get_recovery_data(b"topolino").unwrap(), None);
+    assert_eq!(store.get_server_setup().unwrap(), Some(b"setup 2".to_vec()));
+}
+
+#[test]
+fn memory_store() {
+    conformance(&MemoryCredentialStore::new());
+}
+
+#[test]
+fn file_store() {
+    let path = temp_path("file-store");
+    conformance(&FileCredentialStore::open(&path).unwrap());
+    check_reopened(&FileCredentialStore::open(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn file_store_drops_truncated_entry() {
+    let path = temp_path("file-store-truncated");
+    let store = FileCredentialStore::open(&path).unwrap();
+    store.put_password_file(b"pippo", b"password file").unwrap();
+    drop(store);
+
+    // crash while appending: operation and half of the key length
+    let whole_len = fs::metadata(&path).unwrap().len();
+    OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap()
+        .write_all(&[1, 0, 0])
+        .unwrap();
+
+    let store = FileCredentialStore::open(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), whole_len);
+    assert_eq!(
+        store.get_password_file(b"pippo").unwrap(),
+        Some(b"password file".to_vec())
+    );
+
+    // entries appended afterwards are read back
+    store.put_password_file(b"pluto", b"other file").unwrap();
+    drop(store);
+    let store = FileCredentialStore::open(&path).unwrap();
+    assert_eq!(
+        store.get_password_file(b"pluto").unwrap(),
+        Some(b"other file".to_vec())
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn sqlite_store() {
+    conformance(&SqliteCredentialStore::open_in_memory().unwrap());
+
+    let path = temp_path("sqlite-store");
+    conformance(&SqliteCredentialStore::open(&path).unwrap());
+    check_reopened(&SqliteCredentialStore::open(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+}
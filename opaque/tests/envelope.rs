@@ -0,0 +1,71 @@
+use rust::opaque_envelope::{
+    decode_envelope, encode_envelope, MessageType, CIPHERSUITE_ID, ENVELOPE_VERSION,
+};
+use rust::opaque_error::OpaqueError;
+
+const MESSAGE: &[u8] = &[1, 2, 3, 4, 5, 6, 7, 8];
+
+#[test]
+fn message_comes_back() {
+    let envelope = encode_envelope(MessageType::CredentialRequest, MESSAGE);
+    assert_eq!(
+        decode_envelope(MessageType::CredentialRequest, &envelope),
+        Ok(MESSAGE)
+    );
+}
+
+#[test]
+fn other_message_type_is_refused() {
+    let envelope = encode_envelope(MessageType::RegistrationRequest, MESSAGE);
+    assert_eq!(
+        decode_envelope(MessageType::CredentialRequest, &envelope),
+        Err(OpaqueError::MessageTypeMismatch {
+            expected: MessageType::CredentialRequest,
+            found: MessageType::RegistrationRequest,
+        })
+    );
+}
+
+#[test]
+fn other_ciphersuite_is_refused() {
+    let mut envelope = encode_envelope(MessageType::CredentialRequest, MESSAGE);
+    envelope[6] = CIPHERSUITE_ID + 1;
+    assert_eq!(
+        decode_envelope(MessageType::CredentialRequest, &envelope),
+        Err(OpaqueError::CiphersuiteMismatch {
+            expected: CIPHERSUITE_ID,
+            found: CIPHERSUITE_ID + 1,
+        })
+    );
+}
+
+#[test]
+fn other_bytes_are_not_an_envelope() {
+    // a bare message, a truncated envelope and a wrong length
+    assert_eq!(
+        decode_envelope(MessageType::CredentialRequest, MESSAGE),
+        Err(OpaqueError::NotAnEnvelope)
+    );
+    let envelope = encode_envelope(MessageType::CredentialRequest, MESSAGE);
+    assert_eq!(
+        decode_envelope(MessageType::CredentialRequest, &envelope[..8]),
+        Err(OpaqueError::NotAnEnvelope)
+    );
+    assert_eq!(
+        decode_envelope(
+            MessageType::CredentialRequest,
+            &envelope[..envelope.len() - 1]
+        ),
+        Err(OpaqueError::NotAnEnvelope)
+    );
+}
+
+#[test]
+fn other_version_is_refused() {
+    let mut envelope = encode_envelope(MessageType::CredentialRequest, MESSAGE);
+    envelope[4] = ENVELOPE_VERSION + 1;
+    assert_eq!(
+        decode_envelope(MessageType::CredentialRequest, &envelope),
+        Err(OpaqueError::UnsupportedVersion(ENVELOPE_VERSION + 1))
+    );
+}
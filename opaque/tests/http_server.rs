@@ -0,0 +1,224 @@
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::thread;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+use tiny_http::Server;
+
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_http::{serve_on, MAX_BODY_LEN};
+use rust::opaque_server_facade::OpaqueServer;
+use rust::opaque_store::MemoryCredentialStore;
+
+// opaque-server on a free port of localhost, with an empty store
+fn start_server() -> SocketAddr {
+    let server = OpaqueServer::load_or_generate(
+        "servername".to_string(),
+        "context".to_string(),
+        Box::new(MemoryCredentialStore::new()),
+    )
+    .unwrap();
+    let http = Server::http("127.0.0.1:0").unwrap();
+    let addr = http.server_addr().to_ip().unwrap();
+    thread::spawn(move || serve_on(Arc::new(server), http, 2));
+    addr
+}
+
+// status code and raw body of a POST to path
+fn post_raw(addr: SocketAddr, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        path,
+        body.len()
+    )
+    .unwrap();
+    stream.write_all(body).unwrap();
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let head_len = response
+        .windows(4)
+        .position(|val| val == b"\r\n\r\n")
+        .unwrap();
+    let head = String::from_utf8_lossy(&response[..head_len]).to_string();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, response[head_len + 4..].to_owned())
+}
+
+fn post(addr: SocketAddr, path: &str, body: Value) -> (u16, Value) {
+    let (status, body) = post_raw(addr, path, body.to_string().as_bytes());
+    (status, serde_json::from_slice(&body).unwrap())
+}
+
+fn field(body: &Value, name: &str) -> Vec<u8> {
+    STANDARD.decode(body[name].as_str().unwrap()).unwrap()
+}
+
+fn new_client(username: &str) -> OpaqueClient {
+    OpaqueClient::new(
+        username.to_string(),
+        "servername".to_string(),
+        "context".to_string(),
+    )
+}
+
+fn register(addr: SocketAddr, username: &str, password: &str) -> u16 {
+    let mut client = new_client(username);
+    let request = client.begin_registration(password).unwrap();
+    let (status, body) = post(
+        addr,
+        "/register/start",
+        json!({"username": username, "registration_request": STANDARD.encode(request)}),
+    );
+    if status != 200 {
+        return status;
+    }
+
+    let result = client
+        .complete_registration(&field(&body, "registration_response"))
+        .unwrap();
+    let (status, _) = post(
+        addr,
+        "/register/finish",
+        json!({"username": username, "registration_upload": STANDARD.encode(result.upload)}),
+    );
+    status
+}
+
+// status of /login/finish, or None if the client refused the server answer
+fn login(addr: SocketAddr, username: &str, password: &str) -> Option<u16> {
+    let mut client = new_client(username);
+    let request = client.begin_login(password).unwrap();
+    let (status, body) = post(
+        addr,
+        "/login/start",
+        json!({"username": username, "credential_request": STANDARD.encode(request)}),
+    );
+    assert_eq!(status, 200);
+
+    let result = client
+        .complete_login(&field(&body, "credential_response"))
+        .ok()?;
+    let (status, _) = post(
+        addr,
+        "/login/finish",
+        json!({
+            "username": username,
+            "session_id": body["session_id"],
+            "credential_finalization": STANDARD.encode(result.credential_finalization),
+        }),
+    );
+    Some(status)
+}
+
+#[test]
+fn register_and_login() {
+    let addr = start_server();
+    assert_eq!(register(addr, "pippo", "ciao"), 200);
+    assert_eq!(login(addr, "pippo", "ciao"), Some(200));
+    assert_eq!(login(addr, "pippo", "ciaoSbagliato"), None);
+    assert_eq!(login(addr, "pluto", "ciao"), None);
+}
+
+#[test]
+fn registered_users_cant_be_registered_again() {
+    let addr = start_server();
+    assert_eq!(register(addr, "pippo", "ciao"), 200);
+    assert_eq!(register(addr, "pippo", "other"), 409);
+
+    // an upload sent without a registration start is refused as well
+    let mut client = new_client("pippo");
+    let request = client.begin_registration("other").unwrap();
+    let (status, body) = post(
+        addr,
+        "/register/start",
+        json!({"username": "pluto", "registration_request": STANDARD.encode(request)}),
+    );
+    assert_eq!(status, 200);
+    let result = client
+        .complete_registration(&field(&body, "registration_response"))
+        .unwrap();
+    let (status, _) = post(
+        addr,
+        "/register/finish",
+        json!({"username": "pippo", "registration_upload": STANDARD.encode(result.upload)}),
+    );
+    assert_eq!(status, 409);
+
+    assert_eq!(login(addr, "pippo", "ciao"), Some(200));
+}
+
+#[test]
+fn wrong_requests_are_refused() {
+    let addr = start_server();
+    let (status, _) = post(addr, "/unknown", json!({}));
+    assert_eq!(status, 404);
+    let (status, _) = post(addr, "/login/start", json!({"username": "pippo"}));
+    assert_eq!(status, 400);
+    let (status, _) = post(
+        addr,
+        "/login/finish",
+        json!({"username": "pippo", "session_id": "none", "credential_finalization": ""}),
+    );
+    assert_eq!(status, 401);
+
+    let (status, _) = post_raw(addr, "/login/start", &vec![b' '; MAX_BODY_LEN + 1]);
+    assert_eq!(status, 413);
+}
+
+#[test]
+fn malformed_messages_are_refused() {
+    let addr = start_server();
+    let garbage = STANDARD.encode(b"not a protocol message");
+
+    // a registration error is a bad request
+    let (status, body) = post(
+        addr,
+        "/register/start",
+        json!({"username": "pippo", "registration_request": garbage}),
+    );
+    assert_eq!(status, 400);
+    assert_ne!(body["error"], "login failed");
+    let (status, _) = post(
+        addr,
+        "/register/finish",
+        json!({"username": "pippo", "registration_upload": garbage}),
+    );
+    assert_eq!(status, 400);
+
+    // a login error is a failed login, without details
+    assert_eq!(register(addr, "pippo", "ciao"), 200);
+    let (status, body) = post(
+        addr,
+        "/login/start",
+        json!({"username": "pippo", "credential_request": garbage}),
+    );
+    assert_eq!(status, 401);
+    assert_eq!(body["error"], "login failed");
+
+    let mut client = new_client("pippo");
+    let request = client.begin_login("ciao").unwrap();
+    let (status, body) = post(
+        addr,
+        "/login/start",
+        json!({"username": "pippo", "credential_request": STANDARD.encode(request)}),
+    );
+    assert_eq!(status, 200);
+    let (status, body) = post(
+        addr,
+        "/login/finish",
+        json!({
+            "username": "pippo",
+            "session_id": body["session_id"],
+            "credential_finalization": garbage,
+        }),
+    );
+    assert_eq!(status, 401);
+    assert_eq!(body["error"], "login failed");
+}
@@ -0,0 +1,94 @@
+use std::time::Duration;
+
+use rust::opaque_error::OpaqueError;
+use rust::opaque_login_state::{
+    check_login_state, now_secs, unwrap_login_state, wrap_login_state, LoginStatePolicy,
+    MemoryReplayCache, ReplayCache, LOGIN_STATE_ID_LEN,
+};
+
+// state wrapped as wrap_login_state does, created at created_at
+fn wrapped_at(created_at: u64, id: [u8; LOGIN_STATE_ID_LEN], state: &[u8]) -> Vec<u8> {
+    let mut wrapped = created_at.to_be_bytes().to_vec();
+    wrapped.extend_from_slice(&id);
+    wrapped.extend_from_slice(state);
+    wrapped
+}
+
+#[test]
+fn fresh_state_comes_back() {
+    let wrapped = wrap_login_state(b"state");
+    let (header, state) = unwrap_login_state(&wrapped).unwrap();
+    assert!(header.created_at <= now_secs());
+    assert_eq!(state, b"state");
+
+    let policy = LoginStatePolicy::default();
+    assert_eq!(check_login_state(&policy, &wrapped), Ok(&b"state"[..]));
+}
+
+#[test]
+fn old_state_is_expired() {
+    let wrapped = wrapped_at(now_secs() - 1000, [1; LOGIN_STATE_ID_LEN], b"state");
+
+    let policy = LoginStatePolicy::default();
+    assert_eq!(
+        check_login_state(&policy, &wrapped),
+        Err(OpaqueError::Expired)
+    );
+
+    let policy = LoginStatePolicy {
+        max_age: Duration::from_secs(2000),
+        replay_cache: None,
+    };
+    assert_eq!(check_login_state(&policy, &wrapped), Ok(&b"state"[..]));
+}
+
+#[test]
+fn short_state_is_malformed() {
+    let wrapped = wrap_login_state(b"");
+    let policy = LoginStatePolicy::default();
+    assert_eq!(
+        check_login_state(&policy, &wrapped[..wrapped.len() - 1]),
+        Err(OpaqueError::MalformedRecord)
+    );
+}
+
+#[test]
+fn state_is_single_use_with_a_replay_cache() {
+    let wrapped = wrap_login_state(b"state");
+
+    // no cache: nothing to check against
+    let policy = LoginStatePolicy::default();
+    assert!(check_login_state(&policy, &wrapped).is_ok());
+    assert!(check_login_state(&policy, &wrapped).is_ok());
+
+    let cache = MemoryReplayCache::new();
+    let policy = LoginStatePolicy {
+        replay_cache: Some(&cache),
+        ..Default::default()
+    };
+    assert!(check_login_state(&policy, &wrapped).is_ok());
+    assert_eq!(
+        check_login_state(&policy, &wrapped),
+        Err(OpaqueError::Replayed)
+    );
+
+    // other states are still accepted
+    let other = wrap_login_state(b"state");
+    assert!(check_login_state(&policy, &other).is_ok());
+}
+
+#[test]
+fn replay_cache_drops_expired_ids() {
+    let cache = MemoryReplayCache::new();
+    let live = [1; LOGIN_STATE_ID_LEN];
+    let expired = [2; LOGIN_STATE_ID_LEN];
+
+    assert!(cache.check_and_insert(&live, now_secs() + 60));
+    assert!(!cache.check_and_insert(&live, now_secs() + 60));
+
+    // dropped by the next insert, once past its expiry
+    assert!(cache.check_and_insert(&expired, now_secs() - 1));
+    assert!(cache.check_and_insert(&expired, now_secs() - 1));
+
+    assert!(!cache.check_and_insert(&live, now_secs() + 60));
+}
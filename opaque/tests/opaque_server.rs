@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_config::ProtocolConfig;
+use rust::opaque_error::OpaqueError;
+use rust::opaque_server_facade::OpaqueServer;
+use rust::opaque_store::{CredentialStore, MemoryCredentialStore};
+
+fn new_server() -> OpaqueServer {
+    OpaqueServer::load_or_generate(
+        "server".to_string(),
+        "context".to_string(),
+        Box::new(MemoryCredentialStore::new()),
+    )
+    .unwrap()
+}
+
+fn new_client(username: &str) -> OpaqueClient {
+    OpaqueClient::new(
+        username.to_string(),
+        "server".to_string(),
+        "context".to_string(),
+    )
+}
+
+fn register(server: &OpaqueServer, username: &str, password: &str) -> Result<(), OpaqueError> {
+    let mut client = new_client(username);
+    let request = client.begin_registration(password)?;
+    let response = server.handle_registration_request(username, &request)?;
+    let result = client.complete_registration(&response)?;
+    server.handle_registration_upload(username, &result.upload)
+}
+
+#[test]
+fn login_sessions_of_different_users_are_kept_apart() {
+    let server = new_server();
+    register(&server, "pippo", "ciao").unwrap();
+    register(&server, "pluto", "hello").unwrap();
+
+    // both users pick the same session id
+    let mut pippo = new_client("pippo");
+    let request = pippo.begin_login("ciao").unwrap();
+    let pippo_response = server
+        .handle_credential_request("pippo", "session", &request)
+        .unwrap();
+
+    let mut pluto = new_client("pluto");
+    let request = pluto.begin_login("hello").unwrap();
+    let pluto_response = server
+        .handle_credential_request("pluto", "session", &request)
+        .unwrap();
+
+    let pippo_login = pippo.complete_login(&pippo_response).unwrap();
+    let pluto_login = pluto.complete_login(&pluto_response).unwrap();
+    let session_key = server
+        .handle_credential_finalization("pippo", "session", &pippo_login.credential_finalization)
+        .unwrap();
+    assert_eq!(session_key, pippo_login.session_key);
+    let session_key = server
+        .handle_credential_finalization("pluto", "session", &pluto_login.credential_finalization)
+        .unwrap();
+    assert_eq!(session_key, pluto_login.session_key);
+
+    // the login state is gone once used
+    assert!(matches!(
+        server.handle_credential_finalization(
+            "pippo",
+            "session",
+            &pippo_login.credential_finalization
+        ),
+        Err(OpaqueError::UnknownSession)
+    ));
+}
+
+#[test]
+fn registration_never_replaces_a_password_file() {
+    let server = new_server();
+    register(&server, "pippo", "ciao").unwrap();
+
+    let mut client = new_client("pippo");
+    let request = client.begin_registration("other").unwrap();
+    assert_eq!(
+        server.handle_registration_request("pippo", &request),
+        Err(OpaqueError::AlreadyRegistered)
+    );
+
+    // an upload sent without its registration request is refused as well
+    let other = new_server();
+    let response = other
+        .handle_registration_request("pippo", &request)
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    assert_eq!(
+        server.handle_registration_upload("pippo", &result.upload),
+        Err(OpaqueError::AlreadyRegistered)
+    );
+
+    let mut client = new_client("pippo");
+    let request = client.begin_login("ciao").unwrap();
+    let response = server
+        .handle_credential_request("pippo", "session", &request)
+        .unwrap();
+    assert!(client.complete_login(&response).is_ok());
+}
+
+fn login(server: &OpaqueServer, username: &str, password: &str) -> bool {
+    let mut client = new_client(username);
+    let request = client.begin_login(password).unwrap();
+    let response = server
+        .handle_credential_request(username, "login", &request)
+        .unwrap();
+    match client.complete_login(&response) {
+        Ok(result) => server
+            .handle_credential_finalization(username, "login", &result.credential_finalization)
+            .map(|session_key| session_key == result.session_key)
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn password_change_replaces_the_password() {
+    let server = new_server();
+    register(&server, "pippo", "ciao").unwrap();
+
+    let mut client = new_client("pippo");
+    let request = client.begin_password_change("ciao", "nuova").unwrap();
+    let response = server
+        .handle_password_change_request("pippo", "change", &request)
+        .unwrap();
+    let result = client.complete_password_change(&response).unwrap();
+    server
+        .handle_password_change_finalization("pippo", "change", &result.finalization)
+        .unwrap();
+
+    assert!(login(&server, "pippo", "nuova"));
+    assert!(!login(&server, "pippo", "ciao"));
+}
+
+#[test]
+fn registration_with_other_identities_is_refused() {
+    let server = new_server();
+
+    // the client believes it talks to another server
+    let mut client = OpaqueClient::new(
+        "pippo".to_string(),
+        "otherserver".to_string(),
+        "context".to_string(),
+    );
+    let request = client.begin_registration("ciao").unwrap();
+    let response = server
+        .handle_registration_request("pippo", &request)
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    assert_eq!(
+        server.handle_registration_upload("pippo", &result.upload),
+        Err(OpaqueError::IdentityMismatch)
+    );
+
+    // nothing was saved: pippo can still register
+    register(&server, "pippo", "ciao").unwrap();
+    assert!(login(&server, "pippo", "ciao"));
+}
+
+// login of the credential registered under credential_id, as identity
+fn login_as(
+    server: &OpaqueServer,
+    client: &mut OpaqueClient,
+    credential_id: &str,
+    identity: &str,
+) -> bool {
+    let request = client.begin_login("ciao").unwrap();
+    let response = server
+        .handle_credential_request_with_identity(credential_id, identity, "login", &request)
+        .unwrap();
+    match client.complete_login(&response) {
+        Ok(result) => server
+            .handle_credential_finalization(credential_id, "login", &result.credential_finalization)
+            .map(|session_key| session_key == result.session_key)
+            .unwrap_or(false),
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn identity_change_keeps_the_password_file() {
+    let store = Arc::new(MemoryCredentialStore::new());
+    let server = OpaqueServer::load_or_generate(
+        "server".to_string(),
+        "context".to_string(),
+        Box::new(store.clone()),
+    )
+    .unwrap();
+
+    // registered under a stable id, not under the email the user logs in with
+    let mut client = OpaqueClient::with_identity(
+        "user-1".to_string(),
+        "pippo@example.com".to_string(),
+        ProtocolConfig::new("server", b"context"),
+    );
+    let request = client.begin_registration("ciao").unwrap();
+    let response = server
+        .handle_registration_request("user-1", &request)
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    server
+        .handle_registration_upload("user-1", &result.upload)
+        .unwrap();
+    let password_file = store.get_password_file(b"user-1").unwrap();
+
+    assert!(login_as(
+        &server,
+        &mut client,
+        "user-1",
+        "pippo@example.com"
+    ));
+
+    client.set_identity("pippo@example.org");
+    assert!(login_as(
+        &server,
+        &mut client,
+        "user-1",
+        "pippo@example.org"
+    ));
+    assert_eq!(store.get_password_file(b"user-1").unwrap(), password_file);
+
+    // both sides must use the same identity
+    assert!(!login_as(
+        &server,
+        &mut client,
+        "user-1",
+        "pippo@example.com"
+    ));
+}
@@ -0,0 +1,112 @@
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_password::{
+    normalize_password_with, set_password_normalization, PasswordNormalization,
+};
+use rust::opaque_server_facade::OpaqueServer;
+use rust::opaque_store::MemoryCredentialStore;
+
+// café, composed and decomposed
+const COMPOSED: &str = "caf\u{e9}";
+const DECOMPOSED: &str = "cafe\u{301}";
+
+fn nfc(password: &str) -> String {
+    normalize_password_with(password, PasswordNormalization::Nfc)
+}
+
+fn nfkc(password: &str) -> String {
+    normalize_password_with(password, PasswordNormalization::Nfkc)
+}
+
+fn none(password: &str) -> String {
+    normalize_password_with(password, PasswordNormalization::None)
+}
+
+#[test]
+fn composed_and_decomposed_are_the_same_password() {
+    assert_eq!(nfc(DECOMPOSED), COMPOSED);
+    assert_eq!(nfc(COMPOSED), COMPOSED);
+    assert_eq!(nfkc(DECOMPOSED), COMPOSED);
+    assert_eq!(none(DECOMPOSED), DECOMPOSED);
+}
+
+#[test]
+fn hangul_jamo_are_composed() {
+    // U+1100 U+1161 U+11A8 is the syllable U+AC01
+    assert_eq!(nfc("\u{1100}\u{1161}\u{11A8}"), "\u{AC01}");
+    assert_eq!(nfkc("\u{1100}\u{1161}\u{11A8}"), "\u{AC01}");
+    assert_eq!(nfc("\u{AC01}"), "\u{AC01}");
+}
+
+#[test]
+fn angstrom_sign_is_the_letter() {
+    assert_eq!(nfc("\u{212B}"), "\u{C5}");
+    assert_eq!(nfc("A\u{30A}"), "\u{C5}");
+    assert_eq!(none("\u{212B}"), "\u{212B}");
+}
+
+#[test]
+fn ligatures_are_split_by_nfkc_only() {
+    assert_eq!(nfkc("\u{FB01}le"), "file");
+    assert_eq!(nfc("\u{FB01}le"), "\u{FB01}le");
+}
+
+#[test]
+fn non_ascii_spaces_are_plain_spaces() {
+    assert_eq!(nfc("pass\u{A0}word"), "pass word");
+    assert_eq!(nfc("pass\u{3000}word"), "pass word");
+    assert_eq!(nfkc("pass\u{A0}word"), "pass word");
+    assert_eq!(none("pass\u{A0}word"), "pass\u{A0}word");
+}
+
+fn new_client() -> OpaqueClient {
+    OpaqueClient::new(
+        "pippo".to_string(),
+        "server".to_string(),
+        "context".to_string(),
+    )
+}
+
+fn login(server: &OpaqueServer, password: &str) -> bool {
+    let mut client = new_client();
+    let request = client.begin_login(password).unwrap();
+    let response = match server.handle_credential_request("pippo", "session", &request) {
+        Ok(val) => val,
+        Err(_) => return false,
+    };
+    match client.complete_login(&response) {
+        Ok(login) => server
+            .handle_credential_finalization("pippo", "session", &login.credential_finalization)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+// the only test changing the normalization of the process
+#[test]
+fn decomposed_password_logs_in_after_composed_registration() {
+    let server = OpaqueServer::load_or_generate(
+        "server".to_string(),
+        "context".to_string(),
+        Box::new(MemoryCredentialStore::new()),
+    )
+    .unwrap();
+
+    set_password_normalization(PasswordNormalization::Nfc);
+    let mut client = new_client();
+    let request = client.begin_registration(COMPOSED).unwrap();
+    let response = server
+        .handle_registration_request("pippo", &request)
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    server
+        .handle_registration_upload("pippo", &result.upload)
+        .unwrap();
+
+    assert!(login(&server, DECOMPOSED));
+    assert!(login(&server, COMPOSED));
+
+    // without normalization the two spellings are different passwords
+    set_password_normalization(PasswordNormalization::None);
+    assert!(!login(&server, DECOMPOSED));
+    assert!(login(&server, COMPOSED));
+}
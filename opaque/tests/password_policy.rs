@@ -0,0 +1,58 @@
+use std::sync::Arc;
+
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_error::OpaqueError;
+use rust::opaque_password_policy::{
+    set_default_password_policy, set_password_policy, DefaultPasswordPolicy, PasswordIssue,
+};
+
+fn new_client() -> OpaqueClient {
+    OpaqueClient::new(
+        "pippo".to_string(),
+        "servername".to_string(),
+        "context".to_string(),
+    )
+}
+
+// a single test: the policy is shared by the whole process
+#[test]
+fn process_policy_applies_to_clients_without_their_own() {
+    let mut client = new_client();
+    assert!(client.begin_registration("ciao").is_ok());
+
+    set_default_password_policy(true);
+    assert!(matches!(
+        client.begin_registration("ciao"),
+        Err(OpaqueError::WeakPassword(_))
+    ));
+    assert!(client
+        .begin_registration("correct horse battery staple")
+        .is_ok());
+    // the username is the one of the client
+    assert_eq!(
+        client.begin_registration("Pippo-2024!"),
+        Err(OpaqueError::WeakPassword(vec![PasswordIssue::ContainsUsername]))
+    );
+
+    // a policy of the client wins over the one of the process
+    let mut lenient = new_client();
+    lenient.set_password_policy(Box::new(DefaultPasswordPolicy {
+        min_length: 4,
+        min_classes: 1,
+        reject_common: false,
+        reject_username: false,
+    }));
+    assert!(lenient.begin_registration("ciao").is_ok());
+
+    set_password_policy(Some(Arc::new(DefaultPasswordPolicy {
+        min_length: 30,
+        ..DefaultPasswordPolicy::default()
+    })));
+    assert!(matches!(
+        client.begin_registration("correct horse battery staple"),
+        Err(OpaqueError::WeakPassword(_))
+    ));
+
+    set_default_password_policy(false);
+    assert!(client.begin_registration("ciao").is_ok());
+}
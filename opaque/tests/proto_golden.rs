@@ -0,0 +1,116 @@
+use std::fs;
+use std::path::Path;
+
+use prost::Message as _;
+
+use rust::opaque_envelope::{Message, MessageType};
+use rust::opaque_error::OpaqueError;
+use rust::opaque_proto::{
+    ErrorCode, ErrorResponse, LoginFinishRequest, LoginFinishResponse, LoginStartRequest,
+    LoginStartResponse, RegistrationFinishRequest, RegistrationFinishResponse,
+    RegistrationStartRequest, RegistrationStartResponse,
+};
+
+// bytes of the messages in the golden files: 8 consecutive values,
+// starting from a different one for each message
+fn data(first: u8) -> Vec<u8> {
+    (first..first + 8).collect()
+}
+
+// value encoded as proto/golden/<name>.bin, and decoded back from it
+fn check_golden<T: prost::Message + Default + PartialEq + std::fmt::Debug>(name: &str, value: T) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("proto/golden")
+        .join(format!("{}.bin", name));
+    let golden = fs::read(&path).unwrap();
+    assert_eq!(value.encode_to_vec(), golden, "encoding of {}", name);
+    assert_eq!(T::decode(golden.as_slice()).unwrap(), value, "decoding of {}", name);
+}
+
+#[test]
+fn registration_messages() {
+    check_golden(
+        "registration_start_request",
+        RegistrationStartRequest::new(
+            "pippo".to_string(),
+            Message::new(MessageType::RegistrationRequest, data(1)),
+        )
+        .unwrap(),
+    );
+    check_golden(
+        "registration_start_response",
+        RegistrationStartResponse::new(Message::new(
+            MessageType::RegistrationResponse,
+            data(0x10),
+        ))
+        .unwrap(),
+    );
+    check_golden(
+        "registration_finish_request",
+        RegistrationFinishRequest::new(
+            "pippo".to_string(),
+            Message::new(MessageType::RegistrationUpload, data(0x20)),
+        )
+        .unwrap(),
+    );
+    check_golden("registration_finish_response", RegistrationFinishResponse {});
+}
+
+#[test]
+fn login_messages() {
+    check_golden(
+        "login_start_request",
+        LoginStartRequest::new(
+            "pippo".to_string(),
+            Message::new(MessageType::CredentialRequest, data(0x30)),
+        )
+        .unwrap(),
+    );
+    check_golden(
+        "login_start_response",
+        LoginStartResponse::new(
+            "session-1".to_string(),
+            Message::new(MessageType::CredentialResponse, data(0x40)),
+        )
+        .unwrap(),
+    );
+    check_golden(
+        "login_finish_request",
+        LoginFinishRequest::new(
+            "pippo".to_string(),
+            "session-1".to_string(),
+            Message::new(MessageType::CredentialFinalization, data(0x50)),
+        )
+        .unwrap(),
+    );
+    check_golden("login_finish_response", LoginFinishResponse {});
+}
+
+#[test]
+fn error_response() {
+    let response = ErrorResponse::from(&OpaqueError::UnknownSession);
+    assert_eq!(response.code, ErrorCode::UnknownSession as i32);
+    check_golden("error_response", response);
+}
+
+#[test]
+fn messages_keep_their_type() {
+    let request = LoginStartRequest::decode(
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("proto/golden/login_start_request.bin"))
+            .unwrap()
+            .as_slice(),
+    )
+    .unwrap();
+    assert_eq!(
+        request.message(),
+        Message::new(MessageType::CredentialRequest, data(0x30))
+    );
+
+    assert!(matches!(
+        LoginStartRequest::new(
+            "pippo".to_string(),
+            Message::new(MessageType::RegistrationRequest, data(0x30)),
+        ),
+        Err(OpaqueError::MessageTypeMismatch { .. })
+    ));
+}
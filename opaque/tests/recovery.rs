@@ -0,0 +1,223 @@
+use rust::opaque_client_facade::OpaqueClient;
+use rust::opaque_error::OpaqueError;
+use rust::opaque_recovery::{
+    generate_recovery_codes, recovery_secret, secret_from_export_key, RecoveryResult,
+};
+use rust::opaque_server_facade::OpaqueServer;
+use rust::opaque_store::MemoryCredentialStore;
+
+fn new_server() -> OpaqueServer {
+    OpaqueServer::load_or_generate(
+        "server".to_string(),
+        "context".to_string(),
+        Box::new(MemoryCredentialStore::new()),
+    )
+    .unwrap()
+}
+
+fn new_client(username: &str) -> OpaqueClient {
+    OpaqueClient::new(
+        username.to_string(),
+        "server".to_string(),
+        "context".to_string(),
+    )
+}
+
+// registration of pippo with 2 recovery codes: returns codes and secret
+fn register(server: &OpaqueServer, password: &str) -> (Vec<String>, Vec<u8>) {
+    let mut client = new_client("pippo");
+    let request = client.begin_registration(password).unwrap();
+    let response = server.handle_registration_request("pippo", &request).unwrap();
+    let result = client.complete_registration(&response).unwrap();
+
+    let secret = recovery_secret(&result.export_key);
+    let codes = generate_recovery_codes(&secret, "pippo", 2);
+    server
+        .handle_registration_upload_with_recovery("pippo", &result.upload, &codes.records)
+        .unwrap();
+    (codes.codes, secret)
+}
+
+// recovery secret of pippo after a login with password
+fn login(server: &OpaqueServer, password: &str) -> Result<Vec<u8>, OpaqueError> {
+    let mut client = new_client("pippo");
+    let request = client.begin_login(password)?;
+    let response = server.handle_credential_request("pippo", "session", &request)?;
+    let result = client.complete_login(&response)?;
+    let session_key =
+        server.handle_credential_finalization("pippo", "session", &result.credential_finalization)?;
+    assert_eq!(session_key, result.session_key);
+
+    let wrapped = server.recovery_wrapped_secret("pippo")?;
+    secret_from_export_key(&result.export_key, wrapped.as_deref())
+}
+
+fn recover(
+    server: &OpaqueServer,
+    code: &str,
+    new_password: &str,
+) -> Result<RecoveryResult, OpaqueError> {
+    let mut client = new_client("pippo");
+    let request = client.begin_recovery(code, new_password)?;
+    let response = server.handle_recovery_request("pippo", &request)?;
+    let result = client.complete_recovery(&response)?;
+    server.handle_recovery_finalization("pippo", &result.finalization)?;
+    Ok(result)
+}
+
+#[test]
+fn recover_and_login() {
+    let server = new_server();
+    let (codes, secret) = register(&server, "ciao");
+    assert_eq!(login(&server, "ciao").unwrap(), secret);
+
+    // the code is typed back without dashes and in lower case
+    let typed = codes[0].replace('-', "").to_lowercase();
+    let result = recover(&server, &typed, "nuova").unwrap();
+    assert_eq!(result.secret, secret);
+    assert_eq!(server.recovery_codes_left("pippo").unwrap(), 1);
+
+    assert_eq!(login(&server, "nuova").unwrap(), secret);
+    assert!(login(&server, "ciao").is_err());
+}
+
+#[test]
+fn recovery_codes_are_single_use() {
+    let server = new_server();
+    let (codes, secret) = register(&server, "ciao");
+    recover(&server, &codes[0], "nuova").unwrap();
+
+    assert!(matches!(
+        recover(&server, &codes[0], "altra"),
+        Err(OpaqueError::InvalidRecoveryCode)
+    ));
+    assert_eq!(login(&server, "nuova").unwrap(), secret);
+
+    // a finalization sent twice is refused the second time
+    let mut client = new_client("pippo");
+    let request = client.begin_recovery(&codes[1], "altra").unwrap();
+    let response = server.handle_recovery_request("pippo", &request).unwrap();
+    let result = client.complete_recovery(&response).unwrap();
+    server
+        .handle_recovery_finalization("pippo", &result.finalization)
+        .unwrap();
+    assert_eq!(
+        server.handle_recovery_finalization("pippo", &result.finalization),
+        Err(OpaqueError::InvalidRecoveryCode)
+    );
+    assert_eq!(server.recovery_codes_left("pippo").unwrap(), 0);
+    assert_eq!(login(&server, "altra").unwrap(), secret);
+}
+
+#[test]
+fn wrong_codes_change_nothing() {
+    let server = new_server();
+    let (codes, _) = register(&server, "ciao");
+
+    assert!(matches!(
+        recover(&server, "AAAAA-AAAAA-AAAAA-AAAAA", "nuova"),
+        Err(OpaqueError::InvalidRecoveryCode)
+    ));
+    // codes are bound to the username
+    let mut client = new_client("pluto");
+    let request = client.begin_recovery(&codes[0], "nuova").unwrap();
+    assert!(matches!(
+        server.handle_recovery_request("pippo", &request),
+        Err(OpaqueError::InvalidRecoveryCode)
+    ));
+
+    assert_eq!(server.recovery_codes_left("pippo").unwrap(), 2);
+    assert!(login(&server, "ciao").is_ok());
+}
+
+#[test]
+fn secret_survives_password_changes() {
+    let server = new_server();
+    let (codes, secret) = register(&server, "ciao");
+
+    let change = |current: &str, new: &str, session_id: &str| {
+        let mut client = new_client("pippo");
+        let request = client.begin_password_change(current, new).unwrap();
+        let response = server
+            .handle_password_change_request("pippo", session_id, &request)
+            .unwrap();
+        let result = client.complete_password_change(&response).unwrap();
+        server
+            .handle_password_change_finalization("pippo", session_id, &result.finalization)
+            .unwrap();
+        result.recovery_secret
+    };
+
+    // a change before and after a recovery
+    assert_eq!(change("ciao", "nuova", "change-1"), secret);
+    assert_eq!(login(&server, "nuova").unwrap(), secret);
+    recover(&server, &codes[0], "altra").unwrap();
+    assert_eq!(change("altra", "ultima", "change-2"), secret);
+    assert_eq!(login(&server, "ultima").unwrap(), secret);
+
+    // the code left still opens the same secret
+    assert_eq!(recover(&server, &codes[1], "ancora").unwrap().secret, secret);
+}
+
+#[test]
+fn password_change_without_recovery_codes_keeps_the_secret() {
+    let server = new_server();
+    let mut client = new_client("pippo");
+    let request = client.begin_registration("ciao").unwrap();
+    let response = server.handle_registration_request("pippo", &request).unwrap();
+    let registration = client.complete_registration(&response).unwrap();
+    server
+        .handle_registration_upload("pippo", &registration.upload)
+        .unwrap();
+    let secret = recovery_secret(&registration.export_key);
+
+    let request = client.begin_password_change("ciao", "nuova").unwrap();
+    let response = server
+        .handle_password_change_request("pippo", "change", &request)
+        .unwrap();
+    // nothing wrapped yet: the secret is derived from the password
+    assert!(response.wrapped_secret.is_empty());
+    let result = client.complete_password_change(&response).unwrap();
+    server
+        .handle_password_change_finalization("pippo", "change", &result.finalization)
+        .unwrap();
+    assert_eq!(result.recovery_secret, secret);
+    assert_eq!(login(&server, "nuova").unwrap(), secret);
+    assert_eq!(server.recovery_codes_left("pippo").unwrap(), 0);
+}
+
+#[test]
+fn password_change_with_a_broken_wrapped_secret_fails() {
+    let server = new_server();
+    let (_, secret) = register(&server, "ciao");
+
+    let change = |current: &str,
+                  new: &str,
+                  session_id: &str,
+                  tamper: bool|
+     -> Result<Vec<u8>, OpaqueError> {
+        let mut client = new_client("pippo");
+        let request = client.begin_password_change(current, new).unwrap();
+        let mut response = server
+            .handle_password_change_request("pippo", session_id, &request)
+            .unwrap();
+        if tamper {
+            let last = response.wrapped_secret.len() - 1;
+            response.wrapped_secret[last] ^= 1;
+        }
+        let result = client.complete_password_change(&response)?;
+        server.handle_password_change_finalization("pippo", session_id, &result.finalization)?;
+        Ok(result.recovery_secret)
+    };
+
+    // the first change wraps the secret under the new password
+    assert_eq!(change("ciao", "nuova", "change-1", false).unwrap(), secret);
+
+    // no other secret takes the place of the one that doesn't open
+    assert_eq!(
+        change("nuova", "altra", "change-2", true),
+        Err(OpaqueError::DecryptionFailed)
+    );
+    assert_eq!(login(&server, "nuova").unwrap(), secret);
+    assert!(login(&server, "altra").is_err());
+}
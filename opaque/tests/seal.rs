@@ -0,0 +1,169 @@
+use rust::opaque_error::OpaqueError;
+use rust::opaque_seal::{reseal, seal, sealed_header, unseal, Kek, RecordType, SEAL_VERSION};
+
+fn kek(id: u32, byte: u8) -> Kek {
+    Kek::new(id, &[byte; 32]).unwrap()
+}
+
+#[test]
+fn record_comes_back() {
+    let key = kek(1, 7);
+    let sealed = seal(&key, RecordType::PasswordFile, "pippo", b"password file");
+
+    let header = sealed_header(&sealed).unwrap();
+    assert_eq!(header.version, SEAL_VERSION);
+    assert_eq!(header.record_type, RecordType::PasswordFile);
+    assert_eq!(header.kek_id, 1);
+    assert_eq!(
+        unseal(&key, RecordType::PasswordFile, "pippo", &sealed),
+        Ok(b"password file".to_vec())
+    );
+}
+
+#[test]
+fn kek_must_be_32_bytes() {
+    assert!(matches!(
+        Kek::new(1, &[7; 16]),
+        Err(OpaqueError::InvalidKek)
+    ));
+    assert!(matches!(
+        Kek::new(1, &[7; 33]),
+        Err(OpaqueError::InvalidKek)
+    ));
+}
+
+#[test]
+fn record_is_bound_to_username() {
+    let key = kek(1, 7);
+    let sealed = seal(&key, RecordType::PasswordFile, "pippo", b"password file");
+    assert_eq!(
+        unseal(&key, RecordType::PasswordFile, "pluto", &sealed),
+        Err(OpaqueError::DecryptionFailed)
+    );
+}
+
+#[test]
+fn record_is_bound_to_record_type() {
+    let key = kek(1, 7);
+    let sealed = seal(&key, RecordType::PasswordFile, "pippo", b"password file");
+    assert_eq!(
+        unseal(&key, RecordType::ServerSetup, "pippo", &sealed),
+        Err(OpaqueError::RecordTypeMismatch)
+    );
+
+    // rewriting the record type in the header breaks the tag
+    let mut forged = sealed.clone();
+    forged[1] = RecordType::ServerSetup as u8;
+    assert_eq!(
+        unseal(&key, RecordType::ServerSetup, "pippo", &forged),
+        Err(OpaqueError::DecryptionFailed)
+    );
+}
+
+#[test]
+fn record_is_bound_to_kek() {
+    let sealed = seal(
+        &kek(1, 7),
+        RecordType::PasswordFile,
+        "pippo",
+        b"password file",
+    );
+    assert_eq!(
+        unseal(&kek(2, 7), RecordType::PasswordFile, "pippo", &sealed),
+        Err(OpaqueError::KekMismatch {
+            expected: 2,
+            found: 1
+        })
+    );
+    assert_eq!(
+        unseal(&kek(1, 8), RecordType::PasswordFile, "pippo", &sealed),
+        Err(OpaqueError::DecryptionFailed)
+    );
+}
+
+#[test]
+fn tampered_record_is_refused() {
+    let key = kek(1, 7);
+    let sealed = seal(&key, RecordType::PasswordFile, "pippo", b"password file");
+
+    // ciphertext, nonce and kek id
+    for i in [sealed.len() - 1, 10, 5] {
+        let mut tampered = sealed.clone();
+        tampered[i] ^= 1;
+        assert!(unseal(&key, RecordType::PasswordFile, "pippo", &tampered).is_err());
+    }
+}
+
+#[test]
+fn truncated_record_is_refused() {
+    let key = kek(1, 7);
+    let sealed = seal(&key, RecordType::PasswordFile, "pippo", b"password file");
+
+    assert_eq!(
+        unseal(&key, RecordType::PasswordFile, "pippo", &sealed[..20]),
+        Err(OpaqueError::MalformedRecord)
+    );
+    assert_eq!(
+        unseal(&key, RecordType::PasswordFile, "pippo", &[]),
+        Err(OpaqueError::MalformedRecord)
+    );
+    assert_eq!(
+        unseal(
+            &key,
+            RecordType::PasswordFile,
+            "pippo",
+            &sealed[..sealed.len() - 1]
+        ),
+        Err(OpaqueError::DecryptionFailed)
+    );
+}
+
+#[test]
+fn other_version_is_refused() {
+    let key = kek(1, 7);
+    let mut sealed = seal(&key, RecordType::PasswordFile, "pippo", b"password file");
+    sealed[0] = SEAL_VERSION + 1;
+    assert_eq!(
+        unseal(&key, RecordType::PasswordFile, "pippo", &sealed),
+        Err(OpaqueError::UnsupportedVersion(SEAL_VERSION + 1))
+    );
+}
+
+#[test]
+fn reseal_moves_record_to_new_kek() {
+    let old_key = kek(1, 7);
+    let new_key = kek(2, 8);
+    let sealed = seal(
+        &old_key,
+        RecordType::PasswordFile,
+        "pippo",
+        b"password file",
+    );
+
+    let resealed = reseal(
+        &old_key,
+        &new_key,
+        RecordType::PasswordFile,
+        "pippo",
+        &sealed,
+    )
+    .unwrap();
+    assert_eq!(sealed_header(&resealed).unwrap().kek_id, 2);
+    assert_eq!(
+        unseal(&new_key, RecordType::PasswordFile, "pippo", &resealed),
+        Ok(b"password file".to_vec())
+    );
+    assert!(unseal(&old_key, RecordType::PasswordFile, "pippo", &resealed).is_err());
+
+    // reseal checks the record like unseal does
+    assert_eq!(
+        reseal(
+            &old_key,
+            &new_key,
+            RecordType::PasswordFile,
+            "pluto",
+            &sealed
+        ),
+        Err(OpaqueError::DecryptionFailed)
+    );
+}
@@ -0,0 +1,114 @@
+// run for each ciphersuite: cargo test --features serde
+// and cargo test --features serde,ristretto255
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use rust::opaque_client_facade::{
+    client_login_finish, client_login_start, ClientLoginStart, ClientLoginState, OpaqueClient,
+};
+use rust::opaque_config::ProtocolConfig;
+use rust::opaque_envelope::{envelope_header, EnvelopeHeader, Message, MessageType, CIPHERSUITE_ID};
+use rust::opaque_login_state::{LoginStateHeader, LoginStatePolicy};
+use rust::opaque_server_facade::{OpaqueServer, ServerLoginState};
+use rust::opaque_store::MemoryCredentialStore;
+
+// value written and read back in a human-readable format (JSON)
+// and in a binary one (bincode), each time equal to the original
+fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+    let json = serde_json::to_vec(value).unwrap();
+    let from_json: T = serde_json::from_slice(&json).unwrap();
+    assert_eq!(serde_json::to_vec(&from_json).unwrap(), json);
+
+    let binary = bincode::serialize(value).unwrap();
+    let from_binary: T = bincode::deserialize(&binary).unwrap();
+    assert_eq!(bincode::serialize(&from_binary).unwrap(), binary);
+    from_binary
+}
+
+fn config() -> ProtocolConfig {
+    ProtocolConfig::new("servername", b"context")
+}
+
+fn registered_server() -> OpaqueServer {
+    let server = OpaqueServer::load_or_generate(
+        "servername".to_string(),
+        "context".to_string(),
+        Box::new(MemoryCredentialStore::new()),
+    )
+    .unwrap();
+
+    let mut client = OpaqueClient::with_config("pippo".to_string(), config());
+    let request = client.begin_registration("ciao").unwrap();
+    let response = server.handle_registration_request("pippo", &request).unwrap();
+    let result = round_trip(&client.complete_registration(&response).unwrap());
+    server
+        .handle_registration_upload("pippo", &result.upload)
+        .unwrap();
+    server
+}
+
+#[test]
+fn bytes_are_base64_in_json() {
+    let state = ClientLoginState {
+        state: vec![0, 1, 2, 0xff],
+    };
+    assert_eq!(
+        serde_json::to_string(&state).unwrap(),
+        r#"{"state":"AAEC/w=="}"#
+    );
+}
+
+#[test]
+fn login_states_round_trip() {
+    let server = registered_server();
+
+    // both states leave the process between the two login steps
+    let start: ClientLoginStart = round_trip(&client_login_start("ciao").unwrap());
+    let (response, server_state) = server
+        .handle_credential_request_stateless("pippo", &start.credential_request)
+        .unwrap();
+    let server_state: ServerLoginState = round_trip(&server_state);
+    let header: LoginStateHeader = round_trip(&server_state.header().unwrap());
+    assert_eq!(header, server_state.header().unwrap());
+
+    let login = client_login_finish("ciao", &response, &start.state, "pippo", &config()).unwrap();
+    let login = round_trip(&login);
+    let session_key = server
+        .handle_credential_finalization_stateless(
+            &server_state,
+            &login.credential_finalization,
+            &LoginStatePolicy::default(),
+        )
+        .unwrap();
+    assert_eq!(session_key, login.session_key);
+}
+
+#[test]
+fn messages_round_trip() {
+    let start = client_login_start("ciao").unwrap();
+    let message = Message::new(MessageType::CredentialRequest, start.credential_request);
+    assert_eq!(round_trip(&message), message);
+
+    let header: EnvelopeHeader = round_trip(&envelope_header(&message.to_envelope()).unwrap());
+    assert_eq!(header.ciphersuite, CIPHERSUITE_ID);
+    assert_eq!(header.message_type, MessageType::CredentialRequest);
+}
+
+#[test]
+fn password_change_round_trip() {
+    let server = registered_server();
+    let mut client = OpaqueClient::with_config("pippo".to_string(), config());
+
+    let request = round_trip(&client.begin_password_change("ciao", "nuova").unwrap());
+    let response = round_trip(
+        &server
+            .handle_password_change_request("pippo", "session", &request)
+            .unwrap(),
+    );
+    let result = client.complete_password_change(&response).unwrap();
+    let finalization = round_trip(&result.finalization);
+    server
+        .handle_password_change_finalization("pippo", "session", &finalization)
+        .unwrap();
+}
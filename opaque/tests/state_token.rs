@@ -0,0 +1,196 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rust::opaque_client_facade::{client_login_finish, client_login_start, OpaqueClient};
+use rust::opaque_config::ProtocolConfig;
+use rust::opaque_error::OpaqueError;
+use rust::opaque_login_state::{now_secs, MemoryReplayCache};
+use rust::opaque_seal::{seal, Kek, RecordType};
+use rust::opaque_server_facade::OpaqueServer;
+use rust::opaque_state_token::{
+    server_login_finish_stateless, server_login_start_stateless, unseal_login_state,
+};
+use rust::opaque_store::{CredentialStore, MemoryCredentialStore};
+
+const SERVERNAME: &str = "servername";
+const CONTEXT: &str = "context";
+const TTL: Duration = Duration::from_secs(60);
+
+fn kek(id: u32, byte: u8) -> Kek {
+    Kek::new(id, &[byte; 32]).unwrap()
+}
+
+// server setup and password file of pippo, registered with password ciao
+fn registered() -> (Vec<u8>, Vec<u8>) {
+    let store = Arc::new(MemoryCredentialStore::new());
+    let server = OpaqueServer::load_or_generate(
+        SERVERNAME.to_string(),
+        CONTEXT.to_string(),
+        Box::new(store.clone()),
+    )
+    .unwrap();
+
+    let mut client = OpaqueClient::new(
+        "pippo".to_string(),
+        SERVERNAME.to_string(),
+        CONTEXT.to_string(),
+    );
+    let request = client.begin_registration("ciao").unwrap();
+    let response = server
+        .handle_registration_request("pippo", &request)
+        .unwrap();
+    let result = client.complete_registration(&response).unwrap();
+    server
+        .handle_registration_upload("pippo", &result.upload)
+        .unwrap();
+
+    let password_file = store.get_password_file(b"pippo").unwrap().unwrap();
+    (server.setup(), password_file)
+}
+
+// login start of pippo under key: token and credential finalization of the client
+fn start(key: &Kek) -> (Vec<u8>, Vec<u8>) {
+    let (setup, password_file) = registered();
+    let client_start = client_login_start("ciao").unwrap();
+    let server_start = server_login_start_stateless(
+        key,
+        TTL,
+        "pippo".to_string(),
+        &password_file,
+        &client_start.credential_request,
+        &setup,
+        SERVERNAME.to_string(),
+        CONTEXT.to_string(),
+    );
+    let login = client_login_finish(
+        "ciao",
+        &server_start.response,
+        &client_start.state,
+        "pippo",
+        &ProtocolConfig::new(SERVERNAME, CONTEXT.as_bytes()),
+    )
+    .unwrap();
+    (server_start.state, login.credential_finalization)
+}
+
+#[test]
+fn login_round_trip() {
+    let key = kek(1, 7);
+    let (token, finalization) = start(&key);
+    let cache = MemoryReplayCache::new();
+
+    assert!(server_login_finish_stateless(
+        &key,
+        "pippo",
+        &finalization,
+        &token,
+        Some(&cache)
+    ));
+    // single use with a replay cache
+    assert!(!server_login_finish_stateless(
+        &key,
+        "pippo",
+        &finalization,
+        &token,
+        Some(&cache)
+    ));
+}
+
+#[test]
+fn token_is_bound_to_username_and_key() {
+    let key = kek(1, 7);
+    let (token, finalization) = start(&key);
+
+    assert!(!server_login_finish_stateless(
+        &key,
+        "pluto",
+        &finalization,
+        &token,
+        None
+    ));
+    assert_eq!(
+        unseal_login_state(&key, "pluto", &token),
+        Err(OpaqueError::DecryptionFailed)
+    );
+    assert!(!server_login_finish_stateless(
+        &kek(1, 8),
+        "pippo",
+        &finalization,
+        &token,
+        None
+    ));
+    assert!(matches!(
+        unseal_login_state(&kek(2, 7), "pippo", &token),
+        Err(OpaqueError::KekMismatch { .. })
+    ));
+
+    assert!(server_login_finish_stateless(
+        &key,
+        "pippo",
+        &finalization,
+        &token,
+        None
+    ));
+}
+
+#[test]
+fn expired_token_is_refused() {
+    let key = kek(1, 7);
+    let (token, finalization) = start(&key);
+
+    // same state, sealed with an expiry in the past
+    let state = unseal_login_state(&key, "pippo", &token).unwrap();
+    let mut plaintext = (now_secs() - 10).to_be_bytes().to_vec();
+    plaintext.extend_from_slice(&state);
+    let expired = seal(&key, RecordType::LoginState, "pippo", &plaintext);
+
+    assert_eq!(
+        unseal_login_state(&key, "pippo", &expired),
+        Err(OpaqueError::Expired)
+    );
+    assert!(!server_login_finish_stateless(
+        &key,
+        "pippo",
+        &finalization,
+        &expired,
+        None
+    ));
+}
+
+#[test]
+fn tampered_token_is_refused() {
+    let key = kek(1, 7);
+    let (mut token, finalization) = start(&key);
+    let last = token.len() - 1;
+    token[last] ^= 1;
+
+    assert_eq!(
+        unseal_login_state(&key, "pippo", &token),
+        Err(OpaqueError::DecryptionFailed)
+    );
+    assert!(!server_login_finish_stateless(
+        &key,
+        "pippo",
+        &finalization,
+        &token,
+        None
+    ));
+}
+
+#[test]
+fn failed_login_start_gives_no_token() {
+    let (setup, _) = registered();
+    let client_start = client_login_start("ciao").unwrap();
+    let server_start = server_login_start_stateless(
+        &kek(1, 7),
+        TTL,
+        "pippo".to_string(),
+        b"not a password file",
+        &client_start.credential_request,
+        &setup,
+        SERVERNAME.to_string(),
+        CONTEXT.to_string(),
+    );
+    assert!(server_start.response.is_empty());
+    assert!(server_start.state.is_empty());
+}
@@ -0,0 +1,118 @@
+// registration and login with the WebAssembly client against opaque-server:
+// run through "task test-wasm", which builds both first
+
+import { test, before, after } from "node:test";
+import assert from "node:assert/strict";
+import { spawn } from "node:child_process";
+import { createRequire } from "node:module";
+
+const require = createRequire(import.meta.url);
+const opaque = require("../pkg/opaque.js");
+
+const LISTEN = "127.0.0.1:8181";
+const SERVERNAME = "servername";
+const CONTEXT = "context";
+
+let server;
+
+const b64 = (bytes) => Buffer.from(bytes).toString("base64");
+const unb64 = (text) => new Uint8Array(Buffer.from(text, "base64"));
+
+async function post(path, body) {
+  const res = await fetch(`http://${LISTEN}${path}`, {
+    method: "POST",
+    body: JSON.stringify(body),
+  });
+  return { status: res.status, body: await res.json() };
+}
+
+async function register(username, password) {
+  const start = opaque.clientRegistrationStart(password);
+  const res = await post("/register/start", {
+    username,
+    registration_request: b64(start.response),
+  });
+  assert.equal(res.status, 200);
+
+  const result = await opaque.clientRegistrationFinish(
+    password,
+    unb64(res.body.registration_response),
+    start.state,
+    username,
+    SERVERNAME,
+  );
+  const fin = await post("/register/finish", {
+    username,
+    registration_upload: b64(result.upload),
+  });
+  assert.equal(fin.status, 200);
+}
+
+async function loginStart(username, password) {
+  const start = opaque.clientLoginStart(password);
+  const res = await post("/login/start", {
+    username,
+    credential_request: b64(start.response),
+  });
+  assert.equal(res.status, 200);
+  return { start, res: res.body };
+}
+
+before(async () => {
+  server = spawn("./target/release/opaque-server", [
+    "--listen", LISTEN,
+    "--servername", SERVERNAME,
+    "--context", CONTEXT,
+  ], { stdio: "inherit" });
+
+  // wait for the server to accept connections
+  for (let i = 0; i < 50; i++) {
+    try {
+      await fetch(`http://${LISTEN}/`);
+      return;
+    } catch {
+      await new Promise((resolve) => setTimeout(resolve, 100));
+    }
+  }
+  throw new Error("opaque-server did not start");
+});
+
+after(() => server.kill());
+
+test("happy path", async () => {
+  await register("pippo", "ciao");
+
+  const { start, res } = await loginStart("pippo", "ciao");
+  const login = await opaque.clientLoginFinish(
+    "ciao",
+    unb64(res.credential_response),
+    start.state,
+    "pippo",
+    SERVERNAME,
+    CONTEXT,
+  );
+  assert.equal(login.sessionKey.length > 0, true);
+
+  const fin = await post("/login/finish", {
+    username: "pippo",
+    session_id: res.session_id,
+    credential_finalization: b64(login.credentialFinalization),
+  });
+  assert.equal(fin.status, 200);
+});
+
+test("error path", async () => {
+  await register("pluto", "ciao");
+
+  const { start, res } = await loginStart("pluto", "wrong");
+  await assert.rejects(
+    opaque.clientLoginFinish(
+      "wrong",
+      unb64(res.credential_response),
+      start.state,
+      "pluto",
+      SERVERNAME,
+      CONTEXT,
+    ),
+  );
+});